@@ -9,7 +9,7 @@
 //! new memory settings.
 
 use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Write;
 use std::path::PathBuf;
 
@@ -28,4 +28,19 @@ fn main() {
     // here, we ensure the build script is only re-run when
     // `memory.x` is changed.
     println!("cargo:rerun-if-changed=memory.x");
+
+    // Generate the RTIC application of `examples/taskset.rs` from the
+    // task set description, `tasks/timing_exam.toml` unless overridden
+    // by the `TASKSET` environment variable.
+    let taskset = env::var("TASKSET").unwrap_or_else(|_| "tasks/timing_exam.toml".into());
+    // An invalid task set only fails that example, not every build.
+    let app = taskset::TaskSet::load(&taskset)
+        .and_then(|set| taskset::codegen::rtic_app(&set, &taskset))
+        .unwrap_or_else(|e| {
+            println!("cargo:warning={}: {}", taskset, e);
+            format!("compile_error!({:?});\n", format!("{}: {}", taskset, e))
+        });
+    fs::write(out.join("taskset.rs"), app).unwrap();
+    println!("cargo:rerun-if-changed={}", taskset);
+    println!("cargo:rerun-if-env-changed=TASKSET");
 }
@@ -0,0 +1,18 @@
+//! examples/taskset.rs
+//!
+//! The task set described by `tasks/timing_exam.toml`, emulated by an
+//! RTIC application generated by `build.rs` (see `host/taskset`).
+//!
+//! To run another task set:
+//!
+//! > TASKSET=tasks/my_set.toml cargo run --example taskset --release
+//!
+//! The generated monitors (`T1_RT`, `T1_DL`, ..., `R1_LOCK`, ...) can be
+//! watched in gdb as for `examples/timing_exam.rs`.
+
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+
+include!(concat!(env!("OUT_DIR"), "/taskset.rs"));
@@ -0,0 +1,62 @@
+//! examples/timing_calibrate.rs
+
+#![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+use rtic::cyccnt::U32Ext;
+use rtt_target::{rprintln, rtt_init_print};
+use stm32f4::stm32f411;
+use timing::{Cyccnt, CALIBRATION};
+
+#[rtic::app(device = stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    #[init(schedule = [calibrate])]
+    fn init(mut cx: init::Context) {
+        rtt_init_print!();
+
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+
+        CALIBRATION.measure_read(&Cyccnt);
+        cx.schedule.calibrate(cx.start + 10_000.cycles()).unwrap();
+    }
+
+    // Released while idle, so the release jitter is the dispatch overhead
+    #[task(priority = 1)]
+    fn calibrate(cx: calibrate::Context) {
+        CALIBRATION.record_dispatch(cx.scheduled.elapsed().as_cycles());
+        CALIBRATION.mark_pend(&Cyccnt);
+        rtic::pend(stm32f411::Interrupt::EXTI3);
+    }
+
+    // Preempts `calibrate`, pend to entry is the context switch
+    #[task(binds = EXTI3, priority = 2)]
+    fn switch(_cx: switch::Context) {
+        CALIBRATION.record_switch(&Cyccnt);
+        rprintln!("{}", CALIBRATION);
+    }
+
+    extern "C" {
+        fn EXTI0();
+    }
+};
+
+// Measurement overhead calibration.
+//
+// The calibrated overheads are subtracted by the `timing` monitors,
+// here we just report them to compare builds:
+//
+// > cargo run --example timing_calibrate
+// calibration (debug): read ...
+//
+// > cargo run --example timing_calibrate --release
+// calibration (release): read ...
+//
+// > cargo run --example timing_calibrate --release --features nightly
+// calibration (release, inline-asm): read ...
+//
+// (`examples/timing_exam.rs` runs the same calibration before its task set.)
@@ -5,17 +5,52 @@
 #![no_main]
 #![no_std]
 
-use cortex_m::{asm, peripheral::DWT};
+use cortex_m::peripheral::DWT;
 use panic_halt as _;
 use rtic::cyccnt::{Duration, Instant, U32Ext};
 use stm32f4::stm32f411;
+use timing::{
+    Accounting, Cyccnt, DeadlineMonitor, ExecutionMonitor, InstrumentedLock, JitterMonitor,
+    LockMonitor, Policy, RawLatency, ResponseTimeMonitor, CALIBRATION,
+};
 
 #[no_mangle]
-static mut T1_MAX_RP: u32 = 0;
+static T1_RT: ResponseTimeMonitor = ResponseTimeMonitor::new("t1");
 #[no_mangle]
-static mut T2_MAX_RP: u32 = 0;
+static T2_RT: ResponseTimeMonitor = ResponseTimeMonitor::new("t2");
 #[no_mangle]
-static mut T3_MAX_RP: u32 = 0;
+static T3_RT: ResponseTimeMonitor = ResponseTimeMonitor::new("t3");
+
+// Relative deadlines, a miss hits a `asm::bkpt()`
+// (use `Policy::Panic` to get "task non-schedulable: deadline miss!")
+#[no_mangle]
+static T1_DL: DeadlineMonitor = DeadlineMonitor::new("t1", 100_000, Policy::Breakpoint);
+#[no_mangle]
+static T2_DL: DeadlineMonitor = DeadlineMonitor::new("t2", 200_000, Policy::Breakpoint);
+#[no_mangle]
+static T3_DL: DeadlineMonitor = DeadlineMonitor::new("t3", 50_000, Policy::Breakpoint);
+
+// Release jitter (start latency), histograms in buckets of 1_000 cycles
+#[no_mangle]
+static T1_JIT: JitterMonitor = JitterMonitor::new("t1", 1_000);
+#[no_mangle]
+static T2_JIT: JitterMonitor = JitterMonitor::new("t2", 1_000);
+#[no_mangle]
+static T3_JIT: JitterMonitor = JitterMonitor::new("t3", 1_000);
+
+// Net execution time, preemption and blocking per task instance
+#[no_mangle]
+static ACCOUNTING: Accounting = Accounting::new();
+#[no_mangle]
+static T1_EXEC: ExecutionMonitor = ExecutionMonitor::new("t1");
+#[no_mangle]
+static T2_EXEC: ExecutionMonitor = ExecutionMonitor::new("t2");
+#[no_mangle]
+static T3_EXEC: ExecutionMonitor = ExecutionMonitor::new("t3");
+
+// Critical section length of R2 (ceiling 3), and the blocking it induces
+#[no_mangle]
+static R2_LOCK: LockMonitor = LockMonitor::new("R2", 3, &ACCOUNTING);
 
 #[rtic::app(device = stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
 const APP: () = {
@@ -26,11 +61,17 @@ const APP: () = {
         R2: u64, // non atomic data
     }
 
-    #[init(schedule = [t1, t2, t3])]
+    #[init(schedule = [t1, t2, t3, calibrate])]
     fn init(mut cx: init::Context) {
         // Initialize (enable) the monotonic timer (CYCCNT)
         cx.core.DCB.enable_trace();
         cx.core.DWT.enable_cycle_counter();
+
+        // Measurement overheads, subtracted by the monitors
+        // (see `examples/timing_calibrate.rs`)
+        CALIBRATION.measure_read(&Cyccnt);
+        cx.schedule.calibrate(cx.start + 10_000.cycles()).unwrap();
+
         cx.schedule.t1(cx.start + 100_000.cycles()).unwrap();
         cx.schedule.t2(cx.start + 200_000.cycles()).unwrap();
         cx.schedule.t3(cx.start + 50_000.cycles()).unwrap();
@@ -40,7 +81,11 @@ const APP: () = {
     #[inline(never)]
     #[task(schedule = [t1], priority = 1)]
     fn t1(cx: t1::Context) {
-        let start = cx.scheduled; 
+        let start = cx.scheduled;
+        // raw, the monitors subtract the overheads
+        let latency = start.elapsed().as_cycles();
+        T1_JIT.record(latency);
+        ACCOUNTING.enter(&Cyccnt, 1, RawLatency(latency));
         // asm::bkpt();
         cx.schedule.t1(cx.scheduled + 100_000.cycles()).unwrap();
         // asm::bkpt();
@@ -49,25 +94,21 @@ const APP: () = {
         cortex_m::asm::delay(10_000);
         // asm::bkpt();
 
-        // 2) your code here to update T1_MAX_RP and
+        // 2) your code here to update T1_RT and
         // break if deadline missed
-        let deadline = 100 * 1_000;
-        let prev_rt = unsafe { T1_MAX_RP };
-        let rt = start.elapsed().as_cycles();
-
-        if rt > prev_rt {
-            unsafe { T1_MAX_RP = rt };
-        } else if rt > deadline {
-            asm::bkpt();
-            // panic!("task non-schedulable: deadline miss!");
-        }
+        T1_EXEC.record(&ACCOUNTING.exit(&Cyccnt));
+        T1_DL.complete(&T1_RT, start.elapsed().as_cycles());
     }
 
     // Deadline 200, Inter-arrival 200
     #[inline(never)]
     #[task(schedule = [t2], resources = [R1, R2], priority = 2)]
     fn t2(mut cx: t2::Context) {
-        let start = cx.scheduled; 
+        let start = cx.scheduled;
+        // raw, the monitors subtract the overheads
+        let latency = start.elapsed().as_cycles();
+        T2_JIT.record(latency);
+        ACCOUNTING.enter(&Cyccnt, 2, RawLatency(latency));
         // asm::bkpt();
         cx.schedule.t2(cx.scheduled + 200_000.cycles()).unwrap();
         // asm::bkpt();
@@ -77,7 +118,7 @@ const APP: () = {
         cortex_m::asm::delay(10_000);
 
         cortex_m::asm::delay(2_000); // R1
-        cx.resources.R2.lock(|_| {
+        cx.resources.R2.lock_instrumented(&R2_LOCK, |_| {
             cortex_m::asm::delay(4_000); // R2
         });
         cortex_m::asm::delay(4_000);  //R1
@@ -88,25 +129,21 @@ const APP: () = {
         // asm::bkpt();
 
 
-        // 2) your code here to update T2_MAX_RP and
+        // 2) your code here to update T2_RT and
         // break if deadline missed
-        let deadline = 200 * 1_000;
-        let prev_rt = unsafe { T2_MAX_RP };
-        let rt = start.elapsed().as_cycles();
-
-        if rt > prev_rt {
-            unsafe { T2_MAX_RP = rt };
-        } else if rt > deadline {
-            asm::bkpt();
-            // panic!("task non-schedulable: deadline miss!");
-        }
+        T2_EXEC.record(&ACCOUNTING.exit(&Cyccnt));
+        T2_DL.complete(&T2_RT, start.elapsed().as_cycles());
     }
 
     // Deadline 50, Inter-arrival 50
     #[inline(never)]
     #[task(schedule = [t3], resources = [R2], priority = 3)]
     fn t3(cx: t3::Context) {
-        let start = cx.scheduled; 
+        let start = cx.scheduled;
+        // raw, the monitors subtract the overheads
+        let latency = start.elapsed().as_cycles();
+        T3_JIT.record(latency);
+        ACCOUNTING.enter(&Cyccnt, 3, RawLatency(latency));
         // asm::bkpt();
         cx.schedule.t3(cx.scheduled + 50_000.cycles()).unwrap();
         // asm::bkpt();
@@ -119,18 +156,24 @@ const APP: () = {
         // asm::bkpt();
 
 
-        // 2) your code here to update T3_MAX_RP and
+        // 2) your code here to update T3_RT and
         // break if deadline missed
-        let deadline = 50 * 1_000;
-        let prev_rt = unsafe { T3_MAX_RP };
-        let rt = start.elapsed().as_cycles();
+        T3_EXEC.record(&ACCOUNTING.exit(&Cyccnt));
+        T3_DL.complete(&T3_RT, start.elapsed().as_cycles());
+    }
+
+    // Released while idle, so the release jitter is the dispatch overhead
+    #[task(priority = 1)]
+    fn calibrate(cx: calibrate::Context) {
+        CALIBRATION.record_dispatch(cx.scheduled.elapsed().as_cycles());
+        CALIBRATION.mark_pend(&Cyccnt);
+        rtic::pend(stm32f411::Interrupt::EXTI3);
+    }
 
-        if rt > prev_rt {
-            unsafe { T3_MAX_RP = rt };
-        } else if rt > deadline {
-            asm::bkpt();
-            // panic!("task non-schedulable: deadline miss!");
-        }
+    // Preempts `calibrate`, pend to entry is the context switch
+    #[task(binds = EXTI3, priority = 4)]
+    fn switch(_cx: switch::Context) {
+        CALIBRATION.record_switch(&Cyccnt);
     }
 
     // RTIC requires that unused interrupts are declared in an extern block when
@@ -229,10 +272,18 @@ const APP: () = {
 //
 // You can put WATCHES in vscode for the symbols
 // WATCH
-//  `T1_MAX_RP`
-//  `T2_MAX_RP`
-//  `T3_MAX_RP`
+//  `T1_RT`
+//  `T2_RT`
+//  `T3_RT`
 // To see them being updated during the test.
+// (Each monitor holds count, min, max and sum of the response times,
+// in gdb `print T1_RT`. The `max` field corresponds to `T1_MAX_RP`.
+// Deadline misses are counted in `T1_DL`, `T2_DL` and `T3_DL`,
+// and the start latency (release jitter) in `T1_JIT`, `T2_JIT` and `T3_JIT`.
+// The split of the response time into net execution, preemption and
+// blocking is recorded in `T1_EXEC`, `T2_EXEC` and `T3_EXEC`, and the
+// hold time of R2 together with the blocking it induced in `R2_LOCK`.
+// All monitors subtract the overheads calibrated in `CALIBRATION`.)
 //
 // The first breakpoint hit should be:
 // fn t3(cx: t3::Context) {
@@ -351,6 +402,15 @@ const APP: () = {
 // If some task still misses its deadline go back and adjust
 // the timing until it just passes.
 //
+// (Alternatively replace `asm::delay(x)` by `timing::burn_cycles(x)`,
+// which compensates for the call overhead and the loop granularity
+// once calibrated by `BURN.calibrate(&Cyccnt)` in `init`,
+// see `examples/timing_burn.rs`.)
+//
+// (The margins of the task set, how far each WCET and critical
+// section may grow before some task misses its deadline, are given by
+// `cargo run -p srp_analysis -- --sensitivity` in `host`.)
+//
 // Commit your tuned task set.
 //
 // 5) Final remarks and learning outcomes.
@@ -0,0 +1,91 @@
+//! examples/timing_exceptions.rs
+
+// #![deny(unsafe_code)]
+#![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use cortex_m::asm;
+use panic_halt as _;
+use stm32f4::stm32f411;
+use timing::itm;
+
+#[rtic::app(device = stm32f411)]
+const APP: () = {
+    struct Resources {
+        #[init(0)]
+        shared: u64, // non atomic data
+    }
+
+    #[init]
+    fn init(mut cx: init::Context) {
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        // exception trace, forwarded by the ITM
+        itm::enable_exception_trace(&mut cx.core.DWT);
+        itm::enable(
+            &mut cx.core.ITM,
+            &itm::Config {
+                dwt: true,
+                ..itm::Config::default()
+            },
+        );
+        rtic::pend(stm32f411::Interrupt::EXTI1);
+    }
+
+    #[task(binds = EXTI0, resources = [shared], priority = 2)]
+    fn exti0(cx: exti0::Context) {
+        asm::delay(1_000);
+        *cx.resources.shared += 1;
+    }
+
+    #[task(binds = EXTI1, resources = [shared], priority = 1)]
+    fn exti1(mut cx: exti1::Context) {
+        asm::delay(1_000);
+        rtic::pend(stm32f411::Interrupt::EXTI0);
+        asm::delay(1_000);
+        cx.resources.shared.lock(|shared| {
+            // EXTI0 pended while in the critical section
+            rtic::pend(stm32f411::Interrupt::EXTI0);
+            asm::delay(1_000);
+            *shared += 1;
+        });
+        asm::delay(1_000);
+    }
+};
+
+// Interrupt entry and exit by exception trace.
+//
+// In `timing_task` and `timing_resources` the entry and exit of the
+// EXTI0/EXTI1 handlers are reconstructed by stepping through `bkpt`s.
+// Here the DWT traces them in hardware, a packet (timestamped by the
+// ITM) when a handler is entered, exited, and when returning to a
+// preempted handler (or to thread mode).
+//
+// Uncomment the `tpiu` and `itm` lines of `openocd.gdb` (the clock must
+// match the core clock, 16 MHz for this example), then
+//
+// > cargo run --example timing_exceptions --release
+//
+// and decode the capture on the host, naming the handlers by the vector
+// table of the image and the tasks of the application:
+//
+// > cd host
+// > cargo run -p itm -- --exceptions --elf ../target/thumbv7em-none-eabihf/release/examples/timing_exceptions --app ../examples/timing_exceptions.rs ../itm.txt
+//
+// You should find something like (your timestamps will differ):
+//
+//       1234 entered  exti1
+//       2263   entered  exti0
+//       3310   exited   exti0
+//       3318   returned exti1
+//       ...
+//
+// `exti0` preempts `exti1` when pended outside of the lock, and is
+// entered only when the lock is released when pended inside (the
+// ceiling of `shared` is 2). The gaps between the handler spans and the
+// `delay`s are the overheads of RTIC and the exception entry/exit.
+//
+// With `--events` the handlers are listed as task events, to be charted:
+//
+// > cargo run -p itm -- --exceptions --events --elf .. --app .. ../itm.txt | cargo run -p rtic_trace
@@ -0,0 +1,141 @@
+//! examples/timing_itm.rs
+
+// #![deny(unsafe_code)]
+// #![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use core::fmt::Write;
+use panic_halt as _;
+use rtic::cyccnt::U32Ext;
+use stm32f4::stm32f411;
+use timing::{
+    itm::{self, Port},
+    Cyccnt, TracedLock, Tracer,
+};
+
+// Task and resource ids of the trace
+const T1_ID: u8 = 0;
+const T2_ID: u8 = 1;
+const T3_ID: u8 = 2;
+const R1_ID: u8 = 0;
+const R2_ID: u8 = 1;
+
+static TRACE: Tracer<Port> = Tracer::new();
+
+#[rtic::app(device = stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    struct Resources {
+        #[init(0)]
+        R1: u64,
+        #[init(0)]
+        R2: u64,
+    }
+
+    #[init(schedule = [t1, t2, t3])]
+    fn init(mut cx: init::Context) {
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+
+        // text on port 0, binary trace on port 1
+        itm::enable(&mut cx.core.ITM, &itm::Config::default());
+        TRACE.init(Port::TRACE);
+        TRACE.task_name(T1_ID, "t1");
+        TRACE.task_name(T2_ID, "t2");
+        TRACE.task_name(T3_ID, "t3");
+        TRACE.resource_name(R1_ID, "R1", 2);
+        TRACE.resource_name(R2_ID, "R2", 3);
+        writeln!(Port::LOG, "init").ok();
+
+        cx.schedule.t1(cx.start + 100_000.cycles()).unwrap();
+        cx.schedule.t2(cx.start + 200_000.cycles()).unwrap();
+        cx.schedule.t3(cx.start + 50_000.cycles()).unwrap();
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        loop {
+            // report lost records now and then
+            cortex_m::asm::delay(10_000_000);
+            if TRACE.dropped() > 0 {
+                writeln!(Port::LOG, "trace: {} records dropped", TRACE.dropped()).ok();
+            }
+        }
+    }
+
+    // Deadline 100, Inter-arrival 100
+    #[task(schedule = [t1], priority = 1)]
+    fn t1(cx: t1::Context) {
+        TRACE.enter(&Cyccnt, T1_ID);
+        cx.schedule.t1(cx.scheduled + 100_000.cycles()).unwrap();
+        TRACE.schedule(&Cyccnt, T1_ID);
+        cortex_m::asm::delay(10_000);
+        TRACE.exit(&Cyccnt, T1_ID);
+    }
+
+    // Deadline 200, Inter-arrival 200
+    #[task(schedule = [t2], resources = [R1, R2], priority = 2)]
+    fn t2(cx: t2::Context) {
+        TRACE.enter(&Cyccnt, T2_ID);
+        cx.schedule.t2(cx.scheduled + 200_000.cycles()).unwrap();
+        TRACE.schedule(&Cyccnt, T2_ID);
+        let t2::Resources { mut R1, mut R2 } = cx.resources;
+        cortex_m::asm::delay(10_000);
+        R1.lock_traced(&TRACE, R1_ID, |_| {
+            cortex_m::asm::delay(2_000);
+            R2.lock_traced(&TRACE, R2_ID, |_| {
+                cortex_m::asm::delay(4_000);
+            });
+            cortex_m::asm::delay(4_000);
+        });
+        cortex_m::asm::delay(2_000);
+        R1.lock_traced(&TRACE, R1_ID, |_| {
+            cortex_m::asm::delay(6_000);
+        });
+        cortex_m::asm::delay(2_000);
+        TRACE.exit(&Cyccnt, T2_ID);
+    }
+
+    // Deadline 50, Inter-arrival 50
+    #[task(schedule = [t3], resources = [R2], priority = 3)]
+    fn t3(mut cx: t3::Context) {
+        TRACE.enter(&Cyccnt, T3_ID);
+        cx.schedule.t3(cx.scheduled + 50_000.cycles()).unwrap();
+        TRACE.schedule(&Cyccnt, T3_ID);
+        cortex_m::asm::delay(10_000);
+        cx.resources.R2.lock_traced(&TRACE, R2_ID, |_| {
+            cortex_m::asm::delay(10_000);
+        });
+        cortex_m::asm::delay(10_000);
+        TRACE.exit(&Cyccnt, T3_ID);
+    }
+
+    extern "C" {
+        fn EXTI0();
+        fn EXTI1();
+        fn EXTI2();
+    }
+};
+
+// Binary event tracing over the ITM.
+//
+// The records of `timing_trace`, but written to ITM stimulus port 1
+// (and the log to port 0) instead of RTT. The ITM timestamps the
+// packets in hardware (local timestamps), and the SWO output needs no
+// polling by the probe. On the other hand writes wait for room in the
+// ITM FIFO, at the SWO bit rate.
+//
+// Uncomment the `tpiu` and `itm` lines of `openocd.gdb` (8000000 must
+// match the core clock, 16 MHz for this example), then
+//
+// > cargo run --example timing_itm --release
+//
+// OpenOCD writes the raw SWO stream to `itm.txt`, decode it on the host:
+//
+// > cd host
+// > cargo run -p itm -- ../itm.txt
+// > cargo run -p itm -- --port 0 ../itm.txt
+// > cargo run -p itm -- --events ../itm.txt | cargo run -p rtic_trace
+//
+// Exception trace (DWT) packets are forwarded with `dwt: true` in the
+// `itm::Config`.
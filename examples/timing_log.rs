@@ -0,0 +1,109 @@
+//! examples/timing_log.rs
+
+#![deny(unsafe_code)]
+// #![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+use rtic::cyccnt::U32Ext;
+use rtt_target::{rtt_init, UpChannel};
+use stm32f4::stm32f411;
+use timing::{debug, error, info, warn, Cyccnt, Logger};
+
+const PERIOD: u32 = 100_000;
+
+static LOG: Logger<UpChannel, Cyccnt> = Logger::new(Cyccnt);
+
+#[rtic::app(device = stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    #[init(schedule = [t1])]
+    fn init(mut cx: init::Context) {
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+
+        let channels = rtt_init! {
+            up: {
+                0: { size: 1024 mode: NoBlockSkip name: "Log" }
+            }
+        };
+        LOG.init(channels.up.0);
+        info!(LOG, "init, period {} cycles", PERIOD);
+
+        cx.schedule.t1(cx.start + PERIOD.cycles()).unwrap();
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        loop {
+            cortex_m::asm::delay(10_000_000);
+            if LOG.dropped() > 0 {
+                error!(LOG, "{} messages dropped", LOG.dropped());
+            }
+        }
+    }
+
+    #[task(schedule = [t1], priority = 1)]
+    fn t1(cx: t1::Context) {
+        static mut COUNT: u32 = 0;
+        *COUNT += 1;
+        cx.schedule.t1(cx.scheduled + PERIOD.cycles()).unwrap();
+        // removed unless built with the `log-debug` feature
+        debug!(
+            LOG,
+            "t1: start latency {} cycles",
+            cx.scheduled.elapsed().as_cycles()
+        );
+        cortex_m::asm::delay(10_000 * (*COUNT % 4));
+        let response = cx.scheduled.elapsed().as_cycles();
+        info!(LOG, "t1: count {}, response {} cycles", *COUNT, response);
+        if response > 25_000 {
+            warn!(LOG, "t1: response {:#x} > {:#x}", response, 25_000u32);
+        }
+        if *COUNT % 8 == 0 {
+            info!(LOG, "t1: {} {:?}", "state", &[0xc0u8, 0xff, 0xee][..]);
+        }
+    }
+
+    extern "C" {
+        fn EXTI0();
+    }
+};
+
+// Deferred formatting logging.
+//
+// The format strings of `info!` and friends are interned in the `.log`
+// section of the image (not loaded to the device), only their index and
+// the raw arguments go over RTT, 7 bytes of header and a tag per
+// argument, no formatting on the target.
+//
+// > cargo run --example timing_log --release
+//
+// `probe-run` shows channel 0 as text, capture it with OpenOCD instead
+// (`openocd -f openocd.cfg`, then in gdb):
+//
+// (gdb) monitor rtt setup 0x20000000 0x20000 "SEGGER RTT"
+// (gdb) monitor rtt start
+// (gdb) monitor rtt server start 9090 0
+//
+// > nc localhost 9090 > log.bin
+//
+// and decode it on the host, by the format strings of the image:
+//
+// > cd host
+// > cargo run -p log -- --elf ../target/thumbv7em-none-eabi/release/examples/timing_log log.bin
+//
+// You should find something like (your timestamps will differ):
+//
+//        1093 INFO  init, period 100000 cycles
+//      101342 INFO  t1: count 1, response 10210 cycles
+//      201342 INFO  t1: count 2, response 20210 cycles
+//      301342 INFO  t1: count 3, response 30210 cycles
+//      301401 WARN  t1: response 0x7602 > 0x61a8
+//      ...
+//
+// The `debug!` message is not in the image at all, build with
+//
+// > cargo run --example timing_log --release --features log-debug
+//
+// to include it.
@@ -0,0 +1,97 @@
+//! examples/timing_soak.rs
+
+// #![deny(unsafe_code)]
+// #![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use core::{
+    convert::TryFrom,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use panic_halt as _;
+use stm32f4::stm32f411;
+use timing::{
+    instant::{Duration, Instant, U32Ext},
+    DeadlineMonitor, Policy, ResponseTimeMonitor,
+};
+
+// A period beyond 2^32 cycles (~625s at the default 16MHz)
+const LONG_PERIOD: u64 = 10_000_000_000;
+
+#[no_mangle]
+static T1_RT: ResponseTimeMonitor = ResponseTimeMonitor::new("t1");
+#[no_mangle]
+static T1_DL: DeadlineMonitor = DeadlineMonitor::new("t1", 100_000, Policy::Breakpoint);
+
+// Lateness of the long period task w.r.t. its (64 bit) release time
+#[no_mangle]
+static LONG_RT: ResponseTimeMonitor = ResponseTimeMonitor::new("long");
+
+// Responses beyond 2^32 cycles, recorded as `u32::MAX`
+#[no_mangle]
+static OVERFLOWS: AtomicU32 = AtomicU32::new(0);
+
+/// The response since `scheduled` in 32 bits (as recorded by the monitors),
+/// saturated and counted in `OVERFLOWS` beyond.
+fn response(scheduled: Instant) -> u32 {
+    u32::try_from(scheduled.elapsed()).unwrap_or_else(|_| {
+        OVERFLOWS.fetch_add(1, Ordering::Relaxed);
+        u32::MAX
+    })
+}
+
+#[rtic::app(device = stm32f411, monotonic = timing::instant::CYCCNT64)]
+const APP: () = {
+    #[init(schedule = [t1, long])]
+    fn init(mut cx: init::Context) {
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        cx.schedule.t1(cx.start + 100_000.cycles()).unwrap();
+        cx.schedule
+            .long(cx.start + Duration::from_cycles(LONG_PERIOD))
+            .unwrap();
+    }
+
+    // Deadline 100, Inter-arrival 100
+    #[task(schedule = [t1], priority = 1)]
+    fn t1(cx: t1::Context) {
+        cx.schedule.t1(cx.scheduled + 100_000.cycles()).unwrap();
+        cortex_m::asm::delay(10_000);
+
+        T1_DL.complete(&T1_RT, response(cx.scheduled));
+    }
+
+    #[task(schedule = [long], priority = 2)]
+    fn long(cx: long::Context) {
+        cx.schedule
+            .long(cx.scheduled + Duration::from_cycles(LONG_PERIOD))
+            .unwrap();
+
+        LONG_RT.record(response(cx.scheduled));
+    }
+
+    extern "C" {
+        fn EXTI0();
+        fn EXTI1();
+    }
+};
+
+// Soak testing with 64 bit time stamps.
+//
+// `rtic::cyccnt::CYCCNT` is a wrapping 32 bit counter, intervals
+// beyond 2^32 cycles cannot be measured (nor scheduled). Here the
+// monotonic is `timing::instant::CYCCNT64`, which extends CYCCNT to
+// 64 bits by tracking overflows. As long as tasks are scheduled,
+// the timer queue (SysTick) reads the counter often enough.
+//
+// > cargo run --example timing_soak --release
+//
+// Let it run (for hours), and WATCH
+//  `T1_RT`, `T1_DL`, `LONG_RT` and `OVERFLOWS`
+//
+// `LONG_RT.max` should stay in the range of the dispatch overhead,
+// although the `long` task is released every 10_000_000_000 cycles.
+// `OVERFLOWS` should stay 0, a response beyond 2^32 cycles would not fit
+// the 32 bit monitors (it is recorded as `u32::MAX`, a deadline miss).
@@ -0,0 +1,147 @@
+//! examples/timing_trace.rs
+
+// #![deny(unsafe_code)]
+// #![deny(warnings)]
+#![no_main]
+#![no_std]
+
+use panic_halt as _;
+use rtic::cyccnt::U32Ext;
+use rtt_target::{rprintln, rtt_init, UpChannel};
+use stm32f4::stm32f411;
+use timing::{Cyccnt, TracedLock, Tracer};
+
+// Task and resource ids of the trace
+const T1_ID: u8 = 0;
+const T2_ID: u8 = 1;
+const T3_ID: u8 = 2;
+const R1_ID: u8 = 0;
+const R2_ID: u8 = 1;
+
+static TRACE: Tracer<UpChannel> = Tracer::new();
+
+#[rtic::app(device = stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+    struct Resources {
+        #[init(0)]
+        R1: u64,
+        #[init(0)]
+        R2: u64,
+    }
+
+    #[init(schedule = [t1, t2, t3])]
+    fn init(mut cx: init::Context) {
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+
+        // text on channel 0, binary trace on channel 1
+        let channels = rtt_init! {
+            up: {
+                0: { size: 1024 mode: NoBlockSkip name: "Terminal" }
+                1: { size: 4096 mode: NoBlockSkip name: "Trace" }
+            }
+        };
+        rtt_target::set_print_channel(channels.up.0);
+        TRACE.init(channels.up.1);
+        TRACE.task_name(T1_ID, "t1");
+        TRACE.task_name(T2_ID, "t2");
+        TRACE.task_name(T3_ID, "t3");
+        TRACE.resource_name(R1_ID, "R1", 2);
+        TRACE.resource_name(R2_ID, "R2", 3);
+        rprintln!("init");
+
+        cx.schedule.t1(cx.start + 100_000.cycles()).unwrap();
+        cx.schedule.t2(cx.start + 200_000.cycles()).unwrap();
+        cx.schedule.t3(cx.start + 50_000.cycles()).unwrap();
+    }
+
+    #[idle]
+    fn idle(_cx: idle::Context) -> ! {
+        loop {
+            // report lost records now and then
+            cortex_m::asm::delay(10_000_000);
+            if TRACE.dropped() > 0 {
+                rprintln!("trace: {} records dropped", TRACE.dropped());
+            }
+        }
+    }
+
+    // Deadline 100, Inter-arrival 100
+    #[task(schedule = [t1], priority = 1)]
+    fn t1(cx: t1::Context) {
+        TRACE.enter(&Cyccnt, T1_ID);
+        cx.schedule.t1(cx.scheduled + 100_000.cycles()).unwrap();
+        TRACE.schedule(&Cyccnt, T1_ID);
+        cortex_m::asm::delay(10_000);
+        TRACE.exit(&Cyccnt, T1_ID);
+    }
+
+    // Deadline 200, Inter-arrival 200
+    #[task(schedule = [t2], resources = [R1, R2], priority = 2)]
+    fn t2(cx: t2::Context) {
+        TRACE.enter(&Cyccnt, T2_ID);
+        cx.schedule.t2(cx.scheduled + 200_000.cycles()).unwrap();
+        TRACE.schedule(&Cyccnt, T2_ID);
+        let t2::Resources { mut R1, mut R2 } = cx.resources;
+        cortex_m::asm::delay(10_000);
+        R1.lock_traced(&TRACE, R1_ID, |_| {
+            cortex_m::asm::delay(2_000);
+            R2.lock_traced(&TRACE, R2_ID, |_| {
+                cortex_m::asm::delay(4_000);
+            });
+            cortex_m::asm::delay(4_000);
+        });
+        cortex_m::asm::delay(2_000);
+        R1.lock_traced(&TRACE, R1_ID, |_| {
+            cortex_m::asm::delay(6_000);
+        });
+        cortex_m::asm::delay(2_000);
+        TRACE.exit(&Cyccnt, T2_ID);
+    }
+
+    // Deadline 50, Inter-arrival 50
+    #[task(schedule = [t3], resources = [R2], priority = 3)]
+    fn t3(mut cx: t3::Context) {
+        TRACE.enter(&Cyccnt, T3_ID);
+        cx.schedule.t3(cx.scheduled + 50_000.cycles()).unwrap();
+        TRACE.schedule(&Cyccnt, T3_ID);
+        cortex_m::asm::delay(10_000);
+        cx.resources.R2.lock_traced(&TRACE, R2_ID, |_| {
+            cortex_m::asm::delay(10_000);
+        });
+        cortex_m::asm::delay(10_000);
+        TRACE.exit(&Cyccnt, T3_ID);
+    }
+
+    extern "C" {
+        fn EXTI0();
+        fn EXTI1();
+        fn EXTI2();
+    }
+};
+
+// Binary event tracing.
+//
+// `rprintln!` formats on the target, at the priority of the task, which
+// is far too intrusive for timing work. Here each event is a 6 byte
+// record (tag, id and CYCCNT), written to a separate RTT up-channel.
+//
+// > cargo run --example timing_trace --release
+//
+// `probe-run` only shows channel 0, capture channel 1 with OpenOCD
+// (`openocd -f openocd.cfg`, then in gdb):
+//
+// (gdb) monitor rtt setup 0x20000000 0x20000 "SEGGER RTT"
+// (gdb) monitor rtt start
+// (gdb) monitor rtt server start 9091 1
+//
+// > nc localhost 9091 > trace.bin
+//
+// and decode/chart it on the host:
+//
+// > cd host
+// > cargo run -p rtic_trace -- --binary --events trace.bin
+// > cargo run -p rtic_trace -- --binary --from 200000 --to 300000 trace.bin
+//
+// Task exits are traced at the end of the task body, the dispatch and
+// exit overheads of RTIC show up as (short) gaps in the chart.
@@ -0,0 +1,233 @@
+//! host/elf/src/lib.rs
+//!
+//! A minimal reader of the (32 bit, little endian) ELF images of the
+//! firmware: the sections, their contents by address, and the symbols.
+//!
+//! Enough to look up the vector table (`host/itm`) and the data of the
+//! firmware, without the dependencies of a general object file reader.
+
+use std::fmt;
+
+/// Section type, program data.
+pub const SHT_PROGBITS: u32 = 1;
+/// Section type, symbol table.
+pub const SHT_SYMTAB: u32 = 2;
+/// Section type, no data in the file (`.bss`).
+pub const SHT_NOBITS: u32 = 8;
+
+/// Symbol type, data object.
+pub const STT_OBJECT: u8 = 1;
+/// Symbol type, function.
+pub const STT_FUNC: u8 = 2;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    NotElf,
+    /// Not a 32 bit little endian image.
+    Unsupported,
+    /// A header or table out of the bounds of the file.
+    Truncated,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Error::NotElf => "not an ELF file",
+            Error::Unsupported => "not a 32 bit little endian ELF file",
+            Error::Truncated => "ELF file truncated",
+        })
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub name: String,
+    pub kind: u32,
+    pub addr: u32,
+    pub offset: u32,
+    pub size: u32,
+    pub link: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// Address, with the Thumb bit for functions.
+    pub value: u32,
+    pub size: u32,
+    pub kind: u8,
+    /// Index of the section of the symbol.
+    pub section: u16,
+}
+
+impl Symbol {
+    /// The address of the symbol, without the Thumb bit.
+    pub fn addr(&self) -> u32 {
+        match self.kind {
+            STT_FUNC => self.value & !1,
+            _ => self.value,
+        }
+    }
+}
+
+pub struct Elf<'a> {
+    bytes: &'a [u8],
+    pub sections: Vec<Section>,
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Result<u16, Error> {
+    let b = bytes.get(at..at + 2).ok_or(Error::Truncated)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Result<u32, Error> {
+    let b = bytes.get(at..at + 4).ok_or(Error::Truncated)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+/// The zero terminated string at `at` of a string table.
+fn string(table: &[u8], at: usize) -> String {
+    let s = table.get(at..).unwrap_or_default();
+    let end = s.iter().position(|&b| b == 0).unwrap_or(s.len());
+    String::from_utf8_lossy(&s[..end]).into_owned()
+}
+
+impl<'a> Elf<'a> {
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.get(..4) != Some(b"\x7fELF") {
+            return Err(Error::NotElf);
+        }
+        // class 32 bit, data little endian
+        if bytes.get(4..6) != Some(&[1, 1]) {
+            return Err(Error::Unsupported);
+        }
+        let shoff = u32_at(bytes, 0x20)? as usize;
+        let shentsize = u16_at(bytes, 0x2e)? as usize;
+        let shnum = u16_at(bytes, 0x30)? as usize;
+        let shstrndx = u16_at(bytes, 0x32)? as usize;
+        let mut sections = vec![];
+        let mut names = vec![];
+        for i in 0..shnum {
+            let at = shoff + i * shentsize;
+            names.push(u32_at(bytes, at)? as usize);
+            sections.push(Section {
+                name: String::new(),
+                kind: u32_at(bytes, at + 4)?,
+                addr: u32_at(bytes, at + 12)?,
+                offset: u32_at(bytes, at + 16)?,
+                size: u32_at(bytes, at + 20)?,
+                link: u32_at(bytes, at + 24)?,
+            });
+        }
+        let mut elf = Elf { bytes, sections };
+        if let Some(strtab) = elf.sections.get(shstrndx) {
+            let table = elf.data(strtab)?;
+            for (s, &name) in elf.sections.iter_mut().zip(&names) {
+                s.name = string(table, name);
+            }
+        }
+        Ok(elf)
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    /// The contents of `section`, empty for `.bss` like sections.
+    pub fn data(&self, section: &Section) -> Result<&'a [u8], Error> {
+        if section.kind == SHT_NOBITS {
+            return Ok(&[]);
+        }
+        let start = section.offset as usize;
+        self.bytes
+            .get(start..start + section.size as usize)
+            .ok_or(Error::Truncated)
+    }
+
+    /// The `len` bytes at address `addr`, if within a section with data.
+    pub fn read(&self, addr: u32, len: usize) -> Option<&'a [u8]> {
+        self.sections
+            .iter()
+            .filter(|s| s.kind == SHT_PROGBITS && s.addr != 0)
+            .find(|s| addr >= s.addr && (addr - s.addr) as usize + len <= s.size as usize)
+            .and_then(|s| {
+                let data = self.data(s).ok()?;
+                let at = (addr - s.addr) as usize;
+                data.get(at..at + len)
+            })
+    }
+
+    /// The symbols, without the ARM mapping symbols (`$t`, `$d`, ..).
+    pub fn symbols(&self) -> Result<Vec<Symbol>, Error> {
+        let mut symbols = vec![];
+        for symtab in self.sections.iter().filter(|s| s.kind == SHT_SYMTAB) {
+            let data = self.data(symtab)?;
+            let strtab = self
+                .sections
+                .get(symtab.link as usize)
+                .ok_or(Error::Truncated)?;
+            let names = self.data(strtab)?;
+            for entry in data.chunks_exact(16) {
+                let name = string(names, u32_at(entry, 0)? as usize);
+                if name.is_empty() || name.starts_with('$') {
+                    continue;
+                }
+                symbols.push(Symbol {
+                    name,
+                    value: u32_at(entry, 4)?,
+                    size: u32_at(entry, 8)?,
+                    kind: entry[12] & 0xf,
+                    section: u16_at(entry, 14)?,
+                });
+            }
+        }
+        Ok(symbols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fixtures::elf::timing_itm;
+
+    // a minimal image, the vector table and symbols of `timing_itm`, as
+    // committed
+    const IMAGE: &[u8] = include_bytes!("../../itm/captures/timing_itm.elf");
+
+    #[test]
+    fn image() {
+        let elf = Elf::parse(IMAGE).unwrap();
+        let vectors = elf.section(".vector_table").unwrap();
+        assert_eq!((vectors.addr, vectors.size), (0x0800_0000, 0x198));
+        // the initial stack pointer and the reset vector
+        assert_eq!(elf.read(0x0800_0000, 4), Some(&[0, 0, 2, 0x20][..]));
+        let reset = u32_at(elf.read(0x0800_0004, 4).unwrap(), 0).unwrap();
+        let symbols = elf.symbols().unwrap();
+        let s = symbols.iter().find(|s| s.name == "Reset").unwrap();
+        assert_eq!((s.kind, s.addr(), s.value), (STT_FUNC, reset & !1, reset));
+        // no mapping symbols
+        assert!(symbols.iter().all(|s| !s.name.starts_with('$')));
+        // out of bounds
+        assert_eq!(elf.read(0x0800_0196, 4), None);
+        assert_eq!(elf.read(0x2000_0000, 4), None);
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(Elf::parse(b"\x7fELG").err(), Some(Error::NotElf));
+        assert_eq!(
+            Elf::parse(b"\x7fELF\x02\x01").err(),
+            Some(Error::Unsupported)
+        );
+        let image = timing_itm();
+        assert_eq!(Elf::parse(&image[..0x30]).err(), Some(Error::Truncated));
+        // section headers cut off
+        assert_eq!(
+            Elf::parse(&image[..image.len() - 20]).err(),
+            Some(Error::Truncated)
+        );
+    }
+}
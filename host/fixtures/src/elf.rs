@@ -0,0 +1,222 @@
+//! host/fixtures/src/elf.rs
+//!
+//! Minimal ELF32 ARM images, the sections and symbols of an image as
+//! laid out by `cortex-m-rt`, without program headers.
+
+const PROGBITS: u32 = 1;
+const SYMTAB: u32 = 2;
+const STRTAB: u32 = 3;
+const NOBITS: u32 = 8;
+
+const WRITE: u32 = 1;
+const ALLOC: u32 = 2;
+const EXECINSTR: u32 = 4;
+
+// symbol types
+pub const NOTYPE: u8 = 0;
+pub const OBJECT: u8 = 1;
+pub const FUNC: u8 = 2;
+
+pub enum Contents {
+    Bits(Vec<u8>),
+    /// Size of a `NOBITS` section (`.bss`).
+    NoBits(u32),
+}
+
+pub struct Section {
+    pub name: &'static str,
+    pub addr: u32,
+    pub contents: Contents,
+}
+
+pub struct Symbol {
+    pub name: String,
+    pub value: u32,
+    pub size: u32,
+    pub kind: u8,
+    pub section: &'static str,
+}
+
+impl Symbol {
+    pub fn new(name: &str, value: u32, size: u32, kind: u8, section: &'static str) -> Self {
+        Symbol {
+            name: name.into(),
+            value,
+            size,
+            kind,
+            section,
+        }
+    }
+
+    /// Mapping symbols (`$t`, `$d`) are local, first in the table.
+    fn local(&self) -> bool {
+        self.name.starts_with('$')
+    }
+}
+
+/// Appends `s` to a string table, its offset.
+fn add(table: &mut Vec<u8>, s: &str) -> u32 {
+    let at = table.len() as u32;
+    table.extend_from_slice(s.as_bytes());
+    table.push(0);
+    at
+}
+
+fn words(out: &mut Vec<u8>, words: &[u32]) {
+    for w in words {
+        out.extend_from_slice(&w.to_le_bytes());
+    }
+}
+
+/// The image of `sections` (after the null section), followed by the
+/// symbol, string and section name tables.
+pub fn build(sections: &[Section], symbols: &[Symbol]) -> Vec<u8> {
+    let (mut shstr, mut strtab) = (vec![0], vec![0]);
+    let mut out = vec![0; 52];
+    let mut headers = vec![[0; 10]];
+    for s in sections {
+        let offset = out.len() as u32;
+        let (kind, size) = match &s.contents {
+            Contents::Bits(data) => {
+                out.extend_from_slice(data);
+                (PROGBITS, data.len() as u32)
+            }
+            Contents::NoBits(size) => (NOBITS, *size),
+        };
+        let flags = ALLOC | if s.name == ".text" { EXECINSTR } else { WRITE };
+        let name = add(&mut shstr, s.name);
+        headers.push([name, kind, flags, s.addr, offset, size, 0, 0, 4, 0]);
+    }
+    let mut symtab = vec![0; 16];
+    for s in symbols {
+        let index = 1 + sections.iter().position(|x| x.name == s.section).unwrap() as u16;
+        let bind = if s.local() { 0 } else { 1 };
+        words(&mut symtab, &[add(&mut strtab, &s.name), s.value, s.size]);
+        symtab.extend_from_slice(&[bind << 4 | s.kind, 0]);
+        symtab.extend_from_slice(&index.to_le_bytes());
+    }
+    let locals = 1 + symbols.iter().filter(|s| s.local()).count() as u32;
+    let strtab_index = headers.len() as u32 + 1;
+    for (name, kind, table, link, info, align, entry) in [
+        (".symtab", SYMTAB, symtab, strtab_index, locals, 4, 16),
+        (".strtab", STRTAB, strtab, 0, 0, 1, 0),
+    ] {
+        let name = add(&mut shstr, name);
+        headers.push([
+            name,
+            kind,
+            0,
+            0,
+            out.len() as u32,
+            table.len() as u32,
+            link,
+            info,
+            align,
+            entry,
+        ]);
+        out.extend_from_slice(&table);
+    }
+    let shstrndx = headers.len() as u16;
+    let name = add(&mut shstr, ".shstrtab");
+    headers.push([
+        name,
+        STRTAB,
+        0,
+        0,
+        out.len() as u32,
+        shstr.len() as u32,
+        0,
+        0,
+        1,
+        0,
+    ]);
+    out.extend_from_slice(&shstr);
+    // word aligned section headers
+    out.resize((out.len() + 3) & !3, 0);
+    let shoff = out.len() as u32;
+    for h in &headers {
+        words(&mut out, h);
+    }
+
+    let mut header = b"\x7fELF\x01\x01\x01".to_vec();
+    header.resize(16, 0);
+    // executable, ARM, version 1
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&40u16.to_le_bytes());
+    // version, entry, program and section headers, flags (EABI 5, hard float)
+    words(&mut header, &[1, 0x0800_0199, 0x34, shoff, 0x0500_0400]);
+    for half in &[52, 32, 0, 40, headers.len() as u16, shstrndx] {
+        header.extend_from_slice(&half.to_le_bytes());
+    }
+    out[..52].copy_from_slice(&header);
+    out
+}
+
+/// An image of `examples/timing_itm.rs`: the vector table (SysTick and
+/// the EXTI0..2 dispatchers, the rest `DefaultHandler`), a `.text` of
+/// 32 byte functions (`bkpt`) named as by rustc, and the `TRACE` tracer
+/// in `.bss`.
+pub fn timing_itm() -> Vec<u8> {
+    const TEXT: u32 = 0x0800_0198;
+    let functions = [
+        "Reset",
+        "main",
+        "DefaultHandler",
+        "HardFaultTrampoline",
+        "HardFault",
+        "SysTick",
+        "EXTI0",
+        "EXTI1",
+        "EXTI2",
+        "_ZN10timing_itm3APP2t117h1c2d3e4f5a6b7c8dE",
+        "_ZN10timing_itm3APP2t217h2c2d3e4f5a6b7c8dE",
+        "_ZN10timing_itm3APP2t317h3c2d3e4f5a6b7c8dE",
+    ];
+    let addr = |f: &str| TEXT + 0x20 * functions.iter().position(|&g| g == f).unwrap() as u32;
+    let mut vectors = vec![0x2002_0000, addr("Reset") | 1];
+    for n in 2..102 {
+        vectors.push(match n {
+            7..=10 | 13 => 0,
+            3 => addr("HardFaultTrampoline") | 1,
+            15 => addr("SysTick") | 1,
+            22..=24 => addr(&format!("EXTI{}", n - 22)) | 1,
+            _ => addr("DefaultHandler") | 1,
+        });
+    }
+    let mut table = vec![];
+    words(&mut table, &vectors);
+    let sections = [
+        Section {
+            name: ".vector_table",
+            addr: 0x0800_0000,
+            contents: Contents::Bits(table),
+        },
+        Section {
+            name: ".text",
+            addr: TEXT,
+            // bkpt
+            contents: Contents::Bits([0x00, 0xbe].repeat(0x10 * functions.len())),
+        },
+        Section {
+            name: ".bss",
+            addr: 0x2000_0000,
+            contents: Contents::NoBits(0x100),
+        },
+    ];
+    let mut symbols = vec![
+        Symbol::new("$t", TEXT, 0, NOTYPE, ".text"),
+        Symbol::new("$d", 0x0800_0000, 0, NOTYPE, ".vector_table"),
+        Symbol::new("__RESET_VECTOR", 0x0800_0004, 4, OBJECT, ".vector_table"),
+        Symbol::new(
+            "_ZN10timing_itm5TRACE17h4c2d3e4f5a6b7c8dE",
+            0x2000_0000,
+            12,
+            OBJECT,
+            ".bss",
+        ),
+    ];
+    for f in &functions {
+        symbols.push(Symbol::new(f, addr(f) | 1, 0x20, FUNC, ".text"));
+    }
+    build(&sections, &symbols)
+}
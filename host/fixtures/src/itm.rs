@@ -0,0 +1,153 @@
+//! host/fixtures/src/itm.rs
+//!
+//! Raw SWO captures (as written by OpenOCD to `itm.txt`), of
+//! `examples/timing_itm.rs`.
+
+use crate::trace::{self, EXAM};
+
+/// Stimulus ports of the firmware.
+const LOG: u8 = 0;
+const TRACE: u8 = 1;
+
+/// Exception numbers.
+const SYS_TICK: u16 = 15;
+const EXTI0: u16 = 22;
+const EXTI1: u16 = 23;
+
+// exception trace functions
+const ENTERED: u8 = 1;
+const EXITED: u8 = 2;
+const RETURNED: u8 = 3;
+
+/// A capture under construction.
+#[derive(Default)]
+pub struct Swo {
+    pub bytes: Vec<u8>,
+    /// Time of the last local timestamp.
+    time: u32,
+}
+
+impl Swo {
+    pub fn sync(&mut self) {
+        self.bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0x80]);
+    }
+
+    /// Writes to stimulus `port`, by words, a half word and a byte as the
+    /// ITM port writer.
+    pub fn stimulus(&mut self, port: u8, data: &[u8]) {
+        let mut chunks = data.chunks_exact(4);
+        for word in &mut chunks {
+            self.bytes.push(port << 3 | 3);
+            self.bytes.extend_from_slice(word);
+        }
+        let rest = chunks.remainder();
+        let (half, byte) = rest.split_at(rest.len() & 2);
+        if !half.is_empty() {
+            self.bytes.push(port << 3 | 2);
+            self.bytes.extend_from_slice(half);
+        }
+        if let [b] = byte {
+            self.bytes.extend_from_slice(&[port << 3 | 1, *b]);
+        }
+    }
+
+    /// Exception trace packet.
+    pub fn exception(&mut self, number: u16, function: u8) {
+        self.bytes.extend_from_slice(&[
+            0x0e,
+            number as u8,
+            (number >> 8) as u8 & 1 | function << 4,
+        ]);
+    }
+
+    /// Local timestamp at `time`, `tc` the relation to the packets.
+    pub fn timestamp(&mut self, time: u32, tc: u8) {
+        let mut delta = time - self.time;
+        self.time = time;
+        if tc == 0 && 0 < delta && delta < 7 {
+            self.bytes.push((delta as u8) << 4);
+            return;
+        }
+        self.bytes.push(0xc0 | tc << 4);
+        loop {
+            let b = (delta & 0x7f) as u8;
+            delta >>= 7;
+            if delta == 0 {
+                self.bytes.push(b);
+                break;
+            }
+            self.bytes.push(b | 0x80);
+        }
+    }
+
+    /// Overflow packet.
+    pub fn overflow(&mut self) {
+        self.bytes.push(0x70);
+    }
+}
+
+/// The exam traced over the ITM, the records of [`trace::exam`] on
+/// [`TRACE`] interleaved with the exception trace of the dispatchers
+/// (EXTI0 for t3, EXTI1 for t2) and SysTick, timestamped, lost packets
+/// reported at the end.
+pub fn capture() -> Vec<u8> {
+    enum What {
+        Record(u8, u8, u32),
+        Exception(u16, u8),
+    }
+    let mut events: Vec<(u32, What)> = EXAM
+        .iter()
+        .map(|&(tag, id, time)| (time, What::Record(tag, id, time)))
+        .collect();
+    for &(time, number, function) in &[
+        (200_000, SYS_TICK, ENTERED),
+        (200_100, SYS_TICK, EXITED),
+        (200_110, EXTI1, ENTERED),
+        (217_900, SYS_TICK, ENTERED),
+        (218_100, SYS_TICK, EXITED),
+        (218_110, EXTI0, ENTERED),
+        (248_340, EXTI0, EXITED),
+        (248_350, EXTI1, RETURNED),
+        (260_010, EXTI1, EXITED),
+        (260_020, 0, RETURNED),
+    ] {
+        events.push((time, What::Exception(number, function)));
+    }
+    // stable, records before exceptions at the same time
+    events.sort_by_key(|&(time, _)| time);
+
+    let mut swo = Swo::default();
+    swo.sync();
+    swo.stimulus(LOG, b"init\n");
+    swo.timestamp(1_200, 0);
+    swo.stimulus(TRACE, &trace::exam_names());
+    swo.timestamp(1_500, 0);
+    for (time, what) in events {
+        match what {
+            What::Record(tag, id, time) => {
+                let mut record = vec![tag, id];
+                record.extend_from_slice(&time.to_le_bytes());
+                swo.stimulus(TRACE, &record);
+            }
+            What::Exception(number, function) => swo.exception(number, function),
+        }
+        swo.timestamp(time, 0);
+    }
+    swo.overflow();
+    // delayed by the overflow
+    swo.timestamp(270_000, 1);
+    swo.sync();
+    swo.bytes
+}
+
+/// A text log on [`LOG`] without timestamps, the capture stopped within
+/// a packet.
+pub fn log() -> Vec<u8> {
+    let mut swo = Swo::default();
+    swo.sync();
+    for line in &["init\n", "t1: sample 40632\n", "t3: sample 30362\n"] {
+        swo.stimulus(LOG, line.as_bytes());
+    }
+    swo.bytes.extend_from_slice(&[LOG << 3 | 3, b't', b'1']);
+    swo.bytes
+}
@@ -0,0 +1,63 @@
+//! host/fixtures/src/lib.rs
+//!
+//! The captures of the host tests and examples (`*/captures`), generated
+//! here rather than recorded on target, so that they can be reviewed and
+//! rebuilt. Each reproduces the encoding of the firmware side (also
+//! checked by the `capture` tests of `timing`) for a short run of the
+//! corresponding example:
+//!
+//! - `rtic_trace/captures/exam.bin`, [`trace::exam`].
+//! - `itm/captures/itm.txt`, [`itm::capture`], the same run over the ITM.
+//! - `itm/captures/log.txt`, [`itm::log`].
+//! - `itm/captures/timing_itm.elf`, [`elf::timing_itm`], the sections and
+//!   symbols of the image of the same run.
+//! - `log/captures/timing_log.elf`, [`log::timing_log`], the `.log`
+//!   section of `examples/timing_log.rs`.
+//! - `log/captures/log.bin`, [`log::capture`], its frames.
+//!
+//! The decoders read the committed files in their `capture` tests, the
+//! other host tests build the fixtures by these functions. The files are
+//! written by
+//!
+//! > cargo run -p fixtures
+//!
+//! and checked to be up to date by the tests here.
+
+pub mod elf;
+pub mod itm;
+pub mod log;
+pub mod trace;
+
+use std::path::{Path, PathBuf};
+
+/// The fixtures, by path relative to `host/`.
+pub fn all() -> Vec<(&'static str, Vec<u8>)> {
+    vec![
+        ("rtic_trace/captures/exam.bin", trace::exam()),
+        ("itm/captures/itm.txt", itm::capture()),
+        ("itm/captures/log.txt", itm::log()),
+        ("itm/captures/timing_itm.elf", elf::timing_itm()),
+        ("log/captures/timing_log.elf", log::timing_log()),
+        ("log/captures/log.bin", log::capture()),
+    ]
+}
+
+/// The path of a fixture.
+pub fn path(fixture: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join(fixture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn up_to_date() {
+        for (fixture, bytes) in all() {
+            let committed = std::fs::read(path(fixture)).unwrap();
+            assert!(committed == bytes, "{} (`cargo run -p fixtures`)", fixture);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+//! host/fixtures/src/log.rs
+//!
+//! The deferred formatting log of `timing::log`, the `.log` section of
+//! `examples/timing_log.rs` and a capture of its frames.
+
+use crate::elf::{self, Contents, Section, Symbol};
+
+const U32: u8 = 0x03;
+const STR: u8 = 0x0b;
+const BYTES: u8 = 0x0c;
+
+const OVERFLOW: u16 = 0xffff;
+
+/// The messages of `examples/timing_log.rs`, `(level, format, line)`,
+/// in the order of the `.log` section (the `debug!` left out).
+const ENTRIES: &[(u8, &str, u32)] = &[
+    (2, "init, period {} cycles", 31),
+    (4, "{} messages dropped", 41),
+    (2, "t1: count {}, response {} cycles", 59),
+    (3, "t1: response {:#x} > {:#x}", 61),
+    (2, "t1: {} {:?}", 64),
+];
+
+/// The contents of `.log`, `[level] format 0 file:line 0` per entry.
+fn section() -> Vec<u8> {
+    let mut log = vec![];
+    for &(level, format, line) in ENTRIES {
+        log.push(level);
+        log.extend_from_slice(format.as_bytes());
+        log.push(0);
+        log.extend_from_slice(format!("examples/timing_log.rs:{}", line).as_bytes());
+        log.push(0);
+    }
+    log
+}
+
+/// Index of the entry of `format`, its offset in `.log`.
+fn index(format: &str) -> u16 {
+    ENTRIES
+        .iter()
+        .take_while(|&&(_, f, _)| f != format)
+        .map(|&(_, f, line)| f.len() + format!("examples/timing_log.rs:{}", line).len() + 3)
+        .sum::<usize>() as u16
+}
+
+/// A minimal image of `examples/timing_log.rs`, `.text` and `.log` (an
+/// `INFO` section at 0).
+pub fn timing_log() -> Vec<u8> {
+    let sections = [
+        Section {
+            name: ".text",
+            addr: 0x0800_0400,
+            contents: Contents::Bits(vec![0; 16]),
+        },
+        Section {
+            name: ".log",
+            addr: 0,
+            contents: Contents::Bits(section()),
+        },
+    ];
+    let symbols = [Symbol::new("main", 0x0800_0401, 16, elf::FUNC, ".text")];
+    elf::build(&sections, &symbols)
+}
+
+/// An argument of a frame.
+enum Arg<'a> {
+    U32(u32),
+    Str(&'a str),
+    Bytes(&'a [u8]),
+}
+
+fn frame(index: u16, time: u32, args: &[Arg]) -> Vec<u8> {
+    let mut frame = vec![0];
+    frame.extend_from_slice(&index.to_le_bytes());
+    frame.extend_from_slice(&time.to_le_bytes());
+    for arg in args {
+        match arg {
+            Arg::U32(n) => {
+                frame.push(U32);
+                frame.extend_from_slice(&n.to_le_bytes());
+            }
+            Arg::Str(s) => {
+                frame.extend_from_slice(&[STR, s.len() as u8]);
+                frame.extend_from_slice(s.as_bytes());
+            }
+            Arg::Bytes(b) => {
+                frame.extend_from_slice(&[BYTES, b.len() as u8]);
+                frame.extend_from_slice(b);
+            }
+        }
+    }
+    frame[0] = frame.len() as u8;
+    frame
+}
+
+/// Eight periods of `examples/timing_log.rs`, the response of t1 over
+/// the limit in the third, then two frames lost (reported by an overflow
+/// frame before the next).
+pub fn capture() -> Vec<u8> {
+    let count = index("t1: count {}, response {} cycles");
+    let mut log = frame(index("init, period {} cycles"), 1093, &[Arg::U32(100_000)]);
+    for &(n, response) in &[(1, 10_210), (2, 20_210), (3, 30_210)] {
+        log.extend(frame(
+            count,
+            n * 100_000 + 1342,
+            &[Arg::U32(n), Arg::U32(response)],
+        ));
+    }
+    log.extend(frame(
+        index("t1: response {:#x} > {:#x}"),
+        301_401,
+        &[Arg::U32(30_210), Arg::U32(25_000)],
+    ));
+    log.extend(frame(OVERFLOW, 801_342, &[Arg::U32(2)]));
+    log.extend(frame(count, 801_342, &[Arg::U32(8), Arg::U32(10_210)]));
+    log.extend(frame(
+        index("t1: {} {:?}"),
+        801_380,
+        &[Arg::Str("state"), Arg::Bytes(&[0xc0, 0xff, 0xee])],
+    ));
+    log
+}
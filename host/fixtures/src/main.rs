@@ -0,0 +1,17 @@
+//! host/fixtures/src/main.rs
+//!
+//! > cargo run -p fixtures
+//!
+//! Writes the generated captures (see `fixtures`).
+
+use std::{fs, process};
+
+fn main() {
+    for (fixture, bytes) in fixtures::all() {
+        if let Err(e) = fs::write(fixtures::path(fixture), &bytes) {
+            eprintln!("{}: {}", fixture, e);
+            process::exit(1);
+        }
+        println!("{} ({} bytes)", fixture, bytes.len());
+    }
+}
@@ -0,0 +1,75 @@
+//! host/fixtures/src/trace.rs
+//!
+//! The binary task trace of `timing::trace::Tracer`.
+
+const ENTER: u8 = 0x01;
+const EXIT: u8 = 0x02;
+const LOCK: u8 = 0x03;
+const UNLOCK: u8 = 0x04;
+const PEND: u8 = 0x05;
+const SCHEDULE: u8 = 0x06;
+const TASK_NAME: u8 = 0x10;
+const RESOURCE_NAME: u8 = 0x11;
+
+/// A trace under construction, as written by the tracer.
+#[derive(Default)]
+pub struct Trace(pub Vec<u8>);
+
+impl Trace {
+    pub fn task_name(&mut self, id: u8, name: &str) -> &mut Self {
+        self.0.extend_from_slice(&[TASK_NAME, id, name.len() as u8]);
+        self.0.extend_from_slice(name.as_bytes());
+        self
+    }
+
+    pub fn resource_name(&mut self, id: u8, name: &str, ceiling: u8) -> &mut Self {
+        self.0
+            .extend_from_slice(&[RESOURCE_NAME, id, ceiling, name.len() as u8]);
+        self.0.extend_from_slice(name.as_bytes());
+        self
+    }
+
+    /// A timestamped record.
+    pub fn record(&mut self, tag: u8, id: u8, time: u32) -> &mut Self {
+        self.0.extend_from_slice(&[tag, id]);
+        self.0.extend_from_slice(&time.to_le_bytes());
+        self
+    }
+}
+
+/// `examples/timing_exam.rs` traced, t2 released at 200_000 and
+/// preempted by t3 while holding R1 (ceiling 2), in the cycles of the
+/// measurements (dispatch 240, `schedule` 10, lock 20).
+pub fn exam() -> Vec<u8> {
+    let mut t = Trace(exam_names());
+    for &(tag, id, time) in EXAM {
+        t.record(tag, id, time);
+    }
+    t.0
+}
+
+/// The names announced by `init` for [`exam`].
+pub(crate) fn exam_names() -> Vec<u8> {
+    let mut t = Trace::default();
+    t.task_name(0, "t1").task_name(1, "t2").task_name(2, "t3");
+    t.resource_name(0, "R1", 2).resource_name(1, "R2", 3);
+    t.0
+}
+
+/// The records of [`exam`], `(tag, id, time)`.
+pub(crate) const EXAM: &[(u8, u8, u32)] = &[
+    (PEND, 1, 200_012),
+    (ENTER, 1, 200_240),
+    (SCHEDULE, 1, 200_250),
+    (LOCK, 0, 210_250),
+    (LOCK, 1, 212_270),
+    (UNLOCK, 1, 216_290),
+    (PEND, 2, 218_000),
+    (ENTER, 2, 218_240),
+    (SCHEDULE, 2, 218_250),
+    (LOCK, 1, 228_250),
+    (UNLOCK, 1, 238_290),
+    (EXIT, 2, 248_330),
+    (UNLOCK, 0, 250_000),
+    (EXIT, 1, 260_000),
+];
@@ -0,0 +1,254 @@
+//! host/itm/src/exceptions.rs
+//!
+//! The timeline of the exception handlers, from the DWT exception trace
+//! (`timing::itm::enable_exception_trace`): each handler entry, exit and
+//! return to a preempted handler (or to thread mode), named by the
+//! [`Vectors`] of the firmware.
+//!
+//! Handler entries and exits are those of the hardware, the span of an
+//! RTIC task includes the dispatch and exit overheads of the runtime.
+
+use crate::{Function, Packet, Stream, Vectors};
+use rtic_trace::{Event, Kind};
+use std::{collections::BTreeMap, fmt};
+
+/// An exception trace packet, at `time`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Exception {
+    pub time: u64,
+    pub number: u16,
+    pub function: Function,
+}
+
+/// The exception trace packets of `stream`.
+pub fn exceptions(stream: &Stream) -> Vec<Exception> {
+    stream
+        .packets
+        .iter()
+        .filter_map(|t| match t.packet {
+            Packet::ExceptionTrace { number, function } => Some(Exception {
+                time: t.time,
+                number,
+                function,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Exceptions and the names of the handlers.
+pub struct Timeline<'a> {
+    pub exceptions: &'a [Exception],
+    pub vectors: &'a Vectors,
+}
+
+impl fmt::Display for Timeline<'_> {
+    /// A line per packet, indented by the nesting of the handlers.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut depth = 0usize;
+        for (i, e) in self.exceptions.iter().enumerate() {
+            if e.function == Function::Exited {
+                depth = depth.saturating_sub(1);
+            }
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(
+                f,
+                "{:>10} {:indent$}{:<8} {}",
+                e.time,
+                "",
+                e.function,
+                self.vectors.name(e.number),
+                indent = 2 * depth
+            )?;
+            if e.function == Function::Entered {
+                depth += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Handler activations (entry to exit, including preemptions), in
+/// cycles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Span {
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub total: u64,
+}
+
+/// The activations of each handler, exits without a traced entry are
+/// left out.
+pub fn spans(exceptions: &[Exception]) -> BTreeMap<u16, Span> {
+    let mut spans: BTreeMap<u16, Span> = BTreeMap::new();
+    let mut active: Vec<Exception> = vec![];
+    for e in exceptions {
+        match e.function {
+            Function::Entered => active.push(*e),
+            Function::Exited => {
+                if let Some(i) = active.iter().rposition(|a| a.number == e.number) {
+                    let span = e.time - active.remove(i).time;
+                    let s = spans.entry(e.number).or_default();
+                    s.min = if s.count == 0 { span } else { s.min.min(span) };
+                    s.max = s.max.max(span);
+                    s.total += span;
+                    s.count += 1;
+                }
+            }
+            Function::Returned => {}
+        }
+    }
+    spans
+}
+
+/// See [`spans`].
+pub struct Spans<'a> {
+    pub spans: &'a BTreeMap<u16, Span>,
+    pub vectors: &'a Vectors,
+}
+
+impl fmt::Display for Spans<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:<16} {:>8} {:>10} {:>10} {:>10}",
+            "handler", "count", "min", "max", "mean"
+        )?;
+        for (&n, s) in self.spans {
+            write!(
+                f,
+                "\n{:<16} {:>8} {:>10} {:>10} {:>10}",
+                self.vectors.name(n),
+                s.count,
+                s.min,
+                s.max,
+                s.total / s.count.max(1)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The exceptions as task events (each handler a task), to be charted
+/// by `rtic_trace`.
+pub fn events(exceptions: &[Exception], vectors: &Vectors) -> Vec<Event> {
+    let mut events = vec![];
+    let mut active: Vec<String> = vec![];
+    let mut event = |time, task: &str, kind| {
+        events.push(Event {
+            time,
+            task: task.to_string(),
+            kind,
+        })
+    };
+    for e in exceptions {
+        let name = vectors.name(e.number);
+        match e.function {
+            Function::Entered => {
+                if let Some(top) = active.last() {
+                    let by = name.clone();
+                    event(e.time, top, Kind::Preempted { by });
+                }
+                event(e.time, &name, Kind::Dispatch);
+                active.push(name);
+            }
+            Function::Exited => {
+                if let Some(i) = active.iter().rposition(|a| *a == name) {
+                    active.remove(i);
+                }
+                event(e.time, &name, Kind::Exit);
+            }
+            Function::Returned if e.number == 0 => event(e.time, "-", Kind::Idle),
+            Function::Returned => event(e.time, &name, Kind::Resume),
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode;
+
+    // the committed captures, decoded as recorded
+    const ITM: &[u8] = include_bytes!("../captures/itm.txt");
+    const IMAGE: &[u8] = include_bytes!("../captures/timing_itm.elf");
+
+    fn vectors() -> Vectors {
+        let mut v = Vectors::from_elf(IMAGE).unwrap();
+        v.rtic(include_str!("../../../examples/timing_itm.rs"));
+        v
+    }
+
+    #[test]
+    fn timeline() {
+        let e = exceptions(&decode(ITM));
+        let vectors = vectors();
+        assert_eq!(
+            Timeline {
+                exceptions: &e,
+                vectors: &vectors
+            }
+            .to_string(),
+            "    200000 entered  SysTick
+    200100 exited   SysTick
+    200110 entered  t2
+    217900   entered  SysTick
+    218100   exited   SysTick
+    218110   entered  t3
+    248340   exited   t3
+    248350   returned t2
+    260010 exited   t2
+    260020 returned thread"
+        );
+        let spans = spans(&e);
+        assert_eq!(
+            spans[&15],
+            Span {
+                count: 2,
+                min: 100,
+                max: 200,
+                total: 300
+            }
+        );
+        assert_eq!(
+            Spans {
+                spans: &spans,
+                vectors: &vectors
+            }
+            .to_string(),
+            "handler             count        min        max       mean
+SysTick                 2        100        200        150
+t3                      1      30230      30230      30230
+t2                      1      59900      59900      59900"
+        );
+    }
+
+    #[test]
+    fn chart() {
+        let e = exceptions(&decode(&fixtures::itm::capture()));
+        let events = events(&e, &vectors());
+        let text: String = events.iter().map(|e| format!("\n{}", e)).collect();
+        assert_eq!(
+            text,
+            "
+    200000 SysTick  dispatch
+    200100 SysTick  exit
+    200110 t2       dispatch
+    217900 t2       preempted by SysTick
+    217900 SysTick  dispatch
+    218100 SysTick  exit
+    218110 t2       preempted by t3
+    218110 t3       dispatch
+    248340 t3       exit
+    248350 t2       resume
+    260010 t2       exit
+    260020 -        idle"
+        );
+        let chart = rtic_trace::Chart::new(&events);
+        assert_eq!(chart.rows.len(), 3);
+    }
+}
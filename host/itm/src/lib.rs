@@ -0,0 +1,126 @@
+//! host/itm/src/lib.rs
+//!
+//! Decoding of the ITM/SWO output of the firmware (`timing::itm`), as
+//! captured by OpenOCD in `itm.txt` (see `openocd.gdb`), into [`Packet`]s
+//! timed by the local timestamps, the text written to a stimulus port,
+//! the task events of the binary tracer on the [`TRACE`] port, and the
+//! timeline of the exception handlers (module `exceptions`).
+
+pub mod exceptions;
+pub mod packet;
+pub mod vectors;
+
+pub use exceptions::{exceptions, Exception, Timeline};
+pub use packet::{decode, Function, Packet, Stream, Timed};
+pub use vectors::Vectors;
+
+use rtic_trace::{DecodeError, Event};
+
+/// Stimulus port of the text log.
+pub const LOG: u8 = 0;
+
+/// Stimulus port of the binary trace.
+pub const TRACE: u8 = 1;
+
+/// The bytes written to stimulus `port`, in order.
+pub fn payload(stream: &Stream, port: u8) -> Vec<u8> {
+    stream
+        .packets
+        .iter()
+        .filter_map(|t| match &t.packet {
+            Packet::Instrumentation { port: p, payload } if *p == port => Some(&payload[..]),
+            _ => None,
+        })
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// The text written to stimulus `port`.
+pub fn text(stream: &Stream, port: u8) -> String {
+    String::from_utf8_lossy(&payload(stream, port)).into_owned()
+}
+
+/// The task events of the binary tracer (`timing::trace::Tracer`) on
+/// the [`TRACE`] port.
+pub fn events(stream: &Stream) -> Result<Vec<Event>, DecodeError> {
+    rtic_trace::decode(&payload(stream, TRACE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the committed captures, decoded as recorded
+    const ITM: &[u8] = include_bytes!("../captures/itm.txt");
+    const LOG_ONLY: &[u8] = include_bytes!("../captures/log.txt");
+    const EXAM: &[u8] = include_bytes!("../../rtic_trace/captures/exam.bin");
+
+    #[test]
+    fn capture() {
+        let s = decode(ITM);
+        assert_eq!(s.trailing, 0);
+        assert_eq!(s.packets[0].packet, Packet::Sync);
+        assert_eq!(text(&s, LOG), "init\n");
+        // the RTT capture of the same run, sent over the ITM
+        assert_eq!(payload(&s, TRACE), EXAM);
+        assert_eq!(events(&s).unwrap(), rtic_trace::decode(EXAM).unwrap());
+        // the local timestamps are in step with the CYCCNT of the records
+        let exceptions: Vec<String> = s
+            .packets
+            .iter()
+            .filter(|t| matches!(t.packet, Packet::ExceptionTrace { .. }))
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(
+            exceptions,
+            vec![
+                "    200000 exception 15 entered",
+                "    200100 exception 15 exited",
+                "    200110 exception 23 entered",
+                "    217900 exception 15 entered",
+                "    218100 exception 15 exited",
+                "    218110 exception 22 entered",
+                "    248340 exception 22 exited",
+                "    248350 exception 23 returned",
+                "    260010 exception 23 exited",
+                "    260020 exception 0 returned",
+            ]
+        );
+        // the record of the release of t2
+        let pend = s
+            .packets
+            .iter()
+            .find(|t| match &t.packet {
+                Packet::Instrumentation { port, payload } => {
+                    *port == TRACE && payload.starts_with(&[5, 1])
+                }
+                _ => false,
+            })
+            .unwrap();
+        assert_eq!(pend.time, 200_012);
+        // lost packets, reported by a delayed timestamp
+        let last: Vec<String> = s.packets[s.packets.len() - 3..]
+            .iter()
+            .map(|t| t.to_string())
+            .collect();
+        assert_eq!(
+            last,
+            vec![
+                "    270000 overflow",
+                "    270000 timestamp +9980 (timestamp delayed)",
+                "    270000 sync",
+            ]
+        );
+    }
+
+    #[test]
+    fn log() {
+        // no timestamps, stopped within a packet
+        let s = decode(LOG_ONLY);
+        assert_eq!(s.trailing, 3);
+        assert!(s.packets.iter().all(|t| t.time == 0));
+        assert_eq!(text(&s, LOG), "init\nt1: sample 40632\nt3: sample 30362\n");
+        assert_eq!(events(&s), Ok(vec![]));
+    }
+}
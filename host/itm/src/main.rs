@@ -0,0 +1,109 @@
+//! host/itm/src/main.rs
+//!
+//! > cargo run -p itm -- [--port n | --events | --exceptions [--events] [--elf image] [--app src.rs]] [itm.txt]
+//!
+//! Decodes a raw SWO capture (default stdin), e.g., the `itm.txt` written
+//! by OpenOCD, listing the packets with their time (by the local
+//! timestamps).
+//!
+//! `--port n` prints the text written to stimulus port `n` (the log on
+//! port 0), `--events` the task events of the binary tracer on port 1 (in
+//! the text format of `rtic_trace`).
+//!
+//! `--exceptions` prints the timeline of the exception handlers (from the
+//! DWT exception trace) and their activations, named by the vector table
+//! of the `--elf` image and, with `--app`, by the RTIC tasks of the
+//! application source. With `--events` the handlers are listed as task
+//! events instead, to be charted by `rtic_trace`.
+
+use itm::{decode, events, exceptions, text, Timeline, Vectors};
+use std::{
+    env, fs,
+    io::{self, Read},
+    process,
+};
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
+
+fn main() {
+    let mut port = None;
+    let mut list = false;
+    let mut handlers = false;
+    let (mut image, mut app) = (None, None);
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--port" => {
+                port = Some(
+                    args.next()
+                        .and_then(|p| p.parse().ok())
+                        .unwrap_or_else(|| fail("--port <0..32>")),
+                )
+            }
+            "--events" => list = true,
+            "--exceptions" => handlers = true,
+            "--elf" => image = Some(args.next().unwrap_or_else(|| fail("--elf <image>"))),
+            "--app" => app = Some(args.next().unwrap_or_else(|| fail("--app <src.rs>"))),
+            _ => path = Some(arg),
+        }
+    }
+
+    let mut bytes = vec![];
+    let read = match &path {
+        Some(path) => fs::read(path).map(|b| bytes = b),
+        None => io::stdin().read_to_end(&mut bytes).map(drop),
+    };
+    if let Err(e) = read {
+        fail(&format!("{}: {}", path.as_deref().unwrap_or("stdin"), e));
+    }
+    let stream = decode(&bytes);
+    if stream.trailing > 0 {
+        eprintln!("{} bytes of a packet cut off at the end", stream.trailing);
+    }
+
+    let read = |path: &str| fs::read(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)));
+
+    if handlers {
+        let mut vectors = match &image {
+            Some(image) => Vectors::from_elf(&read(image))
+                .unwrap_or_else(|e| fail(&format!("{}: {}", image, e))),
+            None => Vectors::default(),
+        };
+        if let Some(app) = &app {
+            vectors.rtic(&String::from_utf8_lossy(&read(app)));
+        }
+        let exceptions = exceptions::exceptions(&stream);
+        if list {
+            for e in exceptions::events(&exceptions, &vectors) {
+                println!("{}", e);
+            }
+        } else {
+            let spans = exceptions::spans(&exceptions);
+            println!(
+                "{}\n\n{}",
+                Timeline {
+                    exceptions: &exceptions,
+                    vectors: &vectors
+                },
+                exceptions::Spans {
+                    spans: &spans,
+                    vectors: &vectors
+                }
+            );
+        }
+    } else if let Some(port) = port {
+        print!("{}", text(&stream, port));
+    } else if list {
+        for e in events(&stream).unwrap_or_else(|e| fail(&e.to_string())) {
+            println!("{}", e);
+        }
+    } else {
+        for p in &stream.packets {
+            println!("{}", p);
+        }
+    }
+}
@@ -0,0 +1,355 @@
+//! host/itm/src/packet.rs
+//!
+//! ITM and DWT packets (ARMv7-M Architecture Reference Manual, appendix
+//! D4), decoded from the raw SWO byte stream.
+
+use std::fmt;
+
+/// Function of an exception trace packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Function {
+    /// Entered the handler.
+    Entered,
+    /// Exited the handler.
+    Exited,
+    /// Returned to the (preempted) exception, 0 for thread mode.
+    Returned,
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Function::Entered => "entered",
+            Function::Exited => "exited",
+            Function::Returned => "returned",
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Packet {
+    /// Synchronization, (at least) 47 zero bits and a one.
+    Sync,
+    /// The ITM FIFO overflowed, packets are lost.
+    Overflow,
+    /// Cycles since the previous local timestamp, `tc` tells if the
+    /// timestamp (bit 0) and/or the packets (bit 1) are delayed.
+    LocalTimestamp { delta: u32, tc: u8 },
+    /// The low bits of the global timestamp.
+    GlobalTimestamp1 { bits: u32 },
+    /// The high bits of the global timestamp.
+    GlobalTimestamp2 { bits: u64 },
+    /// Stimulus port page (and other extensions).
+    Extension { info: u32 },
+    /// A stimulus port write, 1, 2 or 4 bytes.
+    Instrumentation { port: u8, payload: Vec<u8> },
+    /// DWT event counters wrapped (bit per counter).
+    EventCounter { counters: u8 },
+    /// DWT exception trace.
+    ExceptionTrace { number: u16, function: Function },
+    /// DWT periodic PC sample, `None` when sleeping.
+    PcSample { pc: Option<u32> },
+    /// DWT data trace (PC, address or data value of a comparator).
+    DataTrace { discriminator: u8, value: u32 },
+    /// A reserved header, or a malformed packet.
+    Unknown { header: u8 },
+}
+
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Packet::Sync => write!(f, "sync"),
+            Packet::Overflow => write!(f, "overflow"),
+            Packet::LocalTimestamp { delta, tc } => {
+                write!(f, "timestamp +{}", delta)?;
+                match tc {
+                    0 => Ok(()),
+                    1 => write!(f, " (timestamp delayed)"),
+                    2 => write!(f, " (packets delayed)"),
+                    _ => write!(f, " (timestamp and packets delayed)"),
+                }
+            }
+            Packet::GlobalTimestamp1 { bits } => write!(f, "global timestamp {:#x}", bits),
+            Packet::GlobalTimestamp2 { bits } => {
+                write!(f, "global timestamp high {:#x}", bits)
+            }
+            Packet::Extension { info } => write!(f, "extension {:#x}", info),
+            Packet::Instrumentation { port, payload } => {
+                write!(f, "port {}:", port)?;
+                for b in payload {
+                    write!(f, " {:02x}", b)?;
+                }
+                Ok(())
+            }
+            Packet::EventCounter { counters } => write!(f, "event counters {:#010b}", counters),
+            Packet::ExceptionTrace { number, function } => {
+                write!(f, "exception {} {}", number, function)
+            }
+            Packet::PcSample { pc: Some(pc) } => write!(f, "pc {:#010x}", pc),
+            Packet::PcSample { pc: None } => write!(f, "pc sleeping"),
+            Packet::DataTrace {
+                discriminator,
+                value,
+            } => write!(f, "data trace {} {:#x}", discriminator, value),
+            Packet::Unknown { header } => write!(f, "unknown {:#04x}", header),
+        }
+    }
+}
+
+/// A packet and its time, in (prescaled) cycles since the start of the
+/// capture, by the local timestamps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Timed {
+    pub time: u64,
+    pub packet: Packet,
+}
+
+impl fmt::Display for Timed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>10} {}", self.time, self.packet)
+    }
+}
+
+/// A decoded capture.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stream {
+    pub packets: Vec<Timed>,
+    /// Bytes of a packet cut off at the end of the capture.
+    pub trailing: usize,
+}
+
+/// Reads up to `max` continuation bytes (7 bits each, bit 7 set when
+/// followed by another), `None` if cut off.
+fn continued(bytes: &[u8], max: usize) -> Option<(u64, usize)> {
+    let mut value = 0;
+    for (i, &b) in bytes.iter().take(max).enumerate() {
+        value |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 || i + 1 == max {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Decodes the packet at the start of `bytes`, with its length, `None`
+/// if cut off.
+fn packet(bytes: &[u8]) -> Option<(Packet, usize)> {
+    let header = bytes[0];
+    let unknown = Packet::Unknown { header };
+    Some(match header {
+        0x00 => {
+            let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+            match bytes.get(zeros)? {
+                0x80 if zeros >= 5 => (Packet::Sync, zeros + 1),
+                _ => (unknown, zeros),
+            }
+        }
+        0x70 => (Packet::Overflow, 1),
+        // local timestamp, format 2
+        h if h & 0x8f == 0 => (
+            Packet::LocalTimestamp {
+                delta: (h >> 4) as u32,
+                tc: 0,
+            },
+            1,
+        ),
+        // local timestamp, format 1
+        h if h & 0xcf == 0xc0 => {
+            let (delta, len) = continued(&bytes[1..], 4)?;
+            (
+                Packet::LocalTimestamp {
+                    delta: delta as u32,
+                    tc: (h >> 4) & 0b11,
+                },
+                1 + len,
+            )
+        }
+        0x94 => {
+            let (bits, len) = continued(&bytes[1..], 4)?;
+            // bit 26 and 27 flag wrap and clock change
+            let bits = bits as u32 & 0x03ff_ffff;
+            (Packet::GlobalTimestamp1 { bits }, 1 + len)
+        }
+        0xb4 => {
+            let (bits, len) = continued(&bytes[1..], 7)?;
+            (Packet::GlobalTimestamp2 { bits }, 1 + len)
+        }
+        h if h & 0x0b == 0x08 => {
+            let ex = ((h >> 4) & 0b111) as u64;
+            if h & 0x80 == 0 {
+                (Packet::Extension { info: ex as u32 }, 1)
+            } else {
+                let (bits, len) = continued(&bytes[1..], 4)?;
+                let info = (ex | bits << 3) as u32;
+                (Packet::Extension { info }, 1 + len)
+            }
+        }
+        h if h & 0b11 == 0 => (unknown, 1),
+        // source packets
+        h => {
+            let size = [0, 1, 2, 4][(h & 0b11) as usize];
+            let payload = bytes.get(1..1 + size)?;
+            let id = h >> 3;
+            let value = payload.iter().rev().fold(0u32, |v, &b| v << 8 | b as u32);
+            let packet = if h & 0b100 == 0 {
+                Packet::Instrumentation {
+                    port: id,
+                    payload: payload.to_vec(),
+                }
+            } else {
+                match (id, size) {
+                    (0, 1) => Packet::EventCounter {
+                        counters: payload[0],
+                    },
+                    (1, 2) => {
+                        let function = match (payload[1] >> 4) & 0b11 {
+                            1 => Function::Entered,
+                            2 => Function::Exited,
+                            3 => Function::Returned,
+                            _ => return Some((unknown, 1 + size)),
+                        };
+                        Packet::ExceptionTrace {
+                            number: (value & 0x1ff) as u16,
+                            function,
+                        }
+                    }
+                    (2, 4) => Packet::PcSample { pc: Some(value) },
+                    (2, 1) => Packet::PcSample { pc: None },
+                    (8..=23, _) => Packet::DataTrace {
+                        discriminator: id,
+                        value,
+                    },
+                    _ => unknown,
+                }
+            };
+            (packet, 1 + size)
+        }
+    })
+}
+
+/// Decodes a raw SWO capture, e.g., the `itm.txt` written by OpenOCD
+/// (`tpiu config internal itm.txt uart off <clock>`).
+///
+/// A local timestamp follows the packets it applies to, so packets are
+/// timed by the next local timestamp (packets after the last one by the
+/// time of the last one).
+pub fn decode(bytes: &[u8]) -> Stream {
+    let mut stream = Stream::default();
+    let (mut time, mut untimed) = (0, 0);
+    let mut at = 0;
+    while at < bytes.len() {
+        let (packet, len) = match packet(&bytes[at..]) {
+            Some(p) => p,
+            None => {
+                stream.trailing = bytes.len() - at;
+                break;
+            }
+        };
+        at += len;
+        if let Packet::LocalTimestamp { delta, .. } = packet {
+            time += delta as u64;
+            for p in &mut stream.packets[untimed..] {
+                p.time = time;
+            }
+            untimed = stream.packets.len() + 1;
+        }
+        stream.packets.push(Timed { time, packet });
+    }
+    stream
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packets(bytes: &[u8]) -> Vec<Packet> {
+        decode(bytes)
+            .packets
+            .into_iter()
+            .map(|t| t.packet)
+            .collect()
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(
+            packets(&[0, 0, 0, 0, 0, 0x80, 0x70, 0x30, 0xe0, 0x81, 0x01]),
+            vec![
+                Packet::Sync,
+                Packet::Overflow,
+                Packet::LocalTimestamp { delta: 3, tc: 0 },
+                Packet::LocalTimestamp { delta: 129, tc: 2 },
+            ]
+        );
+        assert_eq!(
+            packets(&[0x94, 0xff, 0x01, 0xb4, 0x05, 0x08 | 0x10, 0x8c, 0x02]),
+            vec![
+                Packet::GlobalTimestamp1 { bits: 0xff },
+                Packet::GlobalTimestamp2 { bits: 5 },
+                Packet::Extension { info: 1 },
+                Packet::Extension { info: 0b10_000 },
+            ]
+        );
+        assert_eq!(
+            packets(&[
+                0x0b, 1, 2, 3, 4, 0x0e, 0x16, 0x20, 0x17, 0x78, 0x56, 0x34, 0x12, 0x15, 0, 0x05,
+                0x21, 0x46, 0x10, 0x32
+            ]),
+            vec![
+                Packet::Instrumentation {
+                    port: 1,
+                    payload: vec![1, 2, 3, 4]
+                },
+                Packet::ExceptionTrace {
+                    number: 22,
+                    function: Function::Exited
+                },
+                Packet::PcSample {
+                    pc: Some(0x1234_5678)
+                },
+                Packet::PcSample { pc: None },
+                Packet::EventCounter { counters: 0x21 },
+                Packet::DataTrace {
+                    discriminator: 8,
+                    value: 0x3210
+                },
+            ]
+        );
+        // reserved
+        assert_eq!(
+            packets(&[0x80, 0x24, 0, 0x70]),
+            vec![
+                Packet::Unknown { header: 0x80 },
+                Packet::Unknown { header: 0x24 },
+                Packet::Unknown { header: 0 },
+                Packet::Overflow,
+            ]
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        // two writes, timestamped 10, one by a delayed timestamp
+        let s = decode(&[
+            0x01, b'a', 0x01, b'b', 0xc0, 10, 0x09, b'c', 0xd0, 0x82, 0x01,
+        ]);
+        let times: Vec<u64> = s.packets.iter().map(|p| p.time).collect();
+        assert_eq!(times, vec![10, 10, 10, 140, 140]);
+        assert_eq!(
+            s.packets[4].to_string(),
+            "       140 timestamp +130 (timestamp delayed)"
+        );
+        // after the last timestamp
+        let s = decode(&[0x20, 0x01, b'a']);
+        assert_eq!(s.packets[1].time, 2);
+    }
+
+    #[test]
+    fn truncated() {
+        let s = decode(&[0x01, b'a', 0x0b, 1, 2]);
+        assert_eq!(s.packets.len(), 1);
+        assert_eq!(s.trailing, 3);
+        assert_eq!(decode(&[0xc0, 0x81]).trailing, 2);
+        assert_eq!(decode(&[0, 0, 0]).trailing, 3);
+    }
+}
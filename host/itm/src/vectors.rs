@@ -0,0 +1,243 @@
+//! host/itm/src/vectors.rs
+//!
+//! Names of the exceptions (by number, as in the exception trace), from
+//! the vector table of the firmware image, and the RTIC tasks bound to
+//! them, from the source of the application.
+//!
+//! The vector table gives the handler of each interrupt (its symbol is
+//! the interrupt name, e.g., `EXTI0`, unused vectors point to
+//! `DefaultHandler`). RTIC binds hardware tasks to handlers by
+//! `#[task(binds = EXTI0)]`, and dispatches the software tasks of each
+//! priority from one of the free interrupts of `extern "C" { .. }`, the
+//! first to the highest priority (as RTIC 0.5 assigns them).
+
+use std::{collections::BTreeMap, fmt};
+
+/// The system exceptions.
+pub const SYSTEM: [(u16, &str); 10] = [
+    (1, "Reset"),
+    (2, "NMI"),
+    (3, "HardFault"),
+    (4, "MemManage"),
+    (5, "BusFault"),
+    (6, "UsageFault"),
+    (11, "SVCall"),
+    (12, "DebugMonitor"),
+    (14, "PendSV"),
+    (15, "SysTick"),
+];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    Elf(elf::Error),
+    NoVectorTable,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Elf(e) => e.fmt(f),
+            Error::NoVectorTable => write!(f, "no .vector_table section"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<elf::Error> for Error {
+    fn from(e: elf::Error) -> Self {
+        Error::Elf(e)
+    }
+}
+
+/// Exception names by number.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Vectors {
+    pub names: BTreeMap<u16, String>,
+}
+
+impl Vectors {
+    /// The handlers of the vector table of an ELF image.
+    pub fn from_elf(bytes: &[u8]) -> Result<Self, Error> {
+        let elf = elf::Elf::parse(bytes)?;
+        let table = elf.section(".vector_table").ok_or(Error::NoVectorTable)?;
+        let table = elf.data(table)?;
+        let symbols = elf.symbols()?;
+        let mut names = BTreeMap::new();
+        // entry 0 is the initial stack pointer
+        for (n, entry) in table.chunks_exact(4).enumerate().skip(1) {
+            let addr = u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) & !1;
+            let handler = symbols
+                .iter()
+                .find(|s| s.kind == elf::STT_FUNC && s.addr() == addr);
+            match handler {
+                Some(s) if addr != 0 && s.name != "DefaultHandler" => {
+                    let n = n as u16;
+                    let name = system(n).unwrap_or(&s.name);
+                    names.insert(n, name.to_string());
+                }
+                _ => {}
+            }
+        }
+        Ok(Vectors { names })
+    }
+
+    /// Renames the handlers of RTIC tasks by the tasks of the application
+    /// `source`, the dispatchers by their tasks (separated by `|`).
+    pub fn rtic(&mut self, source: &str) {
+        let tasks = tasks(source);
+        for name in self.names.values_mut() {
+            if let Some(task) = tasks.get(name) {
+                *name = task.clone();
+            }
+        }
+    }
+
+    /// The name of exception `n`, `thread` for 0 (the exception trace
+    /// returns to 0 when leaving the last handler).
+    pub fn name(&self, n: u16) -> String {
+        match (self.names.get(&n), system(n)) {
+            (Some(name), _) => name.clone(),
+            (None, _) if n == 0 => "thread".to_string(),
+            (None, Some(name)) => name.to_string(),
+            (None, None) if n >= 16 => format!("IRQ{}", n - 16),
+            _ => format!("exception {}", n),
+        }
+    }
+}
+
+fn system(n: u16) -> Option<&'static str> {
+    SYSTEM.iter().find(|(s, _)| *s == n).map(|(_, name)| *name)
+}
+
+/// The identifier at the start of `s`.
+fn ident(s: &str) -> &str {
+    let s = s.trim_start();
+    let end = s
+        .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .unwrap_or(s.len());
+    &s[..end]
+}
+
+/// The value of `key = value` in `attr`.
+fn arg<'a>(attr: &'a str, key: &str) -> Option<&'a str> {
+    attr.split(',').find_map(|a| {
+        let (k, v) = a.split_once('=')?;
+        Some(ident(v)).filter(|_| k.trim() == key)
+    })
+}
+
+/// RTIC tasks of the application `source` by the interrupt they run
+/// from.
+pub fn tasks(source: &str) -> BTreeMap<String, String> {
+    let code: String = source
+        .lines()
+        .map(|l| l.split("//").next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut tasks = BTreeMap::new();
+    // software tasks by priority
+    let mut software: BTreeMap<u8, Vec<String>> = BTreeMap::new();
+    let mut rest = &code[..];
+    while let Some(at) = rest.find("#[task") {
+        rest = &rest[at + 6..];
+        let attr = match rest.strip_prefix('(') {
+            Some(args) => &args[..args.find(")]").unwrap_or(args.len())],
+            None => "",
+        };
+        let name = match rest.find("fn ") {
+            Some(at) => ident(&rest[at + 3..]).to_string(),
+            None => break,
+        };
+        match arg(attr, "binds") {
+            Some(binds) => {
+                tasks.insert(binds.to_string(), name);
+            }
+            None => {
+                let priority = arg(attr, "priority").and_then(|p| p.parse().ok());
+                software
+                    .entry(priority.unwrap_or(1))
+                    .or_default()
+                    .push(name);
+            }
+        }
+    }
+    let dispatchers = code
+        .find("extern \"C\"")
+        .map(|at| {
+            let block = &code[at..];
+            let block = &block[..block.find('}').unwrap_or(block.len())];
+            block.split("fn ").skip(1).map(ident).collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    for ((_, names), interrupt) in software.iter().rev().zip(dispatchers) {
+        tasks.insert(interrupt.to_string(), names.join("|"));
+    }
+    tasks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the committed image
+    const IMAGE: &[u8] = include_bytes!("../captures/timing_itm.elf");
+
+    #[test]
+    fn vector_table() {
+        let v = Vectors::from_elf(IMAGE).unwrap();
+        assert_eq!(
+            v.names.values().map(|n| n.as_str()).collect::<Vec<_>>(),
+            vec!["Reset", "HardFault", "SysTick", "EXTI0", "EXTI1", "EXTI2"]
+        );
+        assert_eq!(v.name(22), "EXTI0");
+        assert_eq!(v.name(0), "thread");
+        // unused
+        assert_eq!(v.name(14), "PendSV");
+        assert_eq!(v.name(30), "IRQ14");
+        assert_eq!(v.name(9), "exception 9");
+        assert_eq!(
+            Vectors::from_elf(b"ELF"),
+            Err(Error::Elf(elf::Error::NotElf))
+        );
+    }
+
+    #[test]
+    fn rtic_tasks() {
+        let app = include_str!("../../../examples/timing_itm.rs");
+        let t = tasks(app);
+        assert_eq!(t["EXTI0"], "t3");
+        assert_eq!(t["EXTI1"], "t2");
+        assert_eq!(t["EXTI2"], "t1");
+        let mut v = Vectors::from_elf(&fixtures::elf::timing_itm()).unwrap();
+        v.rtic(app);
+        assert_eq!(v.name(22), "t3");
+        assert_eq!(v.name(15), "SysTick");
+
+        let app = include_str!("../../../examples/timing_resources.rs");
+        let t = tasks(app);
+        assert_eq!(t["EXTI0"], "exti0");
+        assert_eq!(t["EXTI1"], "exti1");
+        assert_eq!(t.len(), 2);
+        // several software tasks at a priority, in a comment
+        let t = tasks(
+            "
+            // #[task(priority = 9)]
+            #[task(priority = 2)]
+            fn a(_: a::Context) {}
+            #[task(binds = EXTI3, priority = 2)]
+            fn c(_: c::Context) {}
+            #[task]
+            fn b(_: b::Context) {}
+            #[task(capacity = 2)]
+            fn d(_: d::Context) {}
+            extern \"C\" {
+                fn UART1();
+                fn UART2();
+            }",
+        );
+        assert_eq!(t["UART1"], "a");
+        assert_eq!(t["UART2"], "b|d");
+        assert_eq!(t["EXTI3"], "c");
+    }
+}
@@ -0,0 +1,296 @@
+//! host/log/src/format.rs
+//!
+//! The arguments of a frame (tagged as by `timing::log`), and the host
+//! side formatting of a message by its format string.
+//!
+//! The placeholders are those of `core::fmt`, positional only:
+//! `{[:[[fill]align][#][0][width][.precision][type]]}`, with the types
+//! `?`, `x`, `X`, `b`, `o` and `e`. A placeholder without an argument
+//! (cut off in a full frame) is `{?}`.
+
+// argument tags, as in `timing::log`
+pub const U8: u8 = 0x01;
+pub const U16: u8 = 0x02;
+pub const U32: u8 = 0x03;
+pub const U64: u8 = 0x04;
+pub const I8: u8 = 0x05;
+pub const I16: u8 = 0x06;
+pub const I32: u8 = 0x07;
+pub const I64: u8 = 0x08;
+pub const BOOL: u8 = 0x09;
+pub const F32: u8 = 0x0a;
+pub const STR: u8 = 0x0b;
+pub const BYTES: u8 = 0x0c;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+    Unsigned(u64),
+    /// The value and its width in bits (for `x`, `b` and `o`).
+    Signed(i64, u32),
+    Bool(bool),
+    F32(f32),
+    Str(String),
+    Bytes(Vec<u8>),
+}
+
+/// The arguments of `bytes`, up to the first malformed one.
+pub fn args(mut bytes: &[u8]) -> Vec<Arg> {
+    let mut args = vec![];
+    let int = |b: &[u8]| {
+        let mut v = [0; 8];
+        v[..b.len()].copy_from_slice(b);
+        u64::from_le_bytes(v)
+    };
+    while let Some((&tag, rest)) = bytes.split_first() {
+        let size = match tag {
+            U8 | I8 | BOOL => 1,
+            U16 | I16 => 2,
+            U32 | I32 | F32 => 4,
+            U64 | I64 => 8,
+            STR | BYTES => 1 + *rest.first().unwrap_or(&0xff) as usize,
+            _ => break,
+        };
+        if rest.len() < size {
+            break;
+        }
+        let value = &rest[..size];
+        let signed = |bits: u32| {
+            let shift = 64 - bits;
+            Arg::Signed((int(value) << shift) as i64 >> shift, bits)
+        };
+        args.push(match tag {
+            U8 | U16 | U32 | U64 => Arg::Unsigned(int(value)),
+            I8 | I16 | I32 | I64 => signed(size as u32 * 8),
+            BOOL => Arg::Bool(value[0] != 0),
+            F32 => Arg::F32(f32::from_bits(int(value) as u32)),
+            STR => Arg::Str(String::from_utf8_lossy(&value[1..]).into_owned()),
+            _ => Arg::Bytes(value[1..].to_vec()),
+        });
+        bytes = &rest[size..];
+    }
+    args
+}
+
+/// A parsed format spec, the part after `:`.
+#[derive(Debug, Default, PartialEq)]
+struct Spec {
+    fill: Option<char>,
+    align: Option<char>,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+    kind: Option<char>,
+}
+
+impl Spec {
+    fn parse(s: &str) -> Spec {
+        let mut spec = Spec::default();
+        let mut chars: Vec<char> = s.chars().collect();
+        let align = |c: char| c == '<' || c == '>' || c == '^';
+        if chars.len() >= 2 && align(chars[1]) {
+            spec.fill = Some(chars[0]);
+            spec.align = Some(chars[1]);
+            chars.drain(..2);
+        } else if !chars.is_empty() && align(chars[0]) {
+            spec.align = Some(chars.remove(0));
+        }
+        let mut rest = chars.into_iter().peekable();
+        if rest.peek() == Some(&'#') {
+            spec.alternate = true;
+            rest.next();
+        }
+        if rest.peek() == Some(&'0') {
+            spec.zero = true;
+            rest.next();
+        }
+        let number = |rest: &mut std::iter::Peekable<std::vec::IntoIter<char>>| {
+            let mut n = String::new();
+            while let Some(d) = rest.peek().filter(|c| c.is_ascii_digit()) {
+                n.push(*d);
+                rest.next();
+            }
+            n.parse().ok()
+        };
+        spec.width = number(&mut rest).unwrap_or(0);
+        if rest.peek() == Some(&'.') {
+            rest.next();
+            spec.precision = number(&mut rest);
+        }
+        spec.kind = rest.next();
+        spec
+    }
+
+    /// `arg` without padding, the sign (or radix prefix) apart.
+    fn value(&self, arg: &Arg) -> (String, String) {
+        let radix = |v: u64| -> (String, String) {
+            let (prefix, digits) = match self.kind {
+                Some('x') => ("0x", format!("{:x}", v)),
+                Some('X') => ("0x", format!("{:X}", v)),
+                Some('b') => ("0b", format!("{:b}", v)),
+                Some('o') => ("0o", format!("{:o}", v)),
+                _ => ("", v.to_string()),
+            };
+            let prefix = if self.alternate { prefix } else { "" };
+            (prefix.to_string(), digits)
+        };
+        match arg {
+            Arg::Unsigned(v) => radix(*v),
+            Arg::Signed(v, bits) => match self.kind {
+                // two's complement, as `core::fmt`
+                Some('x') | Some('X') | Some('b') | Some('o') => {
+                    radix(*v as u64 & (u64::MAX >> (64 - bits)))
+                }
+                _ if *v < 0 => ("-".to_string(), v.unsigned_abs().to_string()),
+                _ => (String::new(), v.to_string()),
+            },
+            Arg::Bool(b) => (String::new(), b.to_string()),
+            Arg::F32(v) => {
+                let s = match (self.precision, self.kind) {
+                    (Some(p), Some('e')) => format!("{:.*e}", p, v),
+                    (None, Some('e')) => format!("{:e}", v),
+                    (Some(p), _) => format!("{:.*}", p, v),
+                    (None, Some('?')) => format!("{:?}", v),
+                    (None, _) => v.to_string(),
+                };
+                match s.strip_prefix('-') {
+                    Some(s) => ("-".to_string(), s.to_string()),
+                    None => (String::new(), s),
+                }
+            }
+            Arg::Str(s) => match self.kind {
+                Some('?') => (String::new(), format!("{:?}", s)),
+                _ => match self.precision {
+                    Some(p) => (String::new(), s.chars().take(p).collect()),
+                    None => (String::new(), s.clone()),
+                },
+            },
+            Arg::Bytes(b) => {
+                let items: Vec<String> = b
+                    .iter()
+                    .map(|&v| {
+                        let (prefix, digits) = radix(v as u64);
+                        prefix + &digits
+                    })
+                    .collect();
+                (String::new(), format!("[{}]", items.join(", ")))
+            }
+        }
+    }
+
+    fn format(&self, arg: &Arg) -> String {
+        let (sign, digits) = self.value(arg);
+        let len = sign.chars().count() + digits.chars().count();
+        let pad = self.width.saturating_sub(len);
+        let numeric = !matches!(arg, Arg::Str(_) | Arg::Bool(_) | Arg::Bytes(_));
+        if self.zero && numeric {
+            return format!("{}{}{}", sign, "0".repeat(pad), digits);
+        }
+        let fill = self.fill.unwrap_or(' ').to_string();
+        let align = self.align.unwrap_or(if numeric { '>' } else { '<' });
+        let (left, right) = match align {
+            '>' => (pad, 0),
+            '^' => (pad / 2, pad - pad / 2),
+            _ => (0, pad),
+        };
+        format!(
+            "{}{}{}{}",
+            fill.repeat(left),
+            sign,
+            digits,
+            fill.repeat(right)
+        )
+    }
+}
+
+/// The message of `format` with `args`.
+pub fn format(format: &str, args: &[Arg]) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let placeholder: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let spec = match placeholder.find(':') {
+                    Some(at) => Spec::parse(&placeholder[at + 1..]),
+                    None => Spec::default(),
+                };
+                match args.next() {
+                    Some(arg) => out.push_str(&spec.format(arg)),
+                    None => out.push_str("{?}"),
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        let bytes = [
+            U8, 200, I8, 0xfe, I32, 0xff, 0xff, 0xff, 0xff, BOOL, 1, F32, 0, 0, 0xc0, 0x3f, STR, 2,
+            b't', b'1', BYTES, 1, 0xc0, U64, 1, 2, 3,
+        ];
+        assert_eq!(
+            args(&bytes),
+            vec![
+                Arg::Unsigned(200),
+                Arg::Signed(-2, 8),
+                Arg::Signed(-1, 32),
+                Arg::Bool(true),
+                Arg::F32(1.5),
+                Arg::Str("t1".into()),
+                Arg::Bytes(vec![0xc0]),
+            ]
+        );
+        // unknown tag
+        assert_eq!(args(&[0x7f, U8, 1]), vec![]);
+    }
+
+    #[test]
+    fn specs() {
+        let f = |spec: &str, arg: Arg| format(spec, &[arg]);
+        assert_eq!(f("{}", Arg::Unsigned(42)), "42");
+        assert_eq!(f("{:#x}", Arg::Unsigned(30210)), "0x7602");
+        assert_eq!(f("{:#010b}", Arg::Unsigned(5)), "0b00000101");
+        assert_eq!(f("{:08X}", Arg::Unsigned(0xbeef)), "0000BEEF");
+        assert_eq!(f("{:x}", Arg::Signed(-2, 16)), "fffe");
+        assert_eq!(f("{:05}", Arg::Signed(-42, 32)), "-0042");
+        assert_eq!(f("{:>6}|", Arg::Signed(-42, 32)), "   -42|");
+        assert_eq!(f("{:<4}|", Arg::Unsigned(1)), "1   |");
+        assert_eq!(f("{:*^7}", Arg::Str("t1".into())), "**t1***");
+        assert_eq!(f("{:5}|", Arg::Str("t1".into())), "t1   |");
+        assert_eq!(f("{:?}", Arg::Str("a\"b".into())), "\"a\\\"b\"");
+        assert_eq!(f("{:.2}", Arg::F32(1.005)), "1.00");
+        assert_eq!(f("{:?}", Arg::F32(1.0)), "1.0");
+        assert_eq!(f("{:e}", Arg::F32(1500.0)), "1.5e3");
+        assert_eq!(f("{:#x}", Arg::Bytes(vec![0xc0, 0xff])), "[0xc0, 0xff]");
+        assert_eq!(f("{:?}", Arg::Bytes(vec![1, 2])), "[1, 2]");
+        assert_eq!(f("{}", Arg::Bool(false)), "false");
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            format("{{{}}} {}", &[Arg::Unsigned(1), Arg::Unsigned(2)]),
+            "{1} 2"
+        );
+        // missing arguments, extra ignored
+        assert_eq!(format("{} {}", &[Arg::Unsigned(1)]), "1 {?}");
+        assert_eq!(format("-", &[Arg::Unsigned(1)]), "-");
+    }
+}
@@ -0,0 +1,286 @@
+//! host/log/src/lib.rs
+//!
+//! Decoding of the deferred formatting log of the firmware (`timing::log`).
+//!
+//! The firmware writes frames of an index and the raw arguments, the
+//! format strings (with their level and location) are in the `.log`
+//! section of the image, read into a [`Table`]. [`decode`] splits a
+//! capture into [`Record`]s and formats the messages on the host.
+
+pub mod format;
+
+pub use format::Arg;
+
+use std::{collections::BTreeMap, fmt};
+
+/// Index of the overflow frame, as in `timing::log`.
+pub const OVERFLOW: u16 = 0xffff;
+
+/// Size of the frame header (length, index and timestamp).
+pub const HEADER: usize = 7;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl Level {
+    pub fn from_u8(level: u8) -> Option<Level> {
+        Some(match level {
+            0 => Level::Trace,
+            1 => Level::Debug,
+            2 => Level::Info,
+            3 => Level::Warn,
+            4 => Level::Error,
+            _ => return None,
+        })
+    }
+
+    pub fn parse(level: &str) -> Option<Level> {
+        (0..5)
+            .filter_map(Level::from_u8)
+            .find(|l| l.to_string().eq_ignore_ascii_case(level))
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Level::Trace => "TRACE",
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Error => "ERROR",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    Elf(elf::Error),
+    NoLogSection,
+    /// Beyond the 16 bit indices of the frames.
+    TooLarge(u32),
+    /// A malformed entry at the offset.
+    Entry(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Elf(e) => e.fmt(f),
+            Error::NoLogSection => write!(f, "no .log section (linked with `-Tlog.x`?)"),
+            Error::TooLarge(size) => write!(f, ".log section of {} bytes, over 64 KiB", size),
+            Error::Entry(at) => write!(f, "malformed .log entry at {:#x}", at),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub level: Level,
+    pub format: String,
+    /// `file:line` of the message.
+    pub location: String,
+}
+
+/// The interned entries, by index.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Table {
+    pub entries: BTreeMap<u16, Entry>,
+}
+
+impl Table {
+    /// The entries of the `.log` section of an ELF image.
+    pub fn from_elf(image: &[u8]) -> Result<Table, Error> {
+        let elf = elf::Elf::parse(image).map_err(Error::Elf)?;
+        let section = elf.section(".log").ok_or(Error::NoLogSection)?;
+        if section.size > OVERFLOW as u32 {
+            return Err(Error::TooLarge(section.size));
+        }
+        Table::parse(elf.data(section).map_err(Error::Elf)?)
+    }
+
+    /// The entries of the contents of `.log`, `[level] format 0 location 0`.
+    pub fn parse(section: &[u8]) -> Result<Table, Error> {
+        let mut entries = BTreeMap::new();
+        let mut at = 0;
+        while at < section.len() {
+            let entry = &section[at..];
+            let level = Level::from_u8(entry[0]).ok_or(Error::Entry(at))?;
+            let mut strings = entry[1..].splitn(3, |&b| b == 0);
+            let (format, location) = match (strings.next(), strings.next(), strings.next()) {
+                (Some(format), Some(location), Some(_)) => (format, location),
+                _ => return Err(Error::Entry(at)),
+            };
+            entries.insert(
+                at as u16,
+                Entry {
+                    level,
+                    format: String::from_utf8_lossy(format).into_owned(),
+                    location: String::from_utf8_lossy(location).into_owned(),
+                },
+            );
+            at += 1 + format.len() + 1 + location.len() + 1;
+        }
+        Ok(Table { entries })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record {
+    Message {
+        time: u32,
+        level: Level,
+        text: String,
+        location: String,
+    },
+    /// Frames lost since the previous record.
+    Overflow { time: u32, dropped: u32 },
+    /// An index not in the table, the capture is of another image.
+    Unknown { time: u32, index: u16 },
+}
+
+impl Record {
+    pub fn time(&self) -> u32 {
+        match self {
+            Record::Message { time, .. }
+            | Record::Overflow { time, .. }
+            | Record::Unknown { time, .. } => *time,
+        }
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Record::Message {
+                time, level, text, ..
+            } => write!(f, "{:>10} {:<5} {}", time, level, text),
+            Record::Overflow { time, dropped } => {
+                write!(f, "{:>10} -     overflow, {} dropped", time, dropped)
+            }
+            Record::Unknown { time, index } => {
+                write!(f, "{:>10} ?     unknown entry {:#06x}", time, index)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Stream {
+    pub records: Vec<Record>,
+    /// Bytes of a frame cut off at the end.
+    pub trailing: usize,
+}
+
+/// The records of a capture, formatted by `table`.
+pub fn decode(bytes: &[u8], table: &Table) -> Stream {
+    let mut s = Stream::default();
+    let mut at = 0;
+    while at < bytes.len() {
+        let len = bytes[at] as usize;
+        if len < HEADER || at + len > bytes.len() {
+            s.trailing = bytes.len() - at;
+            break;
+        }
+        let frame = &bytes[at..at + len];
+        let index = u16::from_le_bytes([frame[1], frame[2]]);
+        let time = u32::from_le_bytes([frame[3], frame[4], frame[5], frame[6]]);
+        let args = format::args(&frame[HEADER..]);
+        s.records.push(match (index, table.entries.get(&index)) {
+            (OVERFLOW, _) => Record::Overflow {
+                time,
+                dropped: match args.first() {
+                    Some(Arg::Unsigned(n)) => *n as u32,
+                    _ => 0,
+                },
+            },
+            (_, Some(entry)) => Record::Message {
+                time,
+                level: entry.level,
+                text: format::format(&entry.format, &args),
+                location: entry.location.clone(),
+            },
+            (_, None) => Record::Unknown { time, index },
+        });
+        at += len;
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fixtures::log::timing_log;
+
+    // the committed captures, decoded as recorded: a minimal image, the
+    // `.log` section of `examples/timing_log.rs`, and its frames
+    const IMAGE: &[u8] = include_bytes!("../captures/timing_log.elf");
+    const CAPTURE: &[u8] = include_bytes!("../captures/log.bin");
+
+    #[test]
+    fn table() {
+        let table = Table::from_elf(IMAGE).unwrap();
+        assert_eq!(
+            table.entries.keys().copied().collect::<Vec<_>>(),
+            vec![0, 50, 97, 157, 211]
+        );
+        assert_eq!(
+            table.entries[&157],
+            Entry {
+                level: Level::Warn,
+                format: "t1: response {:#x} > {:#x}".into(),
+                location: "examples/timing_log.rs:61".into(),
+            }
+        );
+        assert_eq!(Table::parse(&[2, b'x', 0, b'y']), Err(Error::Entry(0)));
+        assert_eq!(Table::parse(&[9, 0, 0]), Err(Error::Entry(0)));
+    }
+
+    #[test]
+    fn capture() {
+        let table = Table::from_elf(IMAGE).unwrap();
+        let s = decode(CAPTURE, &table);
+        assert_eq!(s.trailing, 0);
+        let text: Vec<String> = s.records.iter().map(|r| r.to_string()).collect();
+        assert_eq!(
+            text,
+            vec![
+                "      1093 INFO  init, period 100000 cycles",
+                "    101342 INFO  t1: count 1, response 10210 cycles",
+                "    201342 INFO  t1: count 2, response 20210 cycles",
+                "    301342 INFO  t1: count 3, response 30210 cycles",
+                "    301401 WARN  t1: response 0x7602 > 0x61a8",
+                "    801342 -     overflow, 2 dropped",
+                "    801342 INFO  t1: count 8, response 10210 cycles",
+                "    801380 INFO  t1: state [192, 255, 238]",
+            ]
+        );
+        match &s.records[4] {
+            Record::Message { location, .. } => assert_eq!(location, "examples/timing_log.rs:61"),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let table = Table::from_elf(&timing_log()).unwrap();
+        // the image of another build, and a frame cut off
+        let s = decode(&[7, 1, 0, 5, 0, 0, 0, 9, 0, 0], &table);
+        assert_eq!(s.records, vec![Record::Unknown { time: 5, index: 1 }]);
+        assert_eq!(s.trailing, 3);
+        assert_eq!(
+            Table::from_elf(&fixtures::elf::timing_itm()),
+            Err(Error::NoLogSection)
+        );
+        assert_eq!(Level::parse("warn"), Some(Level::Warn));
+    }
+}
@@ -0,0 +1,80 @@
+//! host/log/src/main.rs
+//!
+//! > cargo run -p log -- --elf image [--level l] [--location] [log.bin]
+//!
+//! Decodes a capture of the deferred formatting log of the firmware
+//! (`timing::log`, default read from stdin), e.g., the RTT channel
+//! written by OpenOCD, formatting the messages by the format strings of
+//! the `.log` section of the `--elf` image (the one flashed).
+//!
+//! `--level` lists messages of the level and above only (`trace`,
+//! `debug`, `info`, `warn` or `error`), `--location` adds the source
+//! location of each message.
+
+use log::{decode, Level, Record, Table};
+use std::{
+    env, fs,
+    io::{self, Read},
+    process,
+};
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
+
+fn main() {
+    let mut image = None;
+    let mut level = Level::Trace;
+    let mut location = false;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--elf" => image = Some(args.next().unwrap_or_else(|| fail("--elf <image>"))),
+            "--level" => {
+                level = args
+                    .next()
+                    .and_then(|l| Level::parse(&l))
+                    .unwrap_or_else(|| fail("--level <trace|debug|info|warn|error>"))
+            }
+            "--location" => location = true,
+            _ => path = Some(arg),
+        }
+    }
+    let image = image.unwrap_or_else(|| fail("--elf <image> required"));
+    let table = fs::read(&image)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| Table::from_elf(&bytes).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| fail(&format!("{}: {}", image, e)));
+
+    let mut bytes = vec![];
+    let read = match &path {
+        Some(path) => fs::read(path).map(|b| bytes = b),
+        None => io::stdin().read_to_end(&mut bytes).map(drop),
+    };
+    if let Err(e) = read {
+        fail(&format!("{}: {}", path.as_deref().unwrap_or("stdin"), e));
+    }
+    let stream = decode(&bytes, &table);
+    for r in &stream.records {
+        match r {
+            Record::Message {
+                level: l,
+                location: at,
+                ..
+            } if *l >= level => {
+                if location {
+                    println!("{}  ({})", r, at);
+                } else {
+                    println!("{}", r);
+                }
+            }
+            Record::Message { .. } => {}
+            _ => println!("{}", r),
+        }
+    }
+    if stream.trailing > 0 {
+        eprintln!("{} bytes of a frame cut off at the end", stream.trailing);
+    }
+}
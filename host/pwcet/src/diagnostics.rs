@@ -0,0 +1,220 @@
+//! host/pwcet/src/diagnostics.rs
+//!
+//! Checks of the assumptions behind a pWCET estimate, each at the 5%
+//! significance level.
+//!
+//! - Goodness of fit of the block maxima to the fitted Gumbel,
+//!   Kolmogorov-Smirnov and Anderson-Darling (Stephens' modification for
+//!   estimated parameters).
+//! - Independence of the samples, the Wald-Wolfowitz runs test (above and
+//!   below the median, in capture order).
+//! - Identical distribution, the two sample Kolmogorov-Smirnov test of the
+//!   first half of the samples against the second.
+//!
+//! The Kolmogorov-Smirnov p-value assumes known parameters, with fitted
+//! parameters it is optimistic, the Anderson-Darling test accounts for the
+//! fit and weights the tail.
+
+use crate::Gumbel;
+use std::fmt;
+
+/// Anderson-Darling 5% critical value (modified statistic, Gumbel with
+/// estimated parameters).
+pub const AD_CRITICAL: f64 = 0.757;
+
+/// Two sided standard normal 5% critical value.
+const Z_CRITICAL: f64 = 1.96;
+
+/// Two sample Kolmogorov-Smirnov 5% coefficient.
+const KS_COEFFICIENT: f64 = 1.358;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostics {
+    /// Kolmogorov-Smirnov statistic of the block maxima.
+    pub ks: f64,
+    pub ks_p: f64,
+    /// Anderson-Darling statistic of the block maxima (modified).
+    pub ad: f64,
+    /// Runs test statistic (standard normal under independence).
+    pub runs_z: f64,
+    /// Two sample Kolmogorov-Smirnov statistic of the halves.
+    pub halves: f64,
+    /// Its 5% critical value.
+    pub halves_critical: f64,
+}
+
+impl Diagnostics {
+    pub fn new(samples: &[f64], maxima: &[f64], gumbel: &Gumbel) -> Diagnostics {
+        let (ks, ks_p) = kolmogorov_smirnov(maxima, gumbel);
+        let (first, second) = samples.split_at(samples.len() / 2);
+        let (n, m) = (first.len() as f64, second.len() as f64);
+        Diagnostics {
+            ks,
+            ks_p,
+            ad: anderson_darling(maxima, gumbel),
+            runs_z: runs(samples),
+            halves: two_sample(first, second),
+            halves_critical: KS_COEFFICIENT * ((n + m) / (n * m)).sqrt(),
+        }
+    }
+
+    pub fn fit(&self) -> bool {
+        self.ks_p >= 0.05 && self.ad <= AD_CRITICAL
+    }
+
+    pub fn independent(&self) -> bool {
+        self.runs_z.abs() <= Z_CRITICAL
+    }
+
+    pub fn identical(&self) -> bool {
+        self.halves <= self.halves_critical
+    }
+
+    pub fn passed(&self) -> bool {
+        self.fit() && self.independent() && self.identical()
+    }
+}
+
+fn verdict(ok: bool) -> &'static str {
+    if ok {
+        "ok"
+    } else {
+        "FAIL"
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "fit          KS D {:.4} (p {:.3}), AD {:.3} (5% critical {})  {}",
+            self.ks,
+            self.ks_p,
+            self.ad,
+            AD_CRITICAL,
+            verdict(self.fit())
+        )?;
+        writeln!(
+            f,
+            "independent  runs z {:.2} (5% critical {})  {}",
+            self.runs_z,
+            Z_CRITICAL,
+            verdict(self.independent())
+        )?;
+        write!(
+            f,
+            "identical    halves KS D {:.4} (5% critical {:.4})  {}",
+            self.halves,
+            self.halves_critical,
+            verdict(self.identical())
+        )
+    }
+}
+
+fn sorted(x: &[f64]) -> Vec<f64> {
+    let mut x = x.to_vec();
+    x.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    x
+}
+
+/// Statistic and (asymptotic) p-value.
+fn kolmogorov_smirnov(x: &[f64], g: &Gumbel) -> (f64, f64) {
+    let x = sorted(x);
+    let n = x.len() as f64;
+    let d = x
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| {
+            let f = g.cdf(x);
+            (f - i as f64 / n).max((i + 1) as f64 / n - f)
+        })
+        .fold(0.0, f64::max);
+    // Kolmogorov distribution, with the small sample correction of
+    // Stephens
+    let lambda = (n.sqrt() + 0.12 + 0.11 / n.sqrt()) * d;
+    let p = (1..=100)
+        .map(|k| {
+            let k = k as f64;
+            2.0 * (-1f64).powf(k - 1.0) * (-2.0 * k * k * lambda * lambda).exp()
+        })
+        .sum::<f64>();
+    (d, p.clamp(0.0, 1.0))
+}
+
+fn anderson_darling(x: &[f64], g: &Gumbel) -> f64 {
+    let x = sorted(x);
+    let n = x.len();
+    // clamped, an outlier would give an infinite statistic
+    let f = |x: f64| g.cdf(x).clamp(1e-300, 1.0 - 1e-16);
+    let s: f64 = (0..n)
+        .map(|i| (2 * i + 1) as f64 * (f(x[i]).ln() + (1.0 - f(x[n - 1 - i])).ln()))
+        .sum();
+    let a2 = -(n as f64) - s / n as f64;
+    a2 * (1.0 + 0.2 / (n as f64).sqrt())
+}
+
+/// Runs above/below the median, the normal approximation.
+fn runs(x: &[f64]) -> f64 {
+    let median = {
+        let s = sorted(x);
+        s[s.len() / 2]
+    };
+    // samples at the median are dropped
+    let above: Vec<bool> = x
+        .iter()
+        .filter(|&&x| x != median)
+        .map(|&x| x > median)
+        .collect();
+    let n1 = above.iter().filter(|&&a| a).count() as f64;
+    let n2 = above.len() as f64 - n1;
+    if n1 == 0.0 || n2 == 0.0 {
+        return 0.0;
+    }
+    let runs = 1 + above.windows(2).filter(|w| w[0] != w[1]).count();
+    let n = n1 + n2;
+    let mean = 2.0 * n1 * n2 / n + 1.0;
+    let var = 2.0 * n1 * n2 * (2.0 * n1 * n2 - n) / (n * n * (n - 1.0));
+    (runs as f64 - mean) / var.sqrt()
+}
+
+/// Two sample Kolmogorov-Smirnov statistic.
+fn two_sample(a: &[f64], b: &[f64]) -> f64 {
+    let (a, b) = (sorted(a), sorted(b));
+    let (n, m) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j, mut d) = (0, 0, 0.0f64);
+    while i < a.len() && j < b.len() {
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] <= x {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= x {
+            j += 1;
+        }
+        d = d.max((i as f64 / n - j as f64 / m).abs());
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_of_a_trend() {
+        // alternating, too many runs, and sorted, too few
+        let alternating: Vec<f64> = (0..100)
+            .map(|i| (i % 2) as f64 * 10.0 + i as f64 * 0.01)
+            .collect();
+        assert!(runs(&alternating) > Z_CRITICAL);
+        let trend: Vec<f64> = (0..100).map(|i| i as f64).collect();
+        assert!(runs(&trend) < -Z_CRITICAL);
+    }
+
+    #[test]
+    fn two_sample_statistic() {
+        let a: Vec<f64> = (0..10).map(|i| i as f64).collect();
+        let b: Vec<f64> = (5..15).map(|i| i as f64).collect();
+        assert!((two_sample(&a, &b) - 0.5).abs() < 1e-12);
+        assert_eq!(two_sample(&a, &a), 0.0);
+    }
+}
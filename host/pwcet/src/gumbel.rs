@@ -0,0 +1,91 @@
+//! host/pwcet/src/gumbel.rs
+//!
+//! The Gumbel (extreme value type I) distribution,
+//! `F(x) = exp(-exp(-(x - location) / scale))`, the limit distribution of
+//! the maxima of light tailed execution times.
+
+/// Gumbel distribution.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Gumbel {
+    pub location: f64,
+    pub scale: f64,
+}
+
+impl Gumbel {
+    pub fn cdf(&self, x: f64) -> f64 {
+        (-(-(x - self.location) / self.scale).exp()).exp()
+    }
+
+    /// The value exceeded with probability `p` by the maximum of a block
+    /// of `block` samples, i.e., by any sample with probability `p / block`
+    /// (for small `p`).
+    ///
+    /// `F(x) = (1 - p)^block`, solved without cancellation for tiny `p`.
+    pub fn quantile(&self, p: f64, block: usize) -> f64 {
+        self.location - self.scale * (-(block as f64) * (-p).ln_1p()).ln()
+    }
+
+    /// Maximum likelihood fit, `None` for less than two samples or no
+    /// spread.
+    ///
+    /// The scale solves `scale = mean(x) - sum(x w) / sum(w)` for the
+    /// weights `w = exp(-x / scale)` (decreasing in the scale, found by
+    /// bisection), the location follows as
+    /// `-scale ln(mean(w))`. Weights are taken relative to the smallest
+    /// sample to stay in range.
+    pub fn fit(x: &[f64]) -> Option<Gumbel> {
+        let n = x.len() as f64;
+        let min = x.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = x.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        if x.len() < 2 || max <= min {
+            return None;
+        }
+        let mean = x.iter().sum::<f64>() / n;
+        let weights = |scale: f64| x.iter().map(move |&x| (-(x - min) / scale).exp());
+        let g = |scale: f64| {
+            let (sw, sxw) = weights(scale)
+                .zip(x)
+                .fold((0.0, 0.0), |(sw, sxw), (w, &x)| (sw + w, sxw + x * w));
+            scale - mean + sxw / sw
+        };
+        // g(lo) < 0 (the weighted mean tends to the minimum) and g(hi) > 0
+        let (mut lo, mut hi) = ((max - min) * 1e-9, (max - min) * 10.0);
+        for _ in 0..200 {
+            let mid = 0.5 * (lo + hi);
+            if g(mid) < 0.0 {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        let scale = 0.5 * (lo + hi);
+        let location = min - scale * (weights(scale).sum::<f64>() / n).ln();
+        Some(Gumbel { location, scale })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantile_inverts_cdf() {
+        let g = Gumbel {
+            location: 1000.0,
+            scale: 20.0,
+        };
+        for &p in &[0.5, 1e-3, 1e-9] {
+            let x = g.quantile(p, 1);
+            assert!((1.0 - g.cdf(x) - p).abs() < p * 1e-6);
+        }
+        // a block of 10 exceeds 10 times as often (for small `p`)
+        let x = g.quantile(1e-6, 10);
+        assert!((g.quantile(1e-5, 1) - x).abs() < 1e-3);
+    }
+
+    #[test]
+    fn degenerate() {
+        assert_eq!(Gumbel::fit(&[1.0]), None);
+        assert_eq!(Gumbel::fit(&[3.0, 3.0, 3.0]), None);
+    }
+}
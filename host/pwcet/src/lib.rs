@@ -0,0 +1,320 @@
+//! host/pwcet/src/lib.rs
+//!
+//! Measurement based probabilistic WCET (pWCET), by extreme value theory.
+//!
+//! The samples of a task (response or execution times in cycles, in
+//! capture order) are split into blocks, and the Gumbel distribution is
+//! fitted to the block maxima (maximum likelihood). The pWCET at an
+//! exceedance probability `p` is the time exceeded by a single job with
+//! probability at most `p`, extrapolated beyond the observed maximum.
+//!
+//! The estimate is only as good as its assumptions, see [`Diagnostics`]
+//! for the checks of the fit and of independent, identically distributed
+//! samples.
+//!
+//! Samples are read from text, an RTT log with a line per sample, e.g.,
+//! `rprintln!("{}: sample {}", T1_RT.name(), T1_RT.record(rt))`,
+//!
+//! ```text
+//! t1: sample 40632
+//! ```
+//!
+//! or a task event trace (`rtic_trace`), taking the response of each
+//! completed job.
+
+use rtic_trace::Kind;
+use std::{collections::BTreeMap, fmt};
+
+mod diagnostics;
+mod gumbel;
+
+pub use diagnostics::Diagnostics;
+pub use gumbel::Gumbel;
+
+/// Default block size.
+pub const BLOCK: usize = 50;
+
+/// Fewest blocks for a fit.
+pub const MIN_BLOCKS: usize = 20;
+
+/// Default exceedance probabilities (per job).
+pub const EXCEEDANCE: [f64; 4] = [1e-3, 1e-6, 1e-9, 1e-12];
+
+/// Samples by task, in capture order.
+pub type Samples = BTreeMap<String, Vec<u64>>;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    TooFewSamples {
+        samples: usize,
+        needed: usize,
+    },
+    /// All block maxima equal, nothing to extrapolate.
+    NoVariation(u64),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::TooFewSamples { samples, needed } => write!(
+                f,
+                "{} samples, at least {} needed ({} blocks)",
+                samples, needed, MIN_BLOCKS
+            ),
+            Error::NoVariation(max) => {
+                write!(f, "all block maxima are {}, no variation to fit", max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Parses the sample lines and completed jobs of `text`.
+pub fn parse(text: &str) -> Samples {
+    let mut samples = Samples::new();
+    for line in text.lines() {
+        if let Some((task, sample)) = parse_line(line.trim()) {
+            samples.entry(task.to_string()).or_default().push(sample);
+        }
+    }
+    samples
+}
+
+fn parse_line(line: &str) -> Option<(String, u64)> {
+    if let Some((task, rest)) = line.split_once(": sample ") {
+        return Some((task.to_string(), rest.trim().parse().ok()?));
+    }
+    match line.parse::<rtic_trace::Event>().ok()? {
+        rtic_trace::Event {
+            task,
+            kind: Kind::Complete { response, .. },
+            ..
+        } => Some((task, response)),
+        _ => None,
+    }
+}
+
+/// A pWCET estimate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimate {
+    pub samples: usize,
+    pub max: u64,
+    pub block: usize,
+    /// Fit of the block maxima.
+    pub gumbel: Gumbel,
+    pub diagnostics: Diagnostics,
+}
+
+impl Estimate {
+    /// The pWCET (cycles, rounded up) at exceedance probability `p` per job,
+    /// clamped to `0..=u64::MAX`. `u64::MAX` for `p = 0` and, conservatively,
+    /// for `p` beyond `0..=1` (no quantile).
+    pub fn pwcet(&self, p: f64) -> u64 {
+        let q = self.gumbel.quantile(p, self.block).ceil();
+        if q.is_nan() || q >= u64::MAX as f64 {
+            u64::MAX
+        } else {
+            q.max(0.0) as u64
+        }
+    }
+
+    /// Table of the pWCET at the `exceedance` probabilities, with the
+    /// diagnostics.
+    pub fn report<'a>(&'a self, exceedance: &'a [f64]) -> Report<'a> {
+        Report {
+            estimate: self,
+            exceedance,
+        }
+    }
+}
+
+/// Estimates the pWCET of `samples` (in capture order), in blocks of
+/// `block` samples (a trailing partial block is dropped).
+pub fn estimate(samples: &[u64], block: usize) -> Result<Estimate, Error> {
+    let block = block.max(1);
+    let needed = block * MIN_BLOCKS;
+    if samples.len() < needed {
+        return Err(Error::TooFewSamples {
+            samples: samples.len(),
+            needed,
+        });
+    }
+    let x: Vec<f64> = samples.iter().map(|&s| s as f64).collect();
+    let maxima: Vec<f64> = x
+        .chunks_exact(block)
+        .map(|b| b.iter().cloned().fold(f64::NEG_INFINITY, f64::max))
+        .collect();
+    let max = samples.iter().cloned().max().unwrap_or(0);
+    let gumbel = Gumbel::fit(&maxima).ok_or(Error::NoVariation(max))?;
+    Ok(Estimate {
+        samples: samples.len(),
+        max,
+        block,
+        diagnostics: Diagnostics::new(&x, &maxima, &gumbel),
+        gumbel,
+    })
+}
+
+/// See [`Estimate::report`].
+pub struct Report<'a> {
+    estimate: &'a Estimate,
+    exceedance: &'a [f64],
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let e = self.estimate;
+        writeln!(
+            f,
+            "{} samples, max {}, {} blocks of {}, Gumbel location {:.1} scale {:.1}",
+            e.samples,
+            e.max,
+            e.samples / e.block,
+            e.block,
+            e.gumbel.location,
+            e.gumbel.scale
+        )?;
+        writeln!(f, "{}", e.diagnostics)?;
+        write!(f, "{:>12} {:>12} {:>8}", "exceedance", "pwcet", "margin")?;
+        for &p in self.exceedance {
+            let pwcet = e.pwcet(p);
+            // over the observed maximum, none over a maximum of 0
+            let margin = if e.max == 0 {
+                "-".to_string()
+            } else {
+                format!("{:.1}%", 100.0 * (pwcet as f64 / e.max as f64 - 1.0))
+            };
+            write!(f, "\n{:>12.0e} {:>12} {:>8}", p, pwcet, margin)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taskgen::Rng;
+
+    /// Samples of a Gumbel, by inversion.
+    fn gumbel(rng: &mut Rng, n: usize, g: &Gumbel) -> Vec<u64> {
+        (0..n)
+            .map(|_| (g.location - g.scale * (-rng.f64().max(1e-300).ln()).ln()) as u64)
+            .collect()
+    }
+
+    #[test]
+    fn parse_samples() {
+        let text = "\
+init
+t1: sample 40632
+t1: count 1 min 40632 max 40632 mean 40632
+    130630 t1       entry
+    171342 t1       complete, response 40712 deadline 100000
+t3: sample 30362
+t1: sample 40100
+";
+        let s = parse(text);
+        assert_eq!(s["t1"], vec![40632, 40712, 40100]);
+        assert_eq!(s["t3"], vec![30362]);
+        assert_eq!(s.len(), 2);
+    }
+
+    #[test]
+    fn recovers_gumbel() {
+        // each diagnostic rejects 5% of the seeds, this one passes
+        let mut rng = Rng::new(4);
+        let g = Gumbel {
+            location: 40_000.0,
+            scale: 50.0,
+        };
+        // the maximum of 50 Gumbel samples is Gumbel, shifted by
+        // `scale ln(50)`
+        let samples = gumbel(&mut rng, 20_000, &g);
+        let e = estimate(&samples, BLOCK).unwrap();
+        let location = g.location + g.scale * (BLOCK as f64).ln();
+        assert!(
+            (e.gumbel.location - location).abs() < 10.0,
+            "{:?}",
+            e.gumbel
+        );
+        assert!((e.gumbel.scale - g.scale).abs() < 5.0, "{:?}", e.gumbel);
+        assert!(e.diagnostics.passed(), "{}", e.diagnostics);
+        // the true quantiles of a single job
+        for &p in &EXCEEDANCE {
+            let truth = g.quantile(p, 1);
+            let pwcet = e.pwcet(p) as f64;
+            assert!((pwcet - truth).abs() < 0.02 * truth, "{} {}", pwcet, truth);
+            assert!(pwcet >= e.max as f64 || p > 1.0 / samples.len() as f64);
+        }
+        // increasing as the exceedance decreases
+        assert!(e.pwcet(1e-12) > e.pwcet(1e-9) && e.pwcet(1e-9) > e.pwcet(1e-6));
+    }
+
+    #[test]
+    fn exponential_tail() {
+        // exponential execution times are in the Gumbel domain
+        let mut rng = Rng::new(2);
+        let samples: Vec<u64> = (0..100_000)
+            .map(|_| 10_000 + (-200.0 * (1.0 - rng.f64()).ln()) as u64)
+            .collect();
+        let e = estimate(&samples, 100).unwrap();
+        assert!(e.diagnostics.fit(), "{}", e.diagnostics);
+        // P(X > x) = exp(-(x - 10000) / 200)
+        let truth = 10_000.0 + 200.0 * (1e9f64).ln();
+        assert!(
+            (e.pwcet(1e-9) as f64 - truth).abs() < 0.02 * truth,
+            "{} {} {:?}",
+            e.pwcet(1e-9),
+            truth,
+            e.gumbel
+        );
+    }
+
+    #[test]
+    fn diagnoses() {
+        let mut rng = Rng::new(3);
+        let g = Gumbel {
+            location: 1_000.0,
+            scale: 20.0,
+        };
+        // a drift, neither independent nor identically distributed
+        let drift: Vec<u64> = gumbel(&mut rng, 5_000, &g)
+            .iter()
+            .enumerate()
+            .map(|(i, &s)| s + i as u64 / 10)
+            .collect();
+        let e = estimate(&drift, BLOCK).unwrap();
+        assert!(!e.diagnostics.independent() && !e.diagnostics.identical());
+        assert!(!e.diagnostics.passed());
+        assert!(e.report(&EXCEEDANCE).to_string().contains("FAIL"));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            estimate(&[1, 2, 3], BLOCK),
+            Err(Error::TooFewSamples {
+                samples: 3,
+                needed: 1000
+            })
+        );
+        assert_eq!(estimate(&[7; 1000], BLOCK), Err(Error::NoVariation(7)));
+    }
+
+    #[test]
+    fn saturates() {
+        let g = Gumbel {
+            location: 1_000.0,
+            scale: 20.0,
+        };
+        let mut e = estimate(&gumbel(&mut Rng::new(1), 1_000, &g), BLOCK).unwrap();
+        assert_eq!(e.pwcet(0.0), u64::MAX);
+        assert_eq!(e.pwcet(2.0), u64::MAX);
+        assert_eq!(e.pwcet(1.0), 0);
+        e.max = 0;
+        let report = e.report(&[1e-3]).to_string();
+        assert!(report.ends_with(&format!("{:>12} {:>8}", e.pwcet(1e-3), "-")));
+        assert!(!report.contains("inf") && !report.contains("NaN"));
+    }
+}
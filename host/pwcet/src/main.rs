@@ -0,0 +1,81 @@
+//! host/pwcet/src/main.rs
+//!
+//! > cargo run -p pwcet -- [--block n] [--exceedance p,p,..] [--task id] [samples]
+//!
+//! Estimates the pWCET of each task with samples (or `--task id`) in an
+//! RTT log or event trace (default stdin), at the `--exceedance`
+//! probabilities per job (default `1e-3,1e-6,1e-9,1e-12`).
+//!
+//! Exits with 2 if a diagnostic fails.
+
+use pwcet::{estimate, parse, BLOCK, EXCEEDANCE};
+use std::{
+    env, fs,
+    io::{self, Read},
+    process,
+};
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
+
+fn main() {
+    let mut block = BLOCK;
+    let mut exceedance = EXCEEDANCE.to_vec();
+    let mut task = None;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--block" => {
+                block = args
+                    .next()
+                    .and_then(|b| b.parse().ok())
+                    .unwrap_or_else(|| fail("--block <n>"))
+            }
+            "--exceedance" => {
+                exceedance = args
+                    .next()
+                    .and_then(|e| e.split(',').map(|p| p.parse().ok()).collect())
+                    .unwrap_or_else(|| fail("--exceedance <p>,<p>,.."))
+            }
+            "--task" => task = Some(args.next().unwrap_or_else(|| fail("--task <id>"))),
+            _ => path = Some(arg),
+        }
+    }
+
+    let text = match &path {
+        Some(path) => {
+            fs::read_to_string(path).unwrap_or_else(|e| fail(&format!("{}: {}", path, e)))
+        }
+        None => {
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .unwrap_or_else(|e| fail(&e.to_string()));
+            text
+        }
+    };
+    let mut samples = parse(&text);
+    if let Some(task) = task {
+        samples.retain(|t, _| *t == task);
+    }
+    if samples.is_empty() {
+        fail("no samples");
+    }
+
+    let mut passed = true;
+    for (task, samples) in &samples {
+        match estimate(samples, block) {
+            Ok(e) => {
+                passed &= e.diagnostics.passed();
+                println!("{}: {}\n", task, e.report(&exceedance));
+            }
+            Err(e) => println!("{}: {}\n", task, e),
+        }
+    }
+    if !passed {
+        process::exit(2)
+    }
+}
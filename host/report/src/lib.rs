@@ -0,0 +1,363 @@
+//! host/report/src/lib.rs
+//!
+//! Measured response times against the analysed bounds of a task set.
+//!
+//! Measurements are the `ResponseTimeMonitor` statistics of the firmware,
+//! either from an RTT log (the `Display` output of the monitors, one line
+//! per task)
+//!
+//! ```text
+//! t1: count 12 min 10211 max 40632 mean 20443
+//! ```
+//!
+//! or from memory dumps of the `#[repr(C)]` statistics (24 bytes, little
+//! endian) taken in gdb:
+//!
+//! ```text
+//! (gdb) dump binary value t1.bin T1_RT.stats.value.value
+//! ```
+//!
+//! A measured response beyond the analysed bound means that the model
+//! (or the analysis) is unsafe for the implementation.
+
+use serde::Serialize;
+use srp_analysis::Analysis;
+use std::{collections::BTreeMap, fmt};
+use taskset::TaskSet;
+
+/// Measured response times of a task (in cycles).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct Measured {
+    pub count: u32,
+    pub min: u32,
+    pub max: u32,
+    pub mean: u32,
+}
+
+/// Measurements by task id.
+pub type Measurements = BTreeMap<String, Measured>;
+
+/// Size of a `timing::Stats` dump.
+pub const DUMP_SIZE: usize = 24;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    /// A dump of unexpected size.
+    DumpSize(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::DumpSize(size) => write!(
+                f,
+                "dump of {} bytes, expected {} (`timing::Stats`)",
+                size, DUMP_SIZE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Parses the response time monitor lines of an RTT log,
+/// later lines (of the same task) override earlier.
+pub fn parse_rtt(log: &str) -> Measurements {
+    let mut measurements = Measurements::new();
+    for line in log.lines() {
+        if let Some((task, measured)) = parse_line(line.trim()) {
+            measurements.insert(task.to_string(), measured);
+        }
+    }
+    measurements
+}
+
+/// `<task>: count <n> min <min> max <max> mean <mean>`
+fn parse_line(line: &str) -> Option<(&str, Measured)> {
+    let (task, stats) = line.split_at(line.find(": ")?);
+    let fields: Vec<&str> = stats[2..].split_whitespace().collect();
+    match fields.as_slice() {
+        ["count", count, "min", min, "max", max, "mean", mean] => Some((
+            task,
+            Measured {
+                count: count.parse().ok()?,
+                min: min.parse().ok()?,
+                max: max.parse().ok()?,
+                mean: mean.parse().ok()?,
+            },
+        )),
+        _ => None,
+    }
+}
+
+/// Parses a memory dump of a `timing::Stats`, `None` if nothing recorded.
+pub fn parse_dump(dump: &[u8]) -> Result<Option<Measured>, Error> {
+    if dump.len() != DUMP_SIZE {
+        return Err(Error::DumpSize(dump.len()));
+    }
+    let u32_at = |i: usize| {
+        let mut b = [0; 4];
+        b.copy_from_slice(&dump[i..i + 4]);
+        u32::from_le_bytes(b)
+    };
+    let mut sum = [0; 8];
+    sum.copy_from_slice(&dump[16..24]);
+    let (count, min, max, sum) = (u32_at(0), u32_at(4), u32_at(8), u64::from_le_bytes(sum));
+    Ok(if count == 0 {
+        None
+    } else {
+        Some(Measured {
+            count,
+            min,
+            max,
+            mean: (sum / count as u64) as u32,
+        })
+    })
+}
+
+/// Outcome for a task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Measured within the bound.
+    Ok,
+    /// Measured beyond the bound, the model is unsafe.
+    Unsafe,
+    /// No measurement.
+    Missing,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Status::Ok => "ok",
+            Status::Unsafe => "UNSAFE",
+            Status::Missing => "missing",
+        })
+    }
+}
+
+/// Measured against analysed response time of a task, in cycles.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Row {
+    pub task: String,
+    pub prio: u8,
+    pub deadline: u64,
+    pub bound: u64,
+    pub measured: Option<Measured>,
+    /// Bound minus measured maximum.
+    pub slack: Option<i64>,
+    pub status: Status,
+}
+
+/// The comparison of a task set.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Report {
+    pub rows: Vec<Row>,
+}
+
+impl Report {
+    pub fn new(set: &TaskSet, analysis: &Analysis, measurements: &Measurements) -> Report {
+        let cycles = |units: u32| units as u64 * set.unit as u64;
+        Report {
+            rows: analysis
+                .tasks
+                .iter()
+                .map(|t| {
+                    let bound = cycles(t.response);
+                    let measured = measurements.get(&t.id).copied();
+                    let slack = measured.map(|m| bound as i64 - m.max as i64);
+                    Row {
+                        task: t.id.clone(),
+                        prio: t.prio,
+                        deadline: cycles(t.deadline),
+                        bound,
+                        measured,
+                        slack,
+                        status: match slack {
+                            None => Status::Missing,
+                            Some(slack) if slack < 0 => Status::Unsafe,
+                            Some(_) => Status::Ok,
+                        },
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Tasks measured beyond their bound.
+    pub fn unsafe_tasks(&self) -> Vec<&str> {
+        self.rows
+            .iter()
+            .filter(|r| r.status == Status::Unsafe)
+            .map(|r| r.task.as_str())
+            .collect()
+    }
+
+    pub fn csv(&self) -> String {
+        let mut s = String::from("task,prio,deadline,bound,count,min,max,mean,slack,status\n");
+        for r in &self.rows {
+            let opt = |v: Option<String>| v.unwrap_or_default();
+            let m = r.measured;
+            s.push_str(&format!(
+                "{},{},{},{},{},{},{},{},{},{}\n",
+                r.task,
+                r.prio,
+                r.deadline,
+                r.bound,
+                opt(m.map(|m| m.count.to_string())),
+                opt(m.map(|m| m.min.to_string())),
+                opt(m.map(|m| m.max.to_string())),
+                opt(m.map(|m| m.mean.to_string())),
+                opt(r.slack.map(|s| s.to_string())),
+                r.status.to_string().to_lowercase()
+            ));
+        }
+        s
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<8} {:>4} {:>10} {:>10} {:>10} {:>10}  status",
+            "task", "prio", "measured", "bound", "slack", "deadline"
+        )?;
+        for r in &self.rows {
+            let dash = || "-".to_string();
+            writeln!(
+                f,
+                "{:<8} {:>4} {:>10} {:>10} {:>10} {:>10}  {}",
+                r.task,
+                r.prio,
+                r.measured.map_or_else(dash, |m| m.max.to_string()),
+                r.bound,
+                r.slack.map_or_else(dash, |s| s.to_string()),
+                r.deadline,
+                r.status
+            )?;
+        }
+        let unsafe_tasks = self.unsafe_tasks();
+        if unsafe_tasks.is_empty() {
+            write!(f, "all measurements within the analysed bounds")
+        } else {
+            write!(f, "model unsafe for: {}", unsafe_tasks.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAM: &str = include_str!("../../../tasks/timing_exam.toml");
+
+    const LOG: &str = "\
+calibration (release): read 1 dispatch 240 switch 44
+t3: count 20 min 30020 max 34200 mean 30410
+t3: count 20 min 620 max 4220 mean 1100 hist/1000 [15 0 0 4 1 0 0 0]
+t3: deadline 50000 misses 0 max lateness 0
+t1: count 9 min 10050 max 40611 mean 30102
+t2: count 4 min 30000 max 96004 mean 70012
+t1: count 10 min 10050 max 40611 mean 30100
+";
+
+    fn exam() -> (TaskSet, Analysis) {
+        let set = TaskSet::from_toml(EXAM).unwrap();
+        let analysis = srp_analysis::analyse(&set);
+        (set, analysis)
+    }
+
+    #[test]
+    fn rtt() {
+        let m = parse_rtt(LOG);
+        assert_eq!(m.len(), 3);
+        assert_eq!(
+            m["t1"],
+            Measured {
+                count: 10,
+                min: 10050,
+                max: 40611,
+                mean: 30100
+            }
+        );
+        assert_eq!(m["t3"].max, 34200);
+    }
+
+    #[test]
+    fn dump() {
+        let mut dump = vec![];
+        for v in &[4u32, 30_020, 34_200, 0] {
+            dump.extend_from_slice(&v.to_le_bytes());
+        }
+        dump.extend_from_slice(&121_640u64.to_le_bytes());
+        assert_eq!(
+            parse_dump(&dump),
+            Ok(Some(Measured {
+                count: 4,
+                min: 30_020,
+                max: 34_200,
+                mean: 30_410
+            }))
+        );
+        let mut empty = vec![0; DUMP_SIZE];
+        empty[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(parse_dump(&empty), Ok(None));
+        assert_eq!(parse_dump(&dump[..20]), Err(Error::DumpSize(20)));
+    }
+
+    #[test]
+    fn report() {
+        let (set, analysis) = exam();
+        let report = Report::new(&set, &analysis, &parse_rtt(LOG));
+        // t2 measured 96_004 against a bound of 90_000,
+        // t3 34_200 against 34_000 (the model ignores overheads)
+        assert_eq!(report.unsafe_tasks(), vec!["t2", "t3"]);
+        assert_eq!(
+            report.to_string(),
+            "\
+task     prio   measured      bound      slack   deadline  status
+t1          1      40611     100000      59389     100000  ok
+t2          2      96004      90000      -6004     200000  UNSAFE
+t3          3      34200      34000       -200      50000  UNSAFE
+model unsafe for: t2, t3"
+        );
+    }
+
+    #[test]
+    fn missing() {
+        let (set, analysis) = exam();
+        let report = Report::new(&set, &analysis, &Measurements::new());
+        assert!(report.rows.iter().all(|r| r.status == Status::Missing));
+        assert!(report.unsafe_tasks().is_empty());
+        assert!(report.csv().ends_with("t3,3,50000,34000,,,,,,missing\n"));
+    }
+
+    #[test]
+    fn csv_and_json() {
+        let (set, analysis) = exam();
+        let mut m = parse_rtt(LOG);
+        m.remove("t2");
+        m.remove("t3");
+        let report = Report::new(&set, &analysis, &m);
+        assert_eq!(
+            report.csv(),
+            "\
+task,prio,deadline,bound,count,min,max,mean,slack,status
+t1,1,100000,100000,10,10050,40611,30100,59389,ok
+t2,2,200000,90000,,,,,,missing
+t3,3,50000,34000,,,,,,missing
+"
+        );
+        let json: serde_json::Value = serde_json::from_str(&report.json()).unwrap();
+        assert_eq!(json["rows"][0]["measured"]["max"], 40611);
+        assert_eq!(json["rows"][0]["slack"], 59389);
+        assert_eq!(json["rows"][0]["status"], "ok");
+        assert_eq!(json["rows"][1]["measured"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,107 @@
+//! host/report/src/main.rs
+//!
+//! > cargo run -p report -- [options] <task set> [rtt log]
+//!
+//! Options:
+//!
+//! - `--dump <task>=<file>`, measurement from a memory dump (repeatable),
+//!   in addition to (overriding) the RTT log.
+//! - `--approx`, compare to the approximate analysis.
+//! - `--overheads <file>`, bounds including the runtime overheads (TOML,
+//!   see `taskset::Overheads`).
+//! - `--calibrated`, bounds including the runtime overheads of the
+//!   calibration line of the RTT log (other costs from `--overheads`).
+//! - `--csv`, `--json`, machine readable output.
+//!
+//! Exits with 2 if some measurement exceeds its bound.
+
+use report::{parse_dump, parse_rtt, Report};
+use srp_analysis::{analyse, analyse_approx, overheads::inflate};
+use std::{env, fmt::Display, fs, process};
+use taskset::{Overheads, TaskSet};
+
+enum Format {
+    Text,
+    Csv,
+    Json,
+}
+
+fn fail(what: &str, e: impl Display) -> ! {
+    eprintln!("{}: {}", what, e);
+    process::exit(1)
+}
+
+fn main() {
+    let mut approx = false;
+    let mut overheads = None;
+    let mut calibrated = false;
+    let mut format = Format::Text;
+    let mut dumps = vec![];
+    let mut paths = vec![];
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--approx" => approx = true,
+            "--overheads" => {
+                let file = args
+                    .next()
+                    .unwrap_or_else(|| fail("--overheads", "expected <file>"));
+                let toml = fs::read_to_string(&file).unwrap_or_else(|e| fail(&file, e));
+                overheads = Some(Overheads::from_toml(&toml).unwrap_or_else(|e| fail(&file, e)));
+            }
+            "--calibrated" => calibrated = true,
+            "--csv" => format = Format::Csv,
+            "--json" => format = Format::Json,
+            "--dump" => match args.next().as_deref().and_then(|d| d.split_once('=')) {
+                Some((task, file)) => dumps.push((task.to_string(), file.to_string())),
+                None => fail("--dump", "expected <task>=<file>"),
+            },
+            _ => paths.push(arg),
+        }
+    }
+    let (set, log) = match paths.as_slice() {
+        [set] => (set, None),
+        [set, log] => (set, Some(log)),
+        _ => fail("usage", "report [options] <task set> [rtt log]"),
+    };
+
+    let mut set = TaskSet::load(set).unwrap_or_else(|e| fail(set, e));
+    let log = log.map(|log| fs::read_to_string(log).unwrap_or_else(|e| fail(log, e)));
+    let mut measurements = log.as_deref().map(parse_rtt).unwrap_or_default();
+    for (task, file) in dumps {
+        let dump = fs::read(&file).unwrap_or_else(|e| fail(&file, e));
+        match parse_dump(&dump).unwrap_or_else(|e| fail(&file, e)) {
+            Some(measured) => measurements.insert(task, measured),
+            None => measurements.remove(&task),
+        };
+    }
+
+    let tasks: Vec<String> = set.tasks.iter().map(|t| t.id.clone()).collect();
+    if overheads.is_some() || calibrated {
+        let mut o = overheads.unwrap_or_default();
+        if calibrated {
+            o = log
+                .as_deref()
+                .and_then(|log| o.calibrated(log))
+                .unwrap_or_else(|| fail("--calibrated", "no calibration line in the RTT log"));
+        }
+        set = inflate(&set, &o, None);
+    }
+
+    let analysis = if approx {
+        analyse_approx(&set)
+    } else {
+        analyse(&set)
+    };
+    let mut report = Report::new(&set, &analysis, &measurements);
+    // not the timer handler of the inflated task set
+    report.rows.retain(|r| tasks.contains(&r.task));
+    match format {
+        Format::Text => println!("{}", report),
+        Format::Csv => print!("{}", report.csv()),
+        Format::Json => println!("{}", report.json()),
+    }
+    if !report.unsafe_tasks().is_empty() {
+        process::exit(2)
+    }
+}
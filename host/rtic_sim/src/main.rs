@@ -0,0 +1,59 @@
+//! host/rtic_sim/src/main.rs
+//!
+//! > cargo run -p rtic_sim -- [--overheads file.toml] [--timer-prio n] [task set]
+//!
+//! Simulates a task set (default `../tasks/timing_exam.toml`), prints the
+//! event trace and the largest response time per task (in cycles).
+
+use rtic_sim::{simulate, Config};
+use std::{env, fs, process};
+use taskset::{Overheads, TaskSet};
+
+fn main() {
+    let mut config = Config::default();
+    let mut path = "../tasks/timing_exam.toml".to_string();
+    let mut args = env::args().skip(1);
+    let fail = |msg: String| -> ! {
+        eprintln!("{}", msg);
+        process::exit(1)
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--overheads" => {
+                let file = args
+                    .next()
+                    .unwrap_or_else(|| fail("--overheads <file>".into()));
+                let toml =
+                    fs::read_to_string(&file).unwrap_or_else(|e| fail(format!("{}: {}", file, e)));
+                config.overheads = Overheads::from_toml(&toml)
+                    .unwrap_or_else(|e| fail(format!("{}: {}", file, e)));
+            }
+            "--timer-prio" => {
+                let prio = args.next().and_then(|p| p.parse().ok());
+                config.timer_prio =
+                    Some(prio.unwrap_or_else(|| fail("--timer-prio <priority>".into())));
+            }
+            _ => path = arg,
+        }
+    }
+
+    let set = TaskSet::load(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    let sim = simulate(&set, &config).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    for event in &sim.events {
+        println!("{}", event);
+    }
+    println!("\nreleases simulated in 0..{} cycles", sim.horizon);
+    println!("{:<8} {:>10} {:>10}", "task", "response", "deadline");
+    for t in &set.tasks {
+        println!(
+            "{:<8} {:>10} {:>10}",
+            t.id,
+            sim.max_response(&t.id)
+                .map_or_else(|| "-".to_string(), |r| r.to_string()),
+            t.deadline as u64 * set.unit as u64
+        );
+    }
+    if sim.misses().next().is_some() {
+        process::exit(2)
+    }
+}
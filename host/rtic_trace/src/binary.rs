@@ -0,0 +1,300 @@
+//! host/rtic_trace/src/binary.rs
+//!
+//! Decoder of the binary trace of `timing::trace::Tracer`, as captured
+//! from its RTT up-channel, e.g., by OpenOCD
+//!
+//! ```text
+//! (gdb) monitor rtt setup 0x20000000 0x10000 "SEGGER RTT"
+//! (gdb) monitor rtt start
+//! (gdb) monitor rtt server start 9091 1
+//! > nc localhost 9091 > trace.bin
+//! ```
+//!
+//! Records are 6 bytes, `[tag] [id] [CYCCNT, 4 bytes little endian]`,
+//! names are announced by `[0x10] [task] [len] [name]` and
+//! `[0x11] [resource] [ceiling] [len] [name]`, see the firmware side for
+//! the details.
+//!
+//! The 32 bit timestamps are extended to 64 bits, assuming less than
+//! 2^32 cycles between consecutive records. Preemptions and resumptions
+//! are inferred from the nesting of task entries and exits, so the
+//! events can be charted as the simulated ones.
+
+use crate::{Event, Kind};
+use std::{collections::BTreeMap, fmt};
+
+pub const ENTER: u8 = 0x01;
+pub const EXIT: u8 = 0x02;
+pub const LOCK: u8 = 0x03;
+pub const UNLOCK: u8 = 0x04;
+pub const PEND: u8 = 0x05;
+pub const SCHEDULE: u8 = 0x06;
+pub const OVERFLOW: u8 = 0x07;
+pub const TASK_NAME: u8 = 0x10;
+pub const RESOURCE_NAME: u8 = 0x11;
+
+/// Size of a timestamped record.
+pub const RECORD: usize = 6;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// Unknown record tag at `offset`.
+    Tag { offset: usize, tag: u8 },
+    /// The capture ends within the record at `offset`.
+    Truncated { offset: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::Tag { offset, tag } => {
+                write!(f, "unknown record tag {:#04x} at offset {}", tag, offset)
+            }
+            DecodeError::Truncated { offset } => {
+                write!(f, "capture truncated in the record at offset {}", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+#[derive(Default)]
+struct Decoder {
+    events: Vec<Event>,
+    tasks: BTreeMap<u8, String>,
+    /// Name and ceiling.
+    resources: BTreeMap<u8, (String, u8)>,
+    /// Running tasks, innermost last.
+    running: Vec<u8>,
+    time: u64,
+    started: bool,
+}
+
+impl Decoder {
+    fn task(&self, id: u8) -> String {
+        self.tasks
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| format!("task{}", id))
+    }
+
+    fn resource(&self, id: u8) -> (String, u8) {
+        self.resources
+            .get(&id)
+            .cloned()
+            .unwrap_or_else(|| (format!("res{}", id), 0))
+    }
+
+    fn current(&self) -> String {
+        self.running
+            .last()
+            .map_or_else(|| "-".to_string(), |&id| self.task(id))
+    }
+
+    fn event(&mut self, task: String, kind: Kind) {
+        self.events.push(Event {
+            time: self.time,
+            task,
+            kind,
+        });
+    }
+
+    /// Extends `stamp` relative to the previous timestamp.
+    fn stamp(&mut self, stamp: u32) {
+        self.time = if self.started {
+            self.time + stamp.wrapping_sub(self.time as u32) as u64
+        } else {
+            stamp as u64
+        };
+        self.started = true;
+    }
+
+    fn record(&mut self, tag: u8, id: u8) {
+        match tag {
+            ENTER => {
+                let task = self.task(id);
+                if !self.running.is_empty() {
+                    let by = task.clone();
+                    self.event(self.current(), Kind::Preempted { by });
+                }
+                self.running.push(id);
+                self.event(task.clone(), Kind::Dispatch);
+                self.event(task, Kind::Entry);
+            }
+            EXIT => {
+                if let Some(i) = self.running.iter().rposition(|&r| r == id) {
+                    self.running.remove(i);
+                }
+                self.event(self.task(id), Kind::Exit);
+                match self.running.last() {
+                    Some(_) => self.event(self.current(), Kind::Resume),
+                    None => self.event("-".to_string(), Kind::Idle),
+                }
+            }
+            LOCK => {
+                let (resource, ceiling) = self.resource(id);
+                self.event(self.current(), Kind::Lock { resource, ceiling });
+            }
+            UNLOCK => {
+                let (resource, _) = self.resource(id);
+                self.event(self.current(), Kind::Unlock { resource });
+            }
+            PEND => self.event(self.task(id), Kind::Release),
+            SCHEDULE => self.event(self.task(id), Kind::Schedule),
+            _ => self.event("-".to_string(), Kind::Overflow { dropped: id as u32 }),
+        }
+    }
+}
+
+/// Decodes a captured byte stream into events.
+pub fn decode(bytes: &[u8]) -> Result<Vec<Event>, DecodeError> {
+    let mut d = Decoder::default();
+    let mut at = 0;
+    while at < bytes.len() {
+        let offset = at;
+        let byte = |i: usize| {
+            bytes
+                .get(offset + i)
+                .copied()
+                .ok_or(DecodeError::Truncated { offset })
+        };
+        let name = |start: usize| -> Result<(String, usize), DecodeError> {
+            let len = byte(start)? as usize;
+            let name = bytes
+                .get(offset + start + 1..offset + start + 1 + len)
+                .ok_or(DecodeError::Truncated { offset })?;
+            Ok((String::from_utf8_lossy(name).into_owned(), start + 1 + len))
+        };
+        match bytes[at] {
+            TASK_NAME => {
+                let id = byte(1)?;
+                let (name, len) = name(2)?;
+                d.tasks.insert(id, name);
+                at += len;
+            }
+            RESOURCE_NAME => {
+                let (id, ceiling) = (byte(1)?, byte(2)?);
+                let (name, len) = name(3)?;
+                d.resources.insert(id, (name, ceiling));
+                at += len;
+            }
+            tag @ ENTER..=OVERFLOW => {
+                let record = bytes
+                    .get(at..at + RECORD)
+                    .ok_or(DecodeError::Truncated { offset })?;
+                d.stamp(u32::from_le_bytes([
+                    record[2], record[3], record[4], record[5],
+                ]));
+                d.record(tag, record[1]);
+                at += RECORD;
+            }
+            tag => return Err(DecodeError::Tag { offset, tag }),
+        }
+    }
+    Ok(d.events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chart;
+
+    /// The committed capture, decoded as recorded (the other tests build
+    /// the same bytes by `fixtures`).
+    const EXAM: &[u8] = include_bytes!("../captures/exam.bin");
+
+    fn text(events: &[Event]) -> String {
+        events.iter().map(|e| format!("\n{}", e)).collect()
+    }
+
+    #[test]
+    fn capture() {
+        let events = decode(EXAM).unwrap();
+        assert_eq!(
+            text(&events),
+            "
+    200012 t2       release
+    200240 t2       dispatch
+    200240 t2       entry
+    200250 t2       schedule
+    210250 t2       lock R1 (ceiling 2)
+    212270 t2       lock R2 (ceiling 3)
+    216290 t2       unlock R2
+    218000 t3       release
+    218240 t2       preempted by t3
+    218240 t3       dispatch
+    218240 t3       entry
+    218250 t3       schedule
+    228250 t3       lock R2 (ceiling 3)
+    238290 t3       unlock R2
+    248330 t3       exit
+    248330 t2       resume
+    250000 t2       unlock R1
+    260000 t2       exit
+    260000 -        idle"
+        );
+        // as a text trace
+        assert_eq!(crate::parse(&text(&events)), events);
+        let chart = Chart::new(&events);
+        assert_eq!(
+            chart
+                .rows
+                .iter()
+                .map(|r| r.task.as_str())
+                .collect::<Vec<_>>(),
+            vec!["t2", "t3"]
+        );
+    }
+
+    #[test]
+    fn wrapping_timestamps() {
+        let mut bytes = vec![];
+        for (tag, time) in [(PEND, 0xffff_fff0u32), (ENTER, 0x10), (EXIT, 0x20)] {
+            bytes.extend_from_slice(&[tag, 0]);
+            bytes.extend_from_slice(&time.to_le_bytes());
+        }
+        let events = decode(&bytes).unwrap();
+        let times: Vec<u64> = events.iter().map(|e| e.time).collect();
+        assert_eq!(
+            times,
+            vec![
+                0xffff_fff0,
+                0x1_0000_0010,
+                0x1_0000_0010,
+                0x1_0000_0020,
+                0x1_0000_0020
+            ]
+        );
+        // unnamed
+        assert_eq!(events[0].task, "task0");
+    }
+
+    #[test]
+    fn overflow_and_errors() {
+        let events = decode(&[OVERFLOW, 3, 1, 0, 0, 0]).unwrap();
+        assert_eq!(events[0].kind, Kind::Overflow { dropped: 3 });
+        assert_eq!(
+            events[0].to_string(),
+            "         1 -        overflow, 3 dropped"
+        );
+        let exam = fixtures::trace::exam();
+        assert_eq!(
+            decode(&exam[..exam.len() - 2]),
+            Err(DecodeError::Truncated {
+                offset: exam.len() - RECORD
+            })
+        );
+        assert_eq!(
+            decode(&[TASK_NAME, 0, 5, b't']),
+            Err(DecodeError::Truncated { offset: 0 })
+        );
+        assert_eq!(
+            decode(&[0x42]),
+            Err(DecodeError::Tag {
+                offset: 0,
+                tag: 0x42
+            })
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! host/rtic_trace/src/chrome.rs
+//!
+//! Export to the Chrome trace event format (JSON), opened by Perfetto
+//! (<https://ui.perfetto.dev>) or `chrome://tracing`.
+//!
+//! Each track is a thread of its own (named by the task or the priority
+//! level), jobs are complete (`X`) events with the lock regions and
+//! preemptions nested within, releases, deadline misses and overflows
+//! are instant (`i`) events. Times are in microseconds, from cycles at
+//! the core clock `mhz`.
+
+use crate::{
+    event::Event,
+    slices::{Slices, Tracks, What},
+};
+use std::fmt::Write;
+
+/// The process id of the application.
+const PID: u32 = 1;
+
+/// A JSON string.
+fn string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// The trace event JSON of `events`.
+pub fn json(events: &[Event], tracks: &Tracks, mhz: f64) -> String {
+    let s = Slices::new(events, tracks);
+    let us = |cycles: u64| cycles as f64 / mhz;
+    let mut trace: Vec<String> = vec![format!(
+        r#"{{"name":"process_name","ph":"M","pid":{},"args":{{"name":"rtic"}}}}"#,
+        PID
+    )];
+    // tids from 1, displayed in track order
+    for (i, track) in s.tracks.iter().enumerate() {
+        trace.push(format!(
+            r#"{{"name":"thread_name","ph":"M","pid":{},"tid":{},"args":{{"name":{}}}}}"#,
+            PID,
+            i + 1,
+            string(track)
+        ));
+        trace.push(format!(
+            r#"{{"name":"thread_sort_index","ph":"M","pid":{},"tid":{},"args":{{"sort_index":{}}}}}"#,
+            PID,
+            i + 1,
+            i
+        ));
+    }
+    // outer slices first, as nesting is by time and order
+    let mut slices: Vec<_> = s.slices.iter().collect();
+    slices.sort_by_key(|s| (s.start, s.depth));
+    for slice in slices {
+        let (cat, args) = match slice.what {
+            What::Job {
+                response: Some((response, deadline)),
+            } => (
+                "job",
+                format!(
+                    r#","args":{{"response":{},"deadline":{}}}"#,
+                    response, deadline
+                ),
+            ),
+            What::Job { response: None } => ("job", String::new()),
+            What::Lock { ceiling } => ("lock", format!(r#","args":{{"ceiling":{}}}"#, ceiling)),
+            What::Preempted => ("preempted", String::new()),
+        };
+        trace.push(format!(
+            r#"{{"name":{},"cat":"{}","ph":"X","ts":{:.3},"dur":{:.3},"pid":{},"tid":{}{}}}"#,
+            string(&slice.name),
+            cat,
+            us(slice.start),
+            us(slice.end) - us(slice.start),
+            PID,
+            slice.track + 1,
+            args
+        ));
+    }
+    for i in &s.instants {
+        let scope = match i.track {
+            Some(track) => format!(r#""s":"t","tid":{}"#, track + 1),
+            None => r#""s":"g""#.to_string(),
+        };
+        trace.push(format!(
+            r#"{{"name":{},"ph":"i","ts":{:.3},"pid":{},{}}}"#,
+            string(&i.name),
+            us(i.time),
+            PID,
+            scope
+        ));
+    }
+    format!(
+        "{{\"displayTimeUnit\":\"ns\",\"traceEvents\":[\n{}\n]}}\n",
+        trace.join(",\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::decode;
+
+    #[test]
+    fn exam() {
+        let json = json(
+            &decode(&fixtures::trace::exam()).unwrap(),
+            &Tracks::Task,
+            16.0,
+        );
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(lines[0], r#"{"displayTimeUnit":"ns","traceEvents":["#);
+        assert_eq!(
+            lines[2],
+            r#"{"name":"thread_name","ph":"M","pid":1,"tid":1,"args":{"name":"t2"}},"#
+        );
+        // the job of t2, 200240 to 260000 cycles at 16 MHz
+        assert_eq!(
+            lines[6],
+            r#"{"name":"t2","cat":"job","ph":"X","ts":12515.000,"dur":3735.000,"pid":1,"tid":1},"#
+        );
+        assert_eq!(
+            lines[7],
+            r#"{"name":"R1","cat":"lock","ph":"X","ts":13140.625,"dur":2484.375,"pid":1,"tid":1,"args":{"ceiling":2}},"#
+        );
+        // nested in R1
+        assert_eq!(
+            lines[8],
+            r#"{"name":"R2","cat":"lock","ph":"X","ts":13266.875,"dur":251.250,"pid":1,"tid":1,"args":{"ceiling":3}},"#
+        );
+        // t3 preempts t2 (holding R1)
+        assert_eq!(
+            lines[9],
+            r#"{"name":"t3","cat":"job","ph":"X","ts":13640.000,"dur":1880.625,"pid":1,"tid":2},"#
+        );
+        assert_eq!(
+            lines[10],
+            r#"{"name":"preempted by t3","cat":"preempted","ph":"X","ts":13640.000,"dur":1880.625,"pid":1,"tid":1},"#
+        );
+        assert_eq!(
+            lines[lines.len() - 2],
+            r#"{"name":"t3 release","ph":"i","ts":13625.000,"pid":1,"s":"t","tid":2}"#
+        );
+        assert_eq!(lines.last(), Some(&"]}"));
+        assert_eq!(json.matches(r#""ph":"X""#).count(), 6);
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(string("t\"1\\\n"), r#""t\"1\\\u000a""#);
+    }
+}
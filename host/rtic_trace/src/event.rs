@@ -0,0 +1,214 @@
+//! host/rtic_trace/src/event.rs
+//!
+//! Task events, as simulated (`rtic_sim`) or captured on the target.
+//!
+//! The text format is the `Display` output, one event per line:
+//!
+//! ```text
+//!      50240 t3       entry
+//!      60342 t3       lock R2 (ceiling 3)
+//! ```
+
+use std::{fmt, str::FromStr};
+
+/// Name of the timer queue handler (SysTick) in the trace.
+pub const TIMER: &str = "timer";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// Pended by the timer queue handler (at or after the scheduled release).
+    Release,
+    /// First given the CPU (handler entry, dispatch overhead follows).
+    Dispatch,
+    /// Task body entered.
+    Entry,
+    /// Preempted by a higher priority task (or the timer).
+    Preempted { by: String },
+    /// Resumed after preemption.
+    Resume,
+    /// Critical section entered, BASEPRI raised to `ceiling`.
+    Lock { resource: String, ceiling: u8 },
+    /// Critical section left.
+    Unlock { resource: String },
+    /// Task body completed (the measured response time).
+    Complete { response: u64, deadline: u64 },
+    /// Returned (exit overhead completed).
+    Exit,
+    /// Nothing to run.
+    Idle,
+    /// Inserted in the timer queue (by the running task).
+    Schedule,
+    /// Events lost, the trace buffer was full (captured traces).
+    Overflow { dropped: u32 },
+}
+
+/// A scheduling event at `time` (in cycles) of `task` (`-` for none).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub time: u64,
+    pub task: String,
+    pub kind: Kind,
+}
+
+impl Event {
+    /// Deadline missed at completion.
+    pub fn miss(&self) -> bool {
+        match self.kind {
+            Kind::Complete { response, deadline } => response > deadline,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Release => write!(f, "release"),
+            Kind::Dispatch => write!(f, "dispatch"),
+            Kind::Entry => write!(f, "entry"),
+            Kind::Preempted { by } => write!(f, "preempted by {}", by),
+            Kind::Resume => write!(f, "resume"),
+            Kind::Lock { resource, ceiling } => {
+                write!(f, "lock {} (ceiling {})", resource, ceiling)
+            }
+            Kind::Unlock { resource } => write!(f, "unlock {}", resource),
+            Kind::Complete { response, deadline } => write!(
+                f,
+                "complete, response {} deadline {}{}",
+                response,
+                deadline,
+                if response > deadline { " MISS" } else { "" }
+            ),
+            Kind::Exit => write!(f, "exit"),
+            Kind::Idle => write!(f, "idle"),
+            Kind::Schedule => write!(f, "schedule"),
+            Kind::Overflow { dropped } => write!(f, "overflow, {} dropped", dropped),
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>10} {:<8} {}", self.time, self.task, self.kind)
+    }
+}
+
+/// A line that is not an event.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError;
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "not an event, expected `<time> <task> <what>`")
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for Kind {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Kind, ParseError> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let number = |s: &str| s.parse().map_err(|_| ParseError);
+        Ok(match words.as_slice() {
+            ["release"] => Kind::Release,
+            ["dispatch"] => Kind::Dispatch,
+            ["entry"] => Kind::Entry,
+            ["preempted", "by", by] => Kind::Preempted { by: by.to_string() },
+            ["resume"] => Kind::Resume,
+            ["lock", resource, "(ceiling", ceiling] => Kind::Lock {
+                resource: resource.to_string(),
+                ceiling: ceiling
+                    .strip_suffix(')')
+                    .and_then(|c| c.parse().ok())
+                    .ok_or(ParseError)?,
+            },
+            ["unlock", resource] => Kind::Unlock {
+                resource: resource.to_string(),
+            },
+            ["complete,", "response", response, "deadline", deadline, rest @ ..]
+                if rest.is_empty() || rest == ["MISS"] =>
+            {
+                Kind::Complete {
+                    response: number(response)?,
+                    deadline: number(deadline)?,
+                }
+            }
+            ["exit"] => Kind::Exit,
+            ["idle"] => Kind::Idle,
+            ["schedule"] => Kind::Schedule,
+            ["overflow,", dropped, "dropped"] => Kind::Overflow {
+                dropped: dropped.parse().map_err(|_| ParseError)?,
+            },
+            _ => return Err(ParseError),
+        })
+    }
+}
+
+impl FromStr for Event {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Event, ParseError> {
+        let s = s.trim_start();
+        let (time, s) = s.split_at(s.find(' ').ok_or(ParseError)?);
+        let s = s.trim_start();
+        let (task, kind) = s.split_at(s.find(' ').ok_or(ParseError)?);
+        Ok(Event {
+            time: time.parse().map_err(|_| ParseError)?,
+            task: task.to_string(),
+            kind: kind.parse()?,
+        })
+    }
+}
+
+/// Parses the events of a text trace, other lines are ignored.
+pub fn parse(text: &str) -> Vec<Event> {
+    text.lines().filter_map(|l| l.parse().ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text() {
+        let kinds = vec![
+            Kind::Release,
+            Kind::Dispatch,
+            Kind::Entry,
+            Kind::Preempted { by: TIMER.into() },
+            Kind::Resume,
+            Kind::Lock {
+                resource: "R2".into(),
+                ceiling: 3,
+            },
+            Kind::Unlock {
+                resource: "R2".into(),
+            },
+            Kind::Complete {
+                response: 132_046,
+                deadline: 100_000,
+            },
+            Kind::Exit,
+            Kind::Idle,
+            Kind::Schedule,
+            Kind::Overflow { dropped: 3 },
+        ];
+        for kind in kinds {
+            let event = Event {
+                time: 300_000,
+                task: "long_task_name".into(),
+                kind,
+            };
+            assert_eq!(event.to_string().parse(), Ok(event));
+        }
+        let events = parse("trace\n     50240 t3       entry\n\nt3: count 1\n");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].to_string(), "     50240 t3       entry");
+        assert_eq!(
+            "50240 t3 lock R2 (ceiling x)".parse::<Event>(),
+            Err(ParseError)
+        );
+    }
+}
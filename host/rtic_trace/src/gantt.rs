@@ -0,0 +1,544 @@
+//! host/rtic_trace/src/gantt.rs
+//!
+//! Gantt charts of task events, as SVG or ASCII.
+//!
+//! A row per task (in order of appearance) shows when the task runs, holds
+//! a resource (by `lock`), or is preempted (started but not running), with
+//! markers at the (scheduled) release and the absolute deadline of each
+//! completed instance.
+
+use crate::event::{Event, Kind};
+use std::fmt::Write;
+
+/// The state of a task in a span of time.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum State {
+    Run,
+    /// Running, holding a resource (the innermost lock).
+    Lock(String),
+    Preempted,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: u64,
+    pub end: u64,
+    pub state: State,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mark {
+    Release(u64),
+    Deadline { time: u64, miss: bool },
+}
+
+impl Mark {
+    pub fn time(&self) -> u64 {
+        match *self {
+            Mark::Release(time) | Mark::Deadline { time, .. } => time,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub task: String,
+    pub spans: Vec<Span>,
+    pub marks: Vec<Mark>,
+}
+
+/// A Gantt chart of the interval `start..end` (in cycles).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Chart {
+    pub start: u64,
+    pub end: u64,
+    pub rows: Vec<Row>,
+}
+
+struct Open {
+    row: usize,
+    since: u64,
+    state: State,
+}
+
+impl Chart {
+    /// Chart of an event trace.
+    ///
+    /// A task dispatched (or the core idle) while another still runs
+    /// implies the exit of the latter, so traces without `exit` events
+    /// (e.g., of the timer handler) are drawn as well.
+    pub fn new(events: &[Event]) -> Chart {
+        let mut rows: Vec<Row> = vec![];
+        let mut locks: Vec<Vec<String>> = vec![];
+        let mut open: Vec<Open> = vec![];
+        let mut running: Option<usize> = None;
+
+        fn close(rows: &mut [Row], open: &mut Vec<Open>, row: usize, now: u64) {
+            if let Some(i) = open.iter().position(|o| o.row == row) {
+                let o = open.remove(i);
+                if now > o.since {
+                    rows[row].spans.push(Span {
+                        start: o.since,
+                        end: now,
+                        state: o.state,
+                    });
+                }
+            }
+        }
+        let active = |locks: &[String]| match locks.last() {
+            Some(resource) => State::Lock(resource.clone()),
+            None => State::Run,
+        };
+
+        for e in events {
+            let now = e.time;
+            if e.kind == Kind::Idle {
+                if let Some(prev) = running.take() {
+                    close(&mut rows, &mut open, prev, now);
+                }
+                continue;
+            }
+            if let Kind::Overflow { .. } = e.kind {
+                continue;
+            }
+            let row = match rows.iter().position(|r| r.task == e.task) {
+                Some(row) => row,
+                None => {
+                    rows.push(Row {
+                        task: e.task.clone(),
+                        spans: vec![],
+                        marks: vec![],
+                    });
+                    locks.push(vec![]);
+                    rows.len() - 1
+                }
+            };
+            match &e.kind {
+                Kind::Dispatch | Kind::Resume => {
+                    if let Some(prev) = running.filter(|&prev| prev != row) {
+                        close(&mut rows, &mut open, prev, now);
+                    }
+                    close(&mut rows, &mut open, row, now);
+                    open.push(Open {
+                        row,
+                        since: now,
+                        state: active(&locks[row]),
+                    });
+                    running = Some(row);
+                }
+                Kind::Preempted { .. } => {
+                    close(&mut rows, &mut open, row, now);
+                    open.push(Open {
+                        row,
+                        since: now,
+                        state: State::Preempted,
+                    });
+                    running = None;
+                }
+                Kind::Lock { resource, .. } => {
+                    close(&mut rows, &mut open, row, now);
+                    locks[row].push(resource.clone());
+                    open.push(Open {
+                        row,
+                        since: now,
+                        state: active(&locks[row]),
+                    });
+                }
+                Kind::Unlock { .. } => {
+                    close(&mut rows, &mut open, row, now);
+                    locks[row].pop();
+                    open.push(Open {
+                        row,
+                        since: now,
+                        state: active(&locks[row]),
+                    });
+                }
+                Kind::Complete { response, deadline } => {
+                    let release = now.saturating_sub(*response);
+                    rows[row].marks.push(Mark::Release(release));
+                    rows[row].marks.push(Mark::Deadline {
+                        time: release + deadline,
+                        miss: response > deadline,
+                    });
+                }
+                Kind::Exit => {
+                    close(&mut rows, &mut open, row, now);
+                    locks[row].clear();
+                    if running == Some(row) {
+                        running = None;
+                    }
+                }
+                Kind::Release
+                | Kind::Entry
+                | Kind::Idle
+                | Kind::Schedule
+                | Kind::Overflow { .. } => {}
+            }
+        }
+
+        let end = events.last().map_or(0, |e| e.time);
+        for row in 0..rows.len() {
+            close(&mut rows, &mut open, row, end);
+        }
+        let marks = rows.iter().flat_map(|r| r.marks.iter().map(Mark::time));
+        Chart {
+            start: events
+                .iter()
+                .map(|e| e.time)
+                .chain(marks.clone())
+                .min()
+                .unwrap_or(0),
+            end: marks.max().unwrap_or(0).max(end),
+            rows,
+        }
+    }
+
+    /// The part of the chart in `start..end`.
+    pub fn window(&self, start: u64, end: u64) -> Chart {
+        Chart {
+            start,
+            end,
+            rows: self
+                .rows
+                .iter()
+                .map(|r| Row {
+                    task: r.task.clone(),
+                    spans: r
+                        .spans
+                        .iter()
+                        .filter(|s| s.end > start && s.start < end)
+                        .map(|s| Span {
+                            start: s.start.max(start),
+                            end: s.end.min(end),
+                            state: s.state.clone(),
+                        })
+                        .collect(),
+                    marks: r
+                        .marks
+                        .iter()
+                        .copied()
+                        .filter(|m| (start..=end).contains(&m.time()))
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// ASCII chart, `width` columns of time, each task on a line of
+    /// execution and a line of markers.
+    pub fn ascii(&self, width: usize) -> String {
+        let width = width.max(1);
+        let label = self.rows.iter().map(|r| r.task.len()).max().unwrap_or(0);
+        let per_column = (self.end - self.start).div_ceil(width as u64).max(1);
+        let column = |t: u64| (((t - self.start) / per_column) as usize).min(width - 1);
+
+        let mut s = String::new();
+        for r in &self.rows {
+            let mut line = vec![' '; width];
+            for span in &r.spans {
+                let (c, rank) = match span.state {
+                    State::Lock(_) => ('=', 3),
+                    State::Run => ('#', 2),
+                    State::Preempted => ('-', 1),
+                };
+                // the end is exclusive
+                for x in &mut line[column(span.start)..=column(span.end - 1)] {
+                    let old = match *x {
+                        '=' => 3,
+                        '#' => 2,
+                        '-' => 1,
+                        _ => 0,
+                    };
+                    if rank > old {
+                        *x = c;
+                    }
+                }
+            }
+            let mut marks = vec![' '; width];
+            for m in &r.marks {
+                let x = &mut marks[column(m.time())];
+                *x = match (*m, *x) {
+                    (_, 'X') | (Mark::Deadline { miss: true, .. }, _) => 'X',
+                    (Mark::Deadline { .. }, _) | (_, 'v') => 'v',
+                    (Mark::Release(_), _) => '^',
+                };
+            }
+            let line: String = line.into_iter().collect();
+            let marks: String = marks.into_iter().collect();
+            writeln!(s, "{:<label$} {}", r.task, line.trim_end(), label = label).unwrap();
+            if !marks.trim().is_empty() {
+                writeln!(s, "{:<label$} {}", "", marks.trim_end(), label = label).unwrap();
+            }
+        }
+        let end = self.end.to_string();
+        writeln!(
+            s,
+            "{:<label$} {:<w$}{}",
+            "",
+            self.start,
+            end,
+            label = label,
+            w = width.saturating_sub(end.len())
+        )
+        .unwrap();
+        write!(
+            s,
+            "{} cycles per column, # running, = holding a resource, - preempted, \
+             ^ release, v deadline, X deadline missed",
+            per_column
+        )
+        .unwrap();
+        s
+    }
+
+    /// SVG chart, `width` pixels wide.
+    pub fn svg(&self, width: u32) -> String {
+        const LABEL: u32 = 80;
+        const ROW: u32 = 30;
+        const BAR: u32 = 16;
+        let plot = width.saturating_sub(LABEL + 10).max(100);
+        let duration = (self.end - self.start).max(1);
+        let x = |t: u64| LABEL as f64 + (t - self.start) as f64 * plot as f64 / duration as f64;
+        let height = ROW * (self.rows.len() as u32 + 2);
+
+        let mut s = String::new();
+        writeln!(
+            s,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+             font-family=\"monospace\" font-size=\"12\">",
+            LABEL + plot + 10,
+            height
+        )
+        .unwrap();
+        for (i, r) in self.rows.iter().enumerate() {
+            let top = ROW * i as u32 + (ROW - BAR) / 2;
+            writeln!(
+                s,
+                "<text x=\"4\" y=\"{}\">{}</text>",
+                top + BAR - 3,
+                escape(&r.task)
+            )
+            .unwrap();
+            for span in &r.spans {
+                let (fill, what) = match &span.state {
+                    State::Run => ("#4e79a7", "run".to_string()),
+                    State::Lock(resource) => ("#f28e2b", format!("lock {}", escape(resource))),
+                    State::Preempted => ("#d9d9d9", "preempted".to_string()),
+                };
+                let (x0, x1) = (x(span.start), x(span.end));
+                writeln!(
+                    s,
+                    "<rect x=\"{:.2}\" y=\"{}\" width=\"{:.2}\" height=\"{}\" fill=\"{}\">\
+                     <title>{} {} {}..{}</title></rect>",
+                    x0,
+                    top,
+                    x1 - x0,
+                    BAR,
+                    fill,
+                    escape(&r.task),
+                    what,
+                    span.start,
+                    span.end
+                )
+                .unwrap();
+            }
+            for m in &r.marks {
+                let (stroke, dash, what) = match m {
+                    Mark::Release(_) => ("#59a14f", "", "release"),
+                    Mark::Deadline { miss: false, .. } => {
+                        ("#000000", " stroke-dasharray=\"3,2\"", "deadline")
+                    }
+                    Mark::Deadline { miss: true, .. } => ("#e15759", "", "deadline missed"),
+                };
+                writeln!(
+                    s,
+                    "<line x1=\"{0:.2}\" y1=\"{1}\" x2=\"{0:.2}\" y2=\"{2}\" stroke=\"{3}\" \
+                     stroke-width=\"2\"{4}><title>{5} {6} {7}</title></line>",
+                    x(m.time()),
+                    top - 4,
+                    top + BAR + 4,
+                    stroke,
+                    dash,
+                    escape(&r.task),
+                    what,
+                    m.time()
+                )
+                .unwrap();
+            }
+        }
+
+        // time axis
+        let axis = ROW * self.rows.len() as u32 + 4;
+        writeln!(
+            s,
+            "<line x1=\"{}\" y1=\"{2}\" x2=\"{}\" y2=\"{2}\" stroke=\"#000000\"/>",
+            LABEL,
+            LABEL + plot,
+            axis
+        )
+        .unwrap();
+        let step = tick(duration);
+        let mut t = self.start.div_ceil(step) * step;
+        while t <= self.end {
+            writeln!(
+                s,
+                "<line x1=\"{0:.2}\" y1=\"{1}\" x2=\"{0:.2}\" y2=\"{2}\" stroke=\"#000000\"/>\
+                 <text x=\"{0:.2}\" y=\"{3}\" text-anchor=\"middle\">{4}</text>",
+                x(t),
+                axis,
+                axis + 4,
+                axis + 16,
+                t
+            )
+            .unwrap();
+            t += step;
+        }
+        writeln!(
+            s,
+            "<text x=\"{}\" y=\"{}\">cycles: run (blue), lock (orange), preempted (grey), \
+             release (green), deadline (dashed, red if missed)</text>",
+            LABEL,
+            axis + 36
+        )
+        .unwrap();
+        s.push_str("</svg>\n");
+        s
+    }
+}
+
+/// Tick step of a time axis, 1, 2 or 5 times a power of ten, for
+/// at most 10 ticks.
+fn tick(duration: u64) -> u64 {
+    let mut step = 1;
+    loop {
+        for m in &[1, 2, 5] {
+            if duration / (step * m) <= 10 {
+                return step * m;
+            }
+        }
+        step *= 10;
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::parse;
+
+    // `timing_exam` simulated with overheads (`rtic_sim`), the
+    // second instance of t1 misses its deadline
+    const MISS: &str = "
+    291334 t1       dispatch
+    291502 t1       entry
+    300000 t1       preempted by timer
+    300000 timer    dispatch
+    300132 t1       release
+    300132 t3       release
+    300132 timer    exit
+    300132 t3       dispatch
+    300300 t3       entry
+    310402 t3       lock R2 (ceiling 3)
+    320402 t3       unlock R2
+    330422 t3       complete, response 30422 deadline 50000
+    330462 t3       exit
+    330462 t1       resume
+    332046 t1       complete, response 132046 deadline 100000 MISS
+    332086 t1       exit
+    332086 -        idle
+";
+
+    fn spans(chart: &Chart, task: &str) -> Vec<(u64, u64, State)> {
+        let row = chart.rows.iter().find(|r| r.task == task).unwrap();
+        row.spans
+            .iter()
+            .map(|s| (s.start, s.end, s.state.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn chart() {
+        let chart = Chart::new(&parse(MISS));
+        assert_eq!((chart.start, chart.end), (200_000, 350_000));
+        assert_eq!(
+            chart
+                .rows
+                .iter()
+                .map(|r| r.task.as_str())
+                .collect::<Vec<_>>(),
+            vec!["t1", "timer", "t3"]
+        );
+        assert_eq!(
+            spans(&chart, "t1"),
+            vec![
+                (291_334, 300_000, State::Run),
+                (300_000, 330_462, State::Preempted),
+                (330_462, 332_086, State::Run),
+            ]
+        );
+        assert_eq!(
+            spans(&chart, "t3"),
+            vec![
+                (300_132, 310_402, State::Run),
+                (310_402, 320_402, State::Lock("R2".into())),
+                (320_402, 330_462, State::Run),
+            ]
+        );
+        assert_eq!(spans(&chart, "timer"), vec![(300_000, 300_132, State::Run)]);
+        assert_eq!(
+            chart.rows[0].marks,
+            vec![
+                Mark::Release(200_000),
+                Mark::Deadline {
+                    time: 300_000,
+                    miss: true
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn implied_exit() {
+        // without `exit` events, e.g. of a captured trace
+        let events: Vec<Event> = parse(MISS)
+            .into_iter()
+            .filter(|e| e.kind != Kind::Exit)
+            .collect();
+        let chart = Chart::new(&events);
+        assert_eq!(spans(&chart, "timer"), vec![(300_000, 300_132, State::Run)]);
+        assert_eq!(spans(&chart, "t1").last().unwrap().1, 332_086);
+    }
+
+    #[test]
+    fn ascii() {
+        let chart = Chart::new(&parse(MISS)).window(290_000, 340_000);
+        assert_eq!(
+            chart.ascii(50),
+            "\
+t1     #########------------------------------###
+                X
+timer           #
+t3              ##########===========##########
+                ^
+      290000                                      340000
+1000 cycles per column, # running, = holding a resource, - preempted, \
+^ release, v deadline, X deadline missed"
+        );
+    }
+
+    #[test]
+    fn svg() {
+        let svg = Chart::new(&parse(MISS)).svg(800);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"800\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("<title>t3 lock R2 310402..320402</title>"));
+        assert!(svg.contains("<title>t1 deadline missed 300000</title>"));
+        assert_eq!(svg.matches("<rect").count(), 7);
+        assert_eq!(tick(150_086), 20_000);
+    }
+}
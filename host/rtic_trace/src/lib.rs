@@ -0,0 +1,17 @@
+//! host/rtic_trace/src/lib.rs
+//!
+//! Task event traces of RTIC applications, simulated (`rtic_sim`) or
+//! captured on the target (see [`binary`]), and their presentation, as
+//! Gantt charts or exported to Perfetto ([`chrome`]) and GTKWave ([`vcd`]).
+
+pub mod binary;
+pub mod chrome;
+pub mod event;
+pub mod gantt;
+pub mod slices;
+pub mod vcd;
+
+pub use binary::{decode, DecodeError};
+pub use event::{parse, Event, Kind, ParseError, TIMER};
+pub use gantt::Chart;
+pub use slices::{Slices, Tracks};
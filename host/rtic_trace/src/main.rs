@@ -0,0 +1,118 @@
+//! host/rtic_trace/src/main.rs
+//!
+//! > cargo run -p rtic_trace -- [--binary [--events]] [--svg chart.svg] [--width n] [--from t] [--to t] [--chrome trace.json] [--vcd trace.vcd] [--mhz f] [--tasks tasks.toml] [trace]
+//!
+//! Draws a Gantt chart of a text trace (e.g., the output of `rtic_sim`,
+//! default read from stdin), in ASCII, or as SVG with `--svg`.
+//!
+//! With `--binary` the trace is a capture of the binary tracer of the
+//! firmware (`timing::trace`), `--events` prints the decoded events
+//! (in the text format) instead of the chart.
+//!
+//! `--chrome` exports the trace for Perfetto, `--vcd` for GTKWave (times
+//! by the `--mhz` core clock, default 16), a track per task, or per
+//! priority level of the `--tasks` task set.
+
+use rtic_trace::{chrome, decode, parse, vcd, Chart, Tracks};
+use std::{
+    env, fs,
+    io::{self, Read},
+    process,
+};
+
+fn main() {
+    let mut svg = None;
+    let mut width = None;
+    let (mut from, mut to) = (None, None);
+    let mut path = None;
+    let mut binary = false;
+    let mut list = false;
+    let (mut json, mut dump) = (None, None);
+    let mut mhz = 16.0;
+    let mut tracks = Tracks::Task;
+    let mut args = env::args().skip(1);
+    let fail = |msg: String| -> ! {
+        eprintln!("{}", msg);
+        process::exit(1)
+    };
+    let number = |arg: &str, args: &mut dyn Iterator<Item = String>| -> u64 {
+        args.next()
+            .and_then(|n| n.parse().ok())
+            .unwrap_or_else(|| fail(format!("{} <number>", arg)))
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--binary" => binary = true,
+            "--events" => list = true,
+            "--svg" => svg = Some(args.next().unwrap_or_else(|| fail("--svg <file>".into()))),
+            "--width" => width = Some(number(&arg, &mut args) as usize),
+            "--from" => from = Some(number(&arg, &mut args)),
+            "--to" => to = Some(number(&arg, &mut args)),
+            "--chrome" => {
+                json = Some(
+                    args.next()
+                        .unwrap_or_else(|| fail("--chrome <file>".into())),
+                )
+            }
+            "--vcd" => dump = Some(args.next().unwrap_or_else(|| fail("--vcd <file>".into()))),
+            "--mhz" => {
+                mhz = args
+                    .next()
+                    .and_then(|f| f.parse().ok())
+                    .filter(|&f: &f64| f > 0.0)
+                    .unwrap_or_else(|| fail("--mhz <core clock>".into()))
+            }
+            "--tasks" => {
+                let path = args.next().unwrap_or_else(|| fail("--tasks <file>".into()));
+                let set = taskset::TaskSet::load(&path)
+                    .unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+                tracks = Tracks::Priority(set.tasks.into_iter().map(|t| (t.id, t.prio)).collect());
+            }
+            _ => path = Some(arg),
+        }
+    }
+
+    let mut bytes = vec![];
+    let read = match &path {
+        Some(path) => fs::read(path).map(|b| bytes = b),
+        None => io::stdin().read_to_end(&mut bytes).map(drop),
+    };
+    if let Err(e) = read {
+        fail(format!("{}: {}", path.as_deref().unwrap_or("stdin"), e));
+    }
+    let events = if binary {
+        decode(&bytes).unwrap_or_else(|e| fail(e.to_string()))
+    } else {
+        parse(&String::from_utf8_lossy(&bytes))
+    };
+    let write = |file: &str, contents: String| {
+        fs::write(file, contents).unwrap_or_else(|e| fail(format!("{}: {}", file, e)))
+    };
+    if let Some(file) = &json {
+        write(file, chrome::json(&events, &tracks, mhz));
+    }
+    if let Some(file) = &dump {
+        write(file, vcd::vcd(&events, &tracks, mhz));
+    }
+    if json.is_some() || dump.is_some() {
+        return;
+    }
+    if list {
+        for e in &events {
+            println!("{}", e);
+        }
+        return;
+    }
+    if events.is_empty() {
+        fail("no events in the trace".into());
+    }
+    let mut chart = Chart::new(&events);
+    if from.is_some() || to.is_some() {
+        chart = chart.window(from.unwrap_or(chart.start), to.unwrap_or(chart.end));
+    }
+    match svg {
+        Some(file) => fs::write(&file, chart.svg(width.unwrap_or(1200) as u32))
+            .unwrap_or_else(|e| fail(format!("{}: {}", file, e))),
+        None => println!("{}", chart.ascii(width.unwrap_or(100))),
+    }
+}
@@ -0,0 +1,341 @@
+//! host/rtic_trace/src/slices.rs
+//!
+//! Task event traces as nested slices of time, per track, the common
+//! ground of the exporters ([`crate::chrome`], [`crate::vcd`]).
+//!
+//! A job (dispatch to exit) is a slice of depth 0 on the track of its
+//! task, the lock regions and preemptions within it are nested slices
+//! (locks nest, a preemption is within the innermost lock held). Tracks
+//! are per task, or per priority level (tasks of the same priority never
+//! overlap in time).
+
+use crate::event::{Event, Kind};
+use std::collections::BTreeMap;
+
+/// The grouping of tasks into tracks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Tracks {
+    /// A track per task, in order of appearance.
+    Task,
+    /// A track per priority level, highest first (tasks without a
+    /// priority get a track of their own, last).
+    Priority(BTreeMap<String, u8>),
+}
+
+impl Tracks {
+    pub fn track(&self, task: &str) -> String {
+        match self {
+            Tracks::Priority(prio) if prio.contains_key(task) => {
+                format!("priority {}", prio[task])
+            }
+            _ => task.to_string(),
+        }
+    }
+
+    /// Sort key of the track of `task`.
+    fn order(&self, task: &str) -> i32 {
+        match self {
+            Tracks::Priority(prio) => prio.get(task).map_or(0, |&p| -(p as i32)),
+            Tracks::Task => 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum What {
+    /// A job, with the measured response time and deadline if completed.
+    Job { response: Option<(u64, u64)> },
+    /// A critical section of a resource.
+    Lock { ceiling: u8 },
+    /// Preempted by a higher priority task.
+    Preempted,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Slice {
+    pub track: usize,
+    pub name: String,
+    pub what: What,
+    pub start: u64,
+    pub end: u64,
+    /// Nesting, 0 for jobs.
+    pub depth: usize,
+}
+
+/// A point in time of a track, or of all tracks (`None`).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instant {
+    pub track: Option<usize>,
+    pub name: String,
+    pub time: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Slices {
+    /// Track names, in display order.
+    pub tracks: Vec<String>,
+    /// In order of their end, inner slices before outer.
+    pub slices: Vec<Slice>,
+    pub instants: Vec<Instant>,
+}
+
+/// The open slices of a task, outermost (the job) first.
+struct Open {
+    task: String,
+    stack: Vec<Slice>,
+}
+
+impl Slices {
+    pub fn new(events: &[Event], tracks: &Tracks) -> Slices {
+        // tracks in display order, stable by appearance
+        let mut names: Vec<(i32, String)> = vec![];
+        for e in events.iter().filter(|e| e.task != "-") {
+            let name = tracks.track(&e.task);
+            if !names.iter().any(|(_, n)| *n == name) {
+                names.push((tracks.order(&e.task), name));
+            }
+        }
+        names.sort_by_key(|(order, _)| *order);
+        let names: Vec<String> = names.into_iter().map(|(_, n)| n).collect();
+        let track = |task: &str| {
+            let name = tracks.track(task);
+            names.iter().position(|t| *t == name).unwrap_or(0)
+        };
+        let mut s = Slices {
+            tracks: names.clone(),
+            ..Slices::default()
+        };
+
+        let mut open: Vec<Open> = vec![];
+        // the running task, in `open`
+        let mut running: Option<String> = None;
+        fn close(s: &mut Slices, open: &mut Vec<Open>, task: &str, depth: usize, now: u64) {
+            if let Some(o) = open.iter_mut().find(|o| o.task == task) {
+                while o.stack.len() > depth {
+                    let mut slice = o.stack.pop().unwrap();
+                    slice.end = now;
+                    s.slices.push(slice);
+                }
+            }
+            open.retain(|o| !o.stack.is_empty());
+        }
+        fn push(open: &mut Vec<Open>, task: &str, slice: Slice) {
+            match open.iter_mut().find(|o| o.task == task) {
+                Some(o) => o.stack.push(Slice {
+                    depth: o.stack.len(),
+                    ..slice
+                }),
+                None => open.push(Open {
+                    task: task.to_string(),
+                    stack: vec![slice],
+                }),
+            }
+        }
+
+        for e in events {
+            let now = e.time;
+            let slice = |name: &str, what| Slice {
+                track: track(&e.task),
+                name: name.to_string(),
+                what,
+                start: now,
+                end: now,
+                depth: 0,
+            };
+            match &e.kind {
+                Kind::Dispatch => {
+                    // a dispatch while another runs implies its exit, as
+                    // in the charts
+                    if let Some(prev) = running.take() {
+                        close(&mut s, &mut open, &prev, 0, now);
+                    }
+                    close(&mut s, &mut open, &e.task, 0, now);
+                    push(
+                        &mut open,
+                        &e.task,
+                        slice(&e.task, What::Job { response: None }),
+                    );
+                    running = Some(e.task.clone());
+                }
+                Kind::Preempted { by } => {
+                    push(
+                        &mut open,
+                        &e.task,
+                        slice(&format!("preempted by {}", by), What::Preempted),
+                    );
+                    running = None;
+                }
+                Kind::Resume => {
+                    if let Some(o) = open.iter().find(|o| o.task == e.task) {
+                        let depth = o
+                            .stack
+                            .iter()
+                            .rposition(|s| s.what == What::Preempted)
+                            .unwrap_or(o.stack.len());
+                        close(&mut s, &mut open, &e.task, depth, now);
+                    }
+                    running = Some(e.task.clone());
+                }
+                Kind::Lock { resource, ceiling } => push(
+                    &mut open,
+                    &e.task,
+                    slice(resource, What::Lock { ceiling: *ceiling }),
+                ),
+                Kind::Unlock { resource } => {
+                    if let Some(o) = open.iter().find(|o| o.task == e.task) {
+                        if let Some(depth) = o.stack.iter().rposition(|s| s.name == *resource) {
+                            close(&mut s, &mut open, &e.task, depth, now);
+                        }
+                    }
+                }
+                Kind::Complete { response, deadline } => {
+                    if let Some(job) = open
+                        .iter_mut()
+                        .find(|o| o.task == e.task)
+                        .map(|o| &mut o.stack[0])
+                    {
+                        job.what = What::Job {
+                            response: Some((*response, *deadline)),
+                        };
+                    }
+                    if response > deadline {
+                        s.instants.push(Instant {
+                            track: Some(track(&e.task)),
+                            name: format!("{} deadline miss", e.task),
+                            time: now,
+                        });
+                    }
+                }
+                Kind::Exit => {
+                    close(&mut s, &mut open, &e.task, 0, now);
+                    if running.as_deref() == Some(&e.task) {
+                        running = None;
+                    }
+                }
+                Kind::Idle => {
+                    if let Some(prev) = running.take() {
+                        close(&mut s, &mut open, &prev, 0, now);
+                    }
+                }
+                Kind::Release => s.instants.push(Instant {
+                    track: Some(track(&e.task)),
+                    name: format!("{} release", e.task),
+                    time: now,
+                }),
+                Kind::Overflow { dropped } => s.instants.push(Instant {
+                    track: None,
+                    name: format!("overflow, {} dropped", dropped),
+                    time: now,
+                }),
+                Kind::Entry | Kind::Schedule => {}
+            }
+        }
+        let end = events.last().map_or(0, |e| e.time);
+        while let Some(o) = open.first() {
+            let task = o.task.clone();
+            close(&mut s, &mut open, &task, 0, end);
+        }
+        s
+    }
+
+    /// Resources locked on each track, in order of appearance.
+    pub fn resources(&self) -> Vec<Vec<String>> {
+        let mut resources = vec![vec![]; self.tracks.len()];
+        let mut slices: Vec<&Slice> = self.slices.iter().collect();
+        slices.sort_by_key(|s| s.start);
+        for s in slices {
+            let r: &mut Vec<String> = &mut resources[s.track];
+            if let What::Lock { .. } = s.what {
+                if !r.contains(&s.name) {
+                    r.push(s.name.clone());
+                }
+            }
+        }
+        resources
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::decode;
+
+    fn text(s: &Slices) -> String {
+        s.slices
+            .iter()
+            .map(|s| {
+                format!(
+                    "\n{:>7} {:>7} {}{} {}",
+                    s.start,
+                    s.end,
+                    "  ".repeat(s.depth),
+                    s.track,
+                    s.name
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nested() {
+        let s = Slices::new(&decode(&fixtures::trace::exam()).unwrap(), &Tracks::Task);
+        assert_eq!(s.tracks, vec!["t2", "t3"]);
+        assert_eq!(
+            text(&s),
+            "
+ 212270  216290     0 R2
+ 228250  238290   1 R2
+ 218240  248330 1 t3
+ 218240  248330     0 preempted by t3
+ 210250  250000   0 R1
+ 200240  260000 0 t2"
+        );
+        assert_eq!(s.resources(), vec![vec!["R1", "R2"], vec!["R2"]]);
+        assert_eq!(
+            s.instants
+                .iter()
+                .map(|i| (i.track, i.name.as_str(), i.time))
+                .collect::<Vec<_>>(),
+            vec![
+                (Some(0), "t2 release", 200012),
+                (Some(1), "t3 release", 218000)
+            ]
+        );
+    }
+
+    #[test]
+    fn priority_tracks() {
+        let prio: BTreeMap<String, u8> = [("t1", 1), ("t2", 1), ("t3", 3)]
+            .iter()
+            .map(|(t, p)| (t.to_string(), *p))
+            .collect();
+        let events = crate::parse(
+            "
+    100 t1 dispatch
+    100 t1 complete, response 20 deadline 10
+    110 t1 exit
+    110 t2 dispatch
+    120 t3 dispatch
+    130 -  idle
+    140 -  overflow, 2 dropped",
+        );
+        let s = Slices::new(&events, &Tracks::Priority(prio));
+        assert_eq!(s.tracks, vec!["priority 3", "priority 1"]);
+        // t3 dispatched while t2 runs implies the exit of t2
+        assert_eq!(
+            text(&s),
+            "
+    100     110 1 t1
+    110     120 1 t2
+    120     130 0 t3"
+        );
+        assert_eq!(
+            s.slices[0].what,
+            What::Job {
+                response: Some((20, 10))
+            }
+        );
+        assert_eq!(s.instants[0].name, "t1 deadline miss");
+        assert_eq!(s.instants[1].track, None);
+    }
+}
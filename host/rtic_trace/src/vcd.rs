@@ -0,0 +1,180 @@
+//! host/rtic_trace/src/vcd.rs
+//!
+//! Export to a Value Change Dump (VCD), opened by GTKWave.
+//!
+//! Each track is a scope, with a signal of the track state (`1` running,
+//! `z` preempted, `0` idle) and a signal per resource locked on the track
+//! (`1` while held, nested locks overlap). Times are in picoseconds,
+//! from cycles at the core clock `mhz`.
+
+use crate::{
+    event::Event,
+    slices::{Slices, Tracks, What},
+};
+use std::{collections::BTreeMap, fmt::Write};
+
+/// The identifier code of signal `n`, printable ASCII from `!`.
+fn id(mut n: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (n % 94) as u8) as char);
+        n /= 94;
+        if n == 0 {
+            return id;
+        }
+        n -= 1;
+    }
+}
+
+/// A VCD reference, spaces are not allowed.
+fn reference(name: &str) -> String {
+    name.replace(|c: char| c.is_whitespace(), "_")
+}
+
+/// The VCD of `events`.
+pub fn vcd(events: &[Event], tracks: &Tracks, mhz: f64) -> String {
+    let s = Slices::new(events, tracks);
+    let ps = |cycles: u64| (cycles as f64 * 1e6 / mhz).round() as u64;
+
+    let mut out = String::new();
+    writeln!(out, "$version rtic_trace $end").unwrap();
+    writeln!(out, "$comment {} MHz core clock $end", mhz).unwrap();
+    writeln!(out, "$timescale 1 ps $end").unwrap();
+    writeln!(out, "$scope module rtic $end").unwrap();
+    // signals: the state of each track, then its resources
+    let resources = s.resources();
+    let mut state = vec![];
+    let mut lock: BTreeMap<(usize, &str), usize> = BTreeMap::new();
+    let mut n = 0;
+    for (t, track) in s.tracks.iter().enumerate() {
+        writeln!(out, "$scope module {} $end", reference(track)).unwrap();
+        writeln!(out, "$var wire 1 {} state $end", id(n)).unwrap();
+        state.push(n);
+        n += 1;
+        for r in &resources[t] {
+            writeln!(out, "$var wire 1 {} {} $end", id(n), reference(r)).unwrap();
+            lock.insert((t, r), n);
+            n += 1;
+        }
+        writeln!(out, "$upscope $end").unwrap();
+    }
+    writeln!(out, "$upscope $end").unwrap();
+    writeln!(out, "$enddefinitions $end").unwrap();
+
+    // changes at each time, ends before starts, inner ends first and
+    // outer starts first, the last change of a signal at a time holds
+    let mut changes: Vec<(u64, bool, i64, usize, char)> = vec![];
+    for slice in &s.slices {
+        let depth = slice.depth as i64;
+        let (signal, on, off) = match slice.what {
+            What::Job { .. } => (state[slice.track], '1', '0'),
+            What::Preempted => (state[slice.track], 'z', '1'),
+            What::Lock { .. } => (lock[&(slice.track, slice.name.as_str())], '1', '0'),
+        };
+        changes.push((slice.start, true, depth, signal, on));
+        changes.push((slice.end, false, -depth, signal, off));
+    }
+    changes.sort_by_key(|&(time, start, depth, _, _)| (time, start, depth));
+    let start = events.first().map_or(0, |e| e.time);
+    writeln!(out, "#{}", ps(start)).unwrap();
+    writeln!(out, "$dumpvars").unwrap();
+    let mut value = vec!['0'; n];
+    for i in 0..n {
+        writeln!(out, "0{}", id(i)).unwrap();
+    }
+    writeln!(out, "$end").unwrap();
+    let mut at = 0;
+    while at < changes.len() {
+        let time = changes[at].0;
+        let mut next = value.clone();
+        while at < changes.len() && changes[at].0 == time {
+            let (_, _, _, signal, v) = changes[at];
+            next[signal] = v;
+            at += 1;
+        }
+        if next != value {
+            writeln!(out, "#{}", ps(time)).unwrap();
+            for (i, (&new, old)) in next.iter().zip(&value).enumerate() {
+                if new != *old {
+                    writeln!(out, "{}{}", new, id(i)).unwrap();
+                }
+            }
+            value = next;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binary::decode;
+
+    #[test]
+    fn exam() {
+        let vcd = vcd(
+            &decode(&fixtures::trace::exam()).unwrap(),
+            &Tracks::Task,
+            16.0,
+        );
+        assert_eq!(
+            vcd,
+            "\
+$version rtic_trace $end
+$comment 16 MHz core clock $end
+$timescale 1 ps $end
+$scope module rtic $end
+$scope module t2 $end
+$var wire 1 ! state $end
+$var wire 1 \" R1 $end
+$var wire 1 # R2 $end
+$upscope $end
+$scope module t3 $end
+$var wire 1 $ state $end
+$var wire 1 % R2 $end
+$upscope $end
+$upscope $end
+$enddefinitions $end
+#12500750000
+$dumpvars
+0!
+0\"
+0#
+0$
+0%
+$end
+#12515000000
+1!
+#13140625000
+1\"
+#13266875000
+1#
+#13518125000
+0#
+#13640000000
+z!
+1$
+#14265625000
+1%
+#14893125000
+0%
+#15520625000
+1!
+0$
+#15625000000
+0\"
+#16250000000
+0!
+"
+        );
+    }
+
+    #[test]
+    fn ids() {
+        assert_eq!(id(0), "!");
+        assert_eq!(id(93), "~");
+        assert_eq!(id(94), "!!");
+        assert_eq!(id(95), "\"!");
+        assert_eq!(reference("priority 2"), "priority_2");
+    }
+}
@@ -0,0 +1,307 @@
+//! host/srp_analysis/src/lib.rs
+//!
+//! Response time analysis under the Stack Resource Policy (SRP),
+//! for the task set model of `taskset` (as used by the firmware).
+//!
+//! For each task `t`, in time units of the task set:
+//!
+//! - `B(t)`, blocking, the longest critical section of a lower priority
+//!   task on a resource with a ceiling `>= prio(t)`. Under SRP a task is
+//!   blocked at most once, before it starts.
+//! - `I(t)`, interference, the execution of higher and equal priority
+//!   tasks released in the busy period `Bp(t)`,
+//!   `sum(ceil(Bp(t) / A(h)) * C(h))`.
+//! - `R(t) = C(t) + B(t) + I(t)`, where [`analyse`] iterates
+//!   `Bp(t) = R(t)` to a fixed point (response time analysis), while
+//!   [`analyse_approx`] assumes `Bp(t) = D(t)` (as `klee_tutorial/srp_analysis`).
+//!
+//! Tasks of equal priority do not preempt each other, but are dispatched
+//! in release order (FIFO), a job may have to wait for those released
+//! before it, so they are accounted as interference.
+//!
+//! Release offsets are ignored here, see [`offsets`] for the exact
+//! (simulated) response times of a task set with offsets.
+//!
+//! The runtime overheads are ignored as well, see [`overheads`] for
+//! inflating a task set by the costs of RTIC.
+
+use std::fmt;
+use taskset::{Task, TaskSet};
+
+pub mod offsets;
+pub mod overheads;
+pub mod priority;
+pub mod sensitivity;
+#[cfg(test)]
+mod sets;
+pub mod sufficient;
+
+/// Analysis result of a task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskAnalysis {
+    pub id: String,
+    pub prio: u8,
+    pub wcet: u32,
+    pub blocking: u32,
+    pub interference: u32,
+    /// Response time bound, when beyond `deadline` the iteration is
+    /// stopped (i.e., a lower bound of the actual response).
+    pub response: u32,
+    pub deadline: u32,
+}
+
+impl TaskAnalysis {
+    pub fn schedulable(&self) -> bool {
+        self.response <= self.deadline
+    }
+
+    /// Deadline minus response time (negative if not schedulable).
+    pub fn slack(&self) -> i64 {
+        self.deadline as i64 - self.response as i64
+    }
+}
+
+/// Analysis result of a task set.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Analysis {
+    pub utilisation: f64,
+    pub tasks: Vec<TaskAnalysis>,
+}
+
+impl Analysis {
+    pub fn schedulable(&self) -> bool {
+        self.tasks.iter().all(|t| t.schedulable())
+    }
+
+    /// Task by id.
+    pub fn task(&self, id: &str) -> Option<&TaskAnalysis> {
+        self.tasks.iter().find(|t| t.id == id)
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "utilisation {:.3}", self.utilisation)?;
+        writeln!(
+            f,
+            "{:<8} {:>4} {:>8} {:>8} {:>8} {:>8} {:>8}",
+            "task", "prio", "wcet", "block", "interf", "resp", "deadline"
+        )?;
+        for t in &self.tasks {
+            writeln!(
+                f,
+                "{:<8} {:>4} {:>8} {:>8} {:>8} {:>8} {:>8}  {}",
+                t.id,
+                t.prio,
+                t.wcet,
+                t.blocking,
+                t.interference,
+                t.response,
+                t.deadline,
+                if t.schedulable() { "ok" } else { "MISS" }
+            )?;
+        }
+        write!(
+            f,
+            "{}",
+            if self.schedulable() {
+                "schedulable"
+            } else {
+                "not schedulable"
+            }
+        )
+    }
+}
+
+/// Total utilisation, `sum(C(t) / A(t))`.
+pub fn utilisation(set: &TaskSet) -> f64 {
+    set.tasks
+        .iter()
+        .map(|t| t.wcet() as f64 / t.inter_arrival as f64)
+        .sum()
+}
+
+/// Worst case blocking of `task`, the longest critical section of a
+/// lower priority task on a resource with ceiling `>= prio(task)`.
+pub fn blocking(set: &TaskSet, task: &Task) -> u32 {
+    let ceilings = set.ceilings();
+    let mut blocking = 0;
+    for t in set.tasks.iter().filter(|t| t.prio < task.prio) {
+        t.trace.visit(&mut |s| {
+            if s.id != t.id && ceilings[&s.id] >= task.prio {
+                blocking = blocking.max(s.len());
+            }
+        });
+    }
+    blocking
+}
+
+/// Interference of higher and equal priority tasks on `task` in a busy
+/// period of length `busy`.
+pub fn interference(set: &TaskSet, task: &Task, busy: u32) -> u32 {
+    set.tasks
+        .iter()
+        .filter(|t| t.prio >= task.prio && t.id != task.id)
+        .map(|t| busy.div_ceil(t.inter_arrival) * t.wcet())
+        .sum()
+}
+
+/// Response time of `task` by fixed point iteration, stopped once
+/// the deadline is exceeded.
+pub fn response_time(set: &TaskSet, task: &Task) -> u32 {
+    let base = task.wcet() + blocking(set, task);
+    let mut response = base;
+    loop {
+        let next = base + interference(set, task, response);
+        if next == response || next > task.deadline {
+            return next;
+        }
+        response = next;
+    }
+}
+
+/// Response time analysis of all tasks.
+pub fn analyse(set: &TaskSet) -> Analysis {
+    analysis(set, |task| response_time(set, task))
+}
+
+/// Approximate analysis, assuming a busy period equal to the deadline.
+pub fn analyse_approx(set: &TaskSet) -> Analysis {
+    analysis(set, |task| {
+        task.wcet() + blocking(set, task) + interference(set, task, task.deadline)
+    })
+}
+
+fn analysis(set: &TaskSet, response: impl Fn(&Task) -> u32) -> Analysis {
+    Analysis {
+        utilisation: utilisation(set),
+        tasks: set
+            .tasks
+            .iter()
+            .map(|task| {
+                let wcet = task.wcet();
+                let blocking = blocking(set, task);
+                let response = response(task);
+                TaskAnalysis {
+                    id: task.id.clone(),
+                    prio: task.prio,
+                    wcet,
+                    blocking,
+                    interference: response - wcet - blocking,
+                    response,
+                    deadline: task.deadline,
+                }
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sets;
+
+    fn summary(a: &Analysis) -> Vec<(&str, u32, u32, u32, u32)> {
+        a.tasks
+            .iter()
+            .map(|t| {
+                (
+                    t.id.as_str(),
+                    t.wcet,
+                    t.blocking,
+                    t.interference,
+                    t.response,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exam() {
+        let set = sets::exam();
+        let a = analyse(&set);
+        assert!((a.utilisation - 0.85).abs() < 1e-9);
+        assert_eq!(
+            summary(&a),
+            vec![
+                ("t1", 10, 0, 90, 100),
+                ("t2", 30, 0, 60, 90),
+                ("t3", 30, 4, 0, 34),
+            ]
+        );
+        assert!(a.schedulable());
+        assert_eq!(a.task("t1").unwrap().slack(), 0);
+        assert_eq!(a.task("t3").unwrap().slack(), 16);
+    }
+
+    #[test]
+    fn exam_approx() {
+        // the numbers of `klee_tutorial/srp_analysis`
+        let set = sets::exam();
+        let a = analyse_approx(&set);
+        assert_eq!(
+            summary(&a),
+            vec![
+                ("t1", 10, 0, 90, 100),
+                ("t2", 30, 0, 120, 150),
+                ("t3", 30, 4, 0, 34),
+            ]
+        );
+        assert!(a.schedulable());
+    }
+
+    #[test]
+    fn nested_blocking() {
+        // R1 (ceiling 2) encloses R2 (ceiling 3), t1 blocks t2 by the
+        // outer section and t3 by the inner
+        let mut set = sets::exam();
+        let t2 = set.tasks[1].trace.clone();
+        set.tasks[0].trace.inner = t2.inner;
+        set.tasks[0].trace.end = 30;
+        set.tasks[0].trace.id = "t1".into();
+        assert_eq!(blocking(&set, &set.tasks[1]), 10);
+        assert_eq!(blocking(&set, &set.tasks[2]), 4);
+        assert_eq!(blocking(&set, &set.tasks[0]), 0);
+    }
+
+    #[test]
+    fn equal_priority() {
+        // released together, one of the two waits for the other
+        let set = sets::equal_priority();
+        let a = analyse(&set);
+        assert_eq!(
+            summary(&a),
+            vec![("a", 10, 0, 10, 20), ("b", 10, 0, 10, 20)]
+        );
+        assert!(!a.schedulable());
+    }
+
+    #[test]
+    fn not_schedulable() {
+        let mut set = sets::exam();
+        set.tasks[0].trace.end = 20;
+        let a = analyse(&set);
+        let t1 = a.task("t1").unwrap();
+        assert!(!t1.schedulable());
+        assert!(t1.response > 100);
+        assert!(t1.slack() < 0);
+        let s = a.to_string();
+        assert!(s.contains("      110      100  MISS\n"));
+        assert!(s.ends_with("\nnot schedulable"));
+    }
+
+    #[test]
+    fn display() {
+        let set = sets::exam();
+        assert_eq!(
+            analyse(&set).to_string(),
+            "\
+utilisation 0.850
+task     prio     wcet    block   interf     resp deadline
+t1          1       10        0       90      100      100  ok
+t2          2       30        0       60       90      200  ok
+t3          3       30        4        0       34       50  ok
+schedulable"
+        );
+    }
+}
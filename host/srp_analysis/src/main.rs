@@ -0,0 +1,107 @@
+//! host/srp_analysis/src/main.rs
+//!
+//! > cargo run -p srp_analysis -- [--approx | --offsets | --sensitivity | --tests | --priorities] [--overheads file.toml] [--calibration rtt.log] [task set]
+//!
+//! Analyses a task set description (default `../tasks/timing_exam.toml`),
+//! `--offsets` adds the offset aware (simulated) response times,
+//! `--sensitivity` the WCET and critical section margins, `--tests` the
+//! Liu & Layland, hyperbolic and processor demand tests, `--priorities`
+//! a priority assignment (deadline monotonic or Audsley's) on the NVIC
+//! of the STM32F4.
+//!
+//! With `--overheads` (TOML, see `taskset::Overheads`) and/or
+//! `--calibration` (the calibration line of an RTT log) the task set
+//! is inflated by the runtime overheads and analysed in cycles.
+
+use srp_analysis::{
+    analyse, analyse_approx,
+    offsets::analyse_offsets,
+    overheads::{inflate, TIMER},
+    priority::{assign, NVIC_PRIO_BITS},
+    sensitivity::sensitivity,
+    sufficient::sufficient,
+};
+use std::{env, fs, process};
+use taskset::{Overheads, TaskSet};
+
+fn main() {
+    let mut approx = false;
+    let mut offsets = false;
+    let mut margins = false;
+    let mut tests = false;
+    let mut priorities = false;
+    let mut overheads = None;
+    let mut calibration = None;
+    let mut path = "../tasks/timing_exam.toml".to_string();
+    let fail = |msg: String| -> ! {
+        eprintln!("{}", msg);
+        process::exit(1)
+    };
+    let read = |file: Option<String>| {
+        let file = file.unwrap_or_else(|| fail("missing file argument".into()));
+        fs::read_to_string(&file).unwrap_or_else(|e| fail(format!("{}: {}", file, e)))
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--approx" => approx = true,
+            "--offsets" => offsets = true,
+            "--sensitivity" => margins = true,
+            "--tests" => tests = true,
+            "--priorities" => priorities = true,
+            "--overheads" => {
+                let toml = read(args.next());
+                overheads = Some(
+                    Overheads::from_toml(&toml)
+                        .unwrap_or_else(|e| fail(format!("overheads: {}", e))),
+                );
+            }
+            "--calibration" => calibration = Some(read(args.next())),
+            _ => path = arg,
+        }
+    }
+
+    let mut set = TaskSet::load(&path).unwrap_or_else(|e| fail(format!("{}: {}", path, e)));
+    if overheads.is_some() || calibration.is_some() {
+        let mut o = overheads.unwrap_or_default();
+        if let Some(log) = calibration {
+            o = o
+                .calibrated(&log)
+                .unwrap_or_else(|| fail("no calibration line in the log".into()));
+        }
+        println!("overheads: {}", o);
+        set = inflate(&set, &o, None);
+    }
+    let mut analysis = if approx {
+        analyse_approx(&set)
+    } else {
+        analyse(&set)
+    };
+    // not the timer handler of the inflated task set
+    analysis.tasks.retain(|t| !t.id.starts_with(TIMER));
+    println!("{} (1 time unit = {} cycles)", path, set.unit);
+    println!("{}", analysis);
+    if offsets {
+        match analyse_offsets(&set) {
+            Ok(offsets) => println!("\n{}", offsets),
+            Err(e) => eprintln!("offsets: {}", e),
+        }
+    }
+    if margins {
+        println!("\n{}", sensitivity(&set));
+    }
+    if tests {
+        for test in sufficient(&set) {
+            println!("\n{}", test);
+        }
+    }
+    if priorities {
+        match assign(&set, NVIC_PRIO_BITS) {
+            Ok(assignment) => println!("\n{}", assignment),
+            Err(e) => eprintln!("priorities: {}", e),
+        }
+    }
+    if !analysis.schedulable() {
+        process::exit(2)
+    }
+}
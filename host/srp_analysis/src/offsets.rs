@@ -0,0 +1,437 @@
+//! host/srp_analysis/src/offsets.rs
+//!
+//! Exact worst case response times for task sets with release offsets.
+//!
+//! The critical instant of [`crate::analyse`] assumes that all higher
+//! priority tasks are released together with the task (and that a lower
+//! priority task just entered its longest critical section). With release
+//! offsets this may never happen. Instead the SRP schedule is simulated
+//! (event by event) for all jobs released in `[0, O + 2H)`, where `O` is
+//! the largest offset and `H` the hyperperiod, after which the schedule
+//! repeats.
+//!
+//! Scheduling follows RTIC: the highest priority ready job runs, a job
+//! may only start if its priority is above the system ceiling (the
+//! highest ceiling of the currently locked resources), and jobs of equal
+//! priority run to completion in release order.
+
+use crate::analyse;
+use std::{cmp::Reverse, collections::BTreeMap, fmt};
+use taskset::{TaskSet, Trace};
+
+/// Simulations beyond this number of jobs are refused.
+pub const MAX_JOBS: u64 = 1_000_000;
+
+/// The hyperperiod is too long to be simulated.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TooLong {
+    /// Jobs in the simulated interval, `None` if beyond 64 bits (cycles).
+    pub jobs: Option<u64>,
+}
+
+impl fmt::Display for TooLong {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.jobs {
+            Some(jobs) => write!(
+                f,
+                "{} jobs in the simulated interval, at most {}",
+                jobs, MAX_JOBS
+            ),
+            None => write!(f, "simulated interval beyond 64 bits"),
+        }
+    }
+}
+
+impl std::error::Error for TooLong {}
+
+/// Response times of a task, with and without offsets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskResponse {
+    pub id: String,
+    pub deadline: u32,
+    /// Response time analysis (critical instant).
+    pub critical_instant: u32,
+    /// Worst simulated response (offset aware), `None` if never released.
+    pub offsets: Option<u64>,
+    /// Simulated jobs.
+    pub jobs: u64,
+    /// Simulated jobs missing their deadline.
+    pub misses: u64,
+}
+
+/// Offset aware analysis of a task set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OffsetAnalysis {
+    pub hyperperiod: u64,
+    /// End of the simulated release interval, `O + 2H`.
+    pub horizon: u64,
+    pub tasks: Vec<TaskResponse>,
+}
+
+impl OffsetAnalysis {
+    /// Task by id.
+    pub fn task(&self, id: &str) -> Option<&TaskResponse> {
+        self.tasks.iter().find(|t| t.id == id)
+    }
+}
+
+impl fmt::Display for OffsetAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "hyperperiod {}, releases simulated in 0..{}",
+            self.hyperperiod, self.horizon
+        )?;
+        write!(
+            f,
+            "{:<8} {:>8} {:>8} {:>8} {:>6} {:>6}",
+            "task", "critical", "offsets", "deadline", "jobs", "misses"
+        )?;
+        for t in &self.tasks {
+            write!(
+                f,
+                "\n{:<8} {:>8} {:>8} {:>8} {:>6} {:>6}",
+                t.id,
+                t.critical_instant,
+                t.offsets.map_or_else(|| "-".to_string(), |r| r.to_string()),
+                t.deadline,
+                t.jobs,
+                t.misses
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Least common multiple of the inter arrival times, `None` beyond 64 bits.
+pub fn hyperperiod(set: &TaskSet) -> Option<u64> {
+    set.tasks
+        .iter()
+        .try_fold(1, |h, t| lcm(h, t.inter_arrival as u64))
+}
+
+/// The largest offset plus two hyperperiods, `None` beyond 64 bits.
+pub fn horizon(set: &TaskSet) -> Option<u64> {
+    let max_offset = set.tasks.iter().map(|t| t.offset as u64).max().unwrap_or(0);
+    hyperperiod(set)?.checked_mul(2)?.checked_add(max_offset)
+}
+
+/// Critical instant and offset aware (simulated) response times.
+pub fn analyse_offsets(set: &TaskSet) -> Result<OffsetAnalysis, TooLong> {
+    let (hyperperiod, horizon) = match (hyperperiod(set), horizon(set)) {
+        (Some(hyperperiod), Some(horizon)) => (hyperperiod, horizon),
+        _ => return Err(TooLong { jobs: None }),
+    };
+    let jobs: u64 = set
+        .tasks
+        .iter()
+        .map(|t| (horizon.saturating_sub(t.offset as u64)).div_ceil(t.inter_arrival as u64))
+        .sum();
+    if jobs > MAX_JOBS {
+        return Err(TooLong { jobs: Some(jobs) });
+    }
+
+    let critical = analyse(set);
+    let mut tasks: Vec<TaskResponse> = set
+        .tasks
+        .iter()
+        .zip(&critical.tasks)
+        .map(|(t, a)| TaskResponse {
+            id: t.id.clone(),
+            deadline: t.deadline,
+            critical_instant: a.response,
+            offsets: None,
+            jobs: 0,
+            misses: 0,
+        })
+        .collect();
+    for (task, response) in simulate(set, horizon) {
+        let t = &mut tasks[task];
+        t.offsets = Some(t.offsets.unwrap_or(0).max(response));
+        t.jobs += 1;
+        if response > t.deadline as u64 {
+            t.misses += 1;
+        }
+    }
+    Ok(OffsetAnalysis {
+        hyperperiod,
+        horizon,
+        tasks,
+    })
+}
+
+#[derive(Clone, Copy)]
+enum Action {
+    Lock(u8),
+    Unlock,
+}
+
+struct Job {
+    task: usize,
+    prio: u8,
+    release: u64,
+    executed: u32,
+    wcet: u32,
+    /// Lock/unlock at execution time, in order.
+    actions: Vec<(u32, Action)>,
+    next: usize,
+}
+
+impl Job {
+    fn started(&self) -> bool {
+        self.executed > 0 || self.next > 0
+    }
+}
+
+fn actions(
+    trace: &Trace,
+    ceilings: &BTreeMap<String, u8>,
+    top: bool,
+    out: &mut Vec<(u32, Action)>,
+) {
+    if !top {
+        out.push((trace.start, Action::Lock(ceilings[&trace.id])));
+    }
+    for inner in &trace.inner {
+        actions(inner, ceilings, false, out);
+    }
+    if !top {
+        out.push((trace.end, Action::Unlock));
+    }
+}
+
+/// Simulates the jobs released before `horizon` to completion,
+/// returns `(task index, response time)` per job in order of completion.
+pub(crate) fn simulate(set: &TaskSet, horizon: u64) -> Vec<(usize, u64)> {
+    let ceilings = set.ceilings();
+    let templates: Vec<Vec<(u32, Action)>> = set
+        .tasks
+        .iter()
+        .map(|t| {
+            let mut out = vec![];
+            actions(&t.trace, &ceilings, true, &mut out);
+            // actions relative to the start of the task
+            out.iter().map(|(at, a)| (at - t.trace.start, *a)).collect()
+        })
+        .collect();
+
+    let mut next_release: Vec<u64> = set.tasks.iter().map(|t| t.offset as u64).collect();
+    let mut ready: Vec<Job> = vec![];
+    // ceilings of the locked resources, a stack under SRP
+    let mut locked: Vec<u8> = vec![];
+    let mut done = vec![];
+    let mut now = 0;
+
+    loop {
+        for (i, t) in set.tasks.iter().enumerate() {
+            while next_release[i] <= now && next_release[i] < horizon {
+                ready.push(Job {
+                    task: i,
+                    prio: t.prio,
+                    release: next_release[i],
+                    executed: 0,
+                    wcet: t.wcet(),
+                    actions: templates[i].clone(),
+                    next: 0,
+                });
+                next_release[i] += t.inter_arrival as u64;
+            }
+        }
+        let release = next_release.iter().copied().filter(|&r| r < horizon).min();
+
+        let ceiling = locked.last().copied().unwrap_or(0);
+        let running = ready
+            .iter()
+            .enumerate()
+            .filter(|(_, j)| j.started() || j.prio > ceiling)
+            .max_by_key(|(i, j)| (j.prio, j.started(), Reverse(j.release), Reverse(*i)))
+            .map(|(i, _)| i);
+        let i = match (running, release) {
+            (Some(i), _) => i,
+            (None, Some(release)) => {
+                now = release;
+                continue;
+            }
+            (None, None) => return done,
+        };
+
+        let job = &mut ready[i];
+        let mut acted = false;
+        while let Some(&(at, action)) = job.actions.get(job.next) {
+            if at != job.executed {
+                break;
+            }
+            apply(&mut locked, action);
+            job.next += 1;
+            acted = true;
+        }
+        if acted {
+            // an unlock may let a pending job start
+            continue;
+        }
+        if job.executed == job.wcet {
+            done.push((job.task, now - job.release));
+            ready.remove(i);
+            continue;
+        }
+
+        let until = job.actions.get(job.next).map_or(job.wcet, |&(at, _)| at) - job.executed;
+        let step = match release {
+            Some(release) => (until as u64).min(release - now),
+            None => until as u64,
+        };
+        job.executed += step as u32;
+        now += step;
+        if job.executed == job.wcet {
+            // complete before any release at `now` is dispatched
+            for &(_, action) in &job.actions[job.next..] {
+                apply(&mut locked, action);
+            }
+            done.push((job.task, now - job.release));
+            ready.remove(i);
+        }
+    }
+}
+
+fn apply(locked: &mut Vec<u8>, action: Action) {
+    match action {
+        Action::Lock(ceiling) => locked.push(ceiling.max(locked.last().copied().unwrap_or(0))),
+        Action::Unlock => {
+            locked.pop();
+        }
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn lcm(a: u64, b: u64) -> Option<u64> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sets;
+
+    fn responses(a: &OffsetAnalysis) -> Vec<(&str, u32, Option<u64>)> {
+        a.tasks
+            .iter()
+            .map(|t| (t.id.as_str(), t.critical_instant, t.offsets))
+            .collect()
+    }
+
+    #[test]
+    fn exam() {
+        let set = sets::exam();
+        let a = analyse_offsets(&set).unwrap();
+        assert_eq!(a.hyperperiod, 200);
+        assert_eq!(a.horizon, 600);
+        // with the offsets t3 is never blocked by t2
+        assert_eq!(
+            responses(&a),
+            vec![
+                ("t1", 100, Some(100)),
+                ("t2", 90, Some(90)),
+                ("t3", 34, Some(30))
+            ]
+        );
+        assert_eq!(
+            a.tasks.iter().map(|t| t.jobs).collect::<Vec<_>>(),
+            vec![5, 2, 11]
+        );
+        assert!(a.tasks.iter().all(|t| t.misses == 0));
+        assert_eq!(
+            a.to_string(),
+            "\
+hyperperiod 200, releases simulated in 0..600
+task     critical  offsets deadline   jobs misses
+t1            100      100      100      5      0
+t2             90       90      200      2      0
+t3             34       30       50     11      0"
+        );
+    }
+
+    #[test]
+    fn blocking() {
+        // t2 enters R2 at 12 (at time 12), t3 released at 13 is
+        // blocked until t2 leaves R2 at 16
+        let mut set = sets::exam();
+        set.tasks.remove(0);
+        set.tasks[0].offset = 0;
+        set.tasks[1].offset = 13;
+        set.tasks[1].inter_arrival = 200;
+        set.tasks[1].deadline = 200;
+        let a = analyse_offsets(&set).unwrap();
+        assert_eq!(a.task("t3").unwrap().offsets, Some(3 + 30));
+        // and then preempts t2 at 16 (outside R2, within R1, ceiling 2)
+        assert_eq!(a.task("t2").unwrap().offsets, Some(60));
+    }
+
+    #[test]
+    fn equal_priority() {
+        // run to completion in release order
+        let mut set = sets::exam();
+        set.tasks[0].prio = 3;
+        set.tasks[0].offset = 55;
+        let a = analyse_offsets(&set).unwrap();
+        // t3 released at 50 runs to 80, t1 (55) completes at 90
+        assert_eq!(a.task("t1").unwrap().offsets, Some(35));
+    }
+
+    #[test]
+    fn synchronous_release() {
+        // without offsets the simulation reaches the critical instant
+        // (except for the blocking)
+        let mut set = sets::exam();
+        for t in &mut set.tasks {
+            t.offset = 0;
+        }
+        let a = analyse_offsets(&set).unwrap();
+        assert_eq!(
+            responses(&a),
+            vec![
+                ("t1", 100, Some(100)),
+                ("t2", 90, Some(90)),
+                ("t3", 34, Some(30))
+            ]
+        );
+    }
+
+    #[test]
+    fn too_long() {
+        let mut set = sets::exam();
+        set.tasks[0].inter_arrival = 999_983;
+        set.tasks[1].inter_arrival = 999_979;
+        assert_eq!(
+            analyse_offsets(&set).unwrap_err().to_string(),
+            "2000123996918 jobs in the simulated interval, at most 1000000"
+        );
+        assert_eq!(lcm(4, 6), Some(12));
+    }
+
+    #[test]
+    fn beyond_64_bits() {
+        // coprime inter arrival times, a hyperperiod of about 2^80
+        let mut set = sets::exam();
+        set.tasks.push(set.tasks[0].clone());
+        set.tasks[3].id = "t4".into();
+        set.tasks[3].trace.id = "t4".into();
+        for (t, a) in set
+            .tasks
+            .iter_mut()
+            .zip(&[1_048_573, 1_048_571, 1_048_559, 1_048_549])
+        {
+            t.inter_arrival = *a;
+            t.deadline = *a;
+        }
+        set.validate().unwrap();
+        assert_eq!(hyperperiod(&set), None);
+        assert_eq!(analyse_offsets(&set), Err(TooLong { jobs: None }));
+    }
+}
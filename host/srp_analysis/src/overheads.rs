@@ -0,0 +1,153 @@
+//! host/srp_analysis/src/overheads.rs
+//!
+//! Runtime overheads in the analysis.
+//!
+//! The task set is inflated by the costs of the RTIC runtime
+//! ([`Overheads`], as simulated by `rtic_sim`), into an equivalent task
+//! set in clock cycles (`unit = 1`), to which any of the analyses apply:
+//!
+//! - Each task is prolonged by `pend + dispatch + timer_insert` before its
+//!   body and `exit` after it.
+//! - Each critical section is prolonged by `lock + unlock` (the costs are
+//!   accounted inside the section, so blocking is bounded safely).
+//! - Each release costs `pend + timer_pop` in the timer handler, which
+//!   preempts the tasks below the timer priority. This is modelled as a
+//!   task `timer:<task>` per task (at the timer priority, released with
+//!   the task). The tasks at the timer priority are delayed by the handler
+//!   as by any task of equal priority, so the releases are charged once,
+//!   as interference of the pseudo tasks.
+
+use taskset::{Overheads, Task, TaskSet, Trace};
+
+/// Prefix of the timer handler (pseudo) tasks.
+pub const TIMER: &str = "timer:";
+
+/// The task set in clock cycles, inflated by the overheads. The timer
+/// handler runs at `timer_prio`, by default the highest task priority.
+pub fn inflate(set: &TaskSet, o: &Overheads, timer_prio: Option<u8>) -> TaskSet {
+    let unit = set.unit;
+    let timer_prio =
+        timer_prio.unwrap_or_else(|| set.tasks.iter().map(|t| t.prio).max().unwrap_or(1));
+    let pop = o.pend + o.timer_pop;
+
+    let mut tasks: Vec<Task> = set
+        .tasks
+        .iter()
+        .map(|t| {
+            let mut trace = t.trace.clone();
+            let mut shift = o.pend + o.dispatch + o.timer_insert;
+            inflate_trace(&mut trace, unit, o.lock + o.unlock, true, &mut shift);
+            trace.end += o.exit;
+            Task {
+                deadline: t.deadline * unit,
+                inter_arrival: t.inter_arrival * unit,
+                offset: t.offset * unit,
+                trace,
+                ..t.clone()
+            }
+        })
+        .collect();
+    if pop > 0 {
+        for t in &set.tasks {
+            let id = format!("{}{}", TIMER, t.id);
+            tasks.push(Task {
+                id: id.clone(),
+                prio: timer_prio,
+                deadline: t.inter_arrival * unit,
+                inter_arrival: t.inter_arrival * unit,
+                offset: t.offset * unit,
+                trace: Trace {
+                    id,
+                    start: 0,
+                    end: pop,
+                    inner: vec![],
+                },
+            });
+        }
+    }
+    TaskSet { unit: 1, tasks }
+}
+
+/// Scales `trace` to cycles, prolonging the critical sections by `cost`.
+fn inflate_trace(trace: &mut Trace, unit: u32, cost: u32, top: bool, shift: &mut u32) {
+    trace.start = trace.start * unit + if top { 0 } else { *shift };
+    for inner in &mut trace.inner {
+        inflate_trace(inner, unit, cost, false, shift);
+    }
+    if !top {
+        *shift += cost;
+    }
+    trace.end = trace.end * unit + *shift;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyse;
+    use crate::sets;
+
+    // in the order of the calibration of `timing_exam.rs`, see `rtic_sim`
+    const OVERHEADS: Overheads = Overheads {
+        pend: 12,
+        dispatch: 156,
+        exit: 40,
+        lock: 20,
+        unlock: 20,
+        timer_insert: 82,
+        timer_pop: 60,
+    };
+
+    #[test]
+    fn no_overheads() {
+        let set = sets::exam();
+        let inflated = inflate(&set, &Overheads::default(), None);
+        assert_eq!(inflated.unit, 1);
+        assert_eq!(inflated.tasks.len(), 3);
+        let a = analyse(&inflated);
+        assert_eq!(
+            a.tasks.iter().map(|t| t.response).collect::<Vec<_>>(),
+            vec![100_000, 90_000, 34_000]
+        );
+    }
+
+    #[test]
+    fn exam() {
+        let set = sets::exam();
+        let inflated = inflate(&set, &OVERHEADS, None);
+        let t2 = &inflated.tasks[1].trace;
+        // 250 cycles before the body, R1 (10..20, R2 12..16), R1 22..28
+        assert_eq!((t2.start, t2.end), (0, 30_000 + 250 + 3 * 40 + 40));
+        assert_eq!((t2.inner[0].start, t2.inner[0].end), (10_250, 20_330));
+        assert_eq!(
+            (t2.inner[0].inner[0].start, t2.inner[0].inner[0].end),
+            (12_250, 16_290)
+        );
+        assert_eq!((t2.inner[1].start, t2.inner[1].end), (22_330, 28_370));
+        // t3 at the timer priority, delayed by the pops as interference
+        assert_eq!(inflated.tasks[2].wcet(), 30_000 + 250 + 40 + 40);
+        assert_eq!(inflated.task("timer:t1").unwrap().wcet(), 72);
+
+        // the deadline miss of t1
+        let a = analyse(&inflated);
+        let t1 = a.task("t1").unwrap();
+        assert!(!t1.schedulable());
+        assert_eq!(a.task("t3").unwrap().blocking, 4_040);
+        assert!(a.task("t2").unwrap().schedulable());
+        assert!(a.task("timer:t3").unwrap().schedulable());
+        // the pops charged once, one release of each task in the busy period
+        let t3 = a.task("t3").unwrap();
+        assert_eq!(t3.interference, 3 * 72);
+        assert_eq!(t3.response, 4_040 + 30_330 + 3 * 72);
+    }
+
+    #[test]
+    fn timer_priority() {
+        // the timer above all tasks, preempts t3 as well
+        let set = sets::exam();
+        let inflated = inflate(&set, &OVERHEADS, Some(4));
+        assert_eq!(inflated.tasks[2].wcet(), 30_000 + 250 + 40 + 40);
+        let t3 = analyse(&inflated).task("t3").unwrap().clone();
+        // one release of each task in the busy period
+        assert_eq!(t3.interference, 3 * 72);
+    }
+}
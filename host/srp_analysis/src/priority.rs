@@ -0,0 +1,423 @@
+//! host/srp_analysis/src/priority.rs
+//!
+//! Priority assignment, from the deadlines, inter arrival times, WCETs
+//! and resource usage of a task set (the priorities given are ignored).
+//!
+//! - Deadline monotonic, the shorter the deadline the higher the
+//!   priority, optimal for constrained deadlines (`D <= A`) without
+//!   blocking.
+//! - Audsley's optimal priority assignment otherwise. From the lowest
+//!   level up, some task schedulable at the level (with all unassigned
+//!   tasks above it) is assigned to it. Under SRP the blocking of a task
+//!   depends only on which tasks are below it, so the search finds an
+//!   assignment whenever one exists for [`crate::analyse`].
+//!
+//! Each task gets a level of its own, `1..=n` (at most 255 tasks). With
+//! more levels than the NVIC implements, adjacent levels are then merged,
+//! from the lowest up, while the task set stays schedulable. RTIC maps the
+//! logical priority `p` onto the NVIC as `(2^bits - p) << (8 - bits)`, for
+//! the `bits` implemented priority bits (4 on the STM32F4, 16 levels).
+
+use crate::{analyse, blocking, Analysis};
+use std::fmt;
+use taskset::{codegen::DISPATCHERS, TaskSet};
+
+/// Priority bits of the STM32F4 NVIC.
+pub const NVIC_PRIO_BITS: u8 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    DeadlineMonotonic,
+    Audsley,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Method::DeadlineMonotonic => "deadline monotonic",
+            Method::Audsley => "Audsley",
+        })
+    }
+}
+
+/// More tasks than logical priorities, `u8` levels `1..=255`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TooManyTasks {
+    pub tasks: usize,
+}
+
+impl fmt::Display for TooManyTasks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} tasks, at most {} priority levels",
+            self.tasks,
+            u8::MAX
+        )
+    }
+}
+
+impl std::error::Error for TooManyTasks {}
+
+/// A proposed priority assignment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assignment {
+    pub method: Method,
+    /// Why the method was chosen.
+    pub why: String,
+    /// The task set with the assigned priorities.
+    pub set: TaskSet,
+    pub analysis: Analysis,
+    /// The priorities given, by task.
+    pub given: Vec<(String, u8)>,
+    /// Priority bits of the target.
+    pub bits: u8,
+}
+
+impl Assignment {
+    /// Priority levels used.
+    pub fn levels(&self) -> usize {
+        let mut prios: Vec<u8> = self.set.tasks.iter().map(|t| t.prio).collect();
+        prios.sort_unstable();
+        prios.dedup();
+        prios.len()
+    }
+
+    /// The assignment fits the NVIC.
+    pub fn fits(&self) -> bool {
+        self.levels() <= 1 << self.bits
+    }
+
+    fn given(&self, id: &str) -> String {
+        self.given
+            .iter()
+            .find(|(t, _)| t == id)
+            .map_or_else(|| "-".to_string(), |(_, p)| p.to_string())
+    }
+}
+
+impl fmt::Display for Assignment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} priorities ({})", self.method, self.why)?;
+        writeln!(
+            f,
+            "{:<8} {:>4} {:>6} {:>8} {:>8} {:>8}",
+            "task", "prio", "nvic", "deadline", "resp", "given"
+        )?;
+        let mut tasks: Vec<_> = self.analysis.tasks.iter().collect();
+        tasks.sort_by_key(|t| std::cmp::Reverse(t.prio));
+        for t in tasks {
+            writeln!(
+                f,
+                "{:<8} {:>4} {:>6} {:>8} {:>8} {:>8}  {}",
+                t.id,
+                t.prio,
+                nvic(t.prio, self.bits)
+                    .map_or_else(|| "-".to_string(), |hw| format!("{:#04x}", hw)),
+                t.deadline,
+                t.response,
+                self.given(&t.id),
+                if t.schedulable() { "ok" } else { "MISS" }
+            )?;
+        }
+        let levels = self.levels();
+        write!(
+            f,
+            "{} levels, the NVIC has {} ({} priority bits)",
+            levels,
+            1 << self.bits,
+            self.bits
+        )?;
+        if !self.fits() {
+            write!(f, ", {} levels too many", levels - (1 << self.bits))?;
+        }
+        if levels > DISPATCHERS.len() {
+            write!(
+                f,
+                "\nthe generated firmware has {} dispatchers, merge levels or add dispatchers",
+                DISPATCHERS.len()
+            )?;
+        }
+        if !self.analysis.schedulable() {
+            write!(f, "\nnot schedulable under any fixed priority assignment")?;
+        }
+        Ok(())
+    }
+}
+
+/// The NVIC priority register value of the logical priority `prio`, `None`
+/// beyond the `bits` implemented.
+pub fn nvic(prio: u8, bits: u8) -> Option<u8> {
+    let levels = 1u16 << bits;
+    if prio == 0 || prio as u16 > levels {
+        None
+    } else {
+        Some(((levels - prio as u16) << (8 - bits)) as u8)
+    }
+}
+
+/// Deadline monotonic priorities, ties in the order of the task set.
+///
+/// # Panics
+///
+/// With more than 255 tasks, see [`assign`].
+pub fn deadline_monotonic(set: &TaskSet) -> TaskSet {
+    assert!(
+        set.tasks.len() <= u8::MAX as usize,
+        "more tasks than levels"
+    );
+    let mut order: Vec<usize> = (0..set.tasks.len()).collect();
+    // by increasing priority, the first of equal deadlines the higher
+    order.sort_by_key(|&i| std::cmp::Reverse((set.tasks[i].deadline, i)));
+    let mut set = set.clone();
+    for (level, &i) in order.iter().rev().enumerate() {
+        set.tasks[i].prio = (order.len() - level) as u8;
+    }
+    set
+}
+
+/// Audsley's optimal priority assignment, `None` if no assignment makes the
+/// task set schedulable.
+///
+/// # Panics
+///
+/// With more than 255 tasks, see [`assign`].
+pub fn audsley(set: &TaskSet) -> Option<TaskSet> {
+    let n = set.tasks.len();
+    assert!(n <= u8::MAX as usize, "more tasks than levels");
+    let mut set = set.clone();
+    let mut unassigned: Vec<usize> = (0..n).collect();
+    // candidates by decreasing deadline, the deadline monotonic order when
+    // that works
+    unassigned.sort_by_key(|&i| set.tasks[i].deadline);
+    for level in 1..=n as u8 {
+        let pick = unassigned.iter().rposition(|&i| {
+            let mut trial = set.clone();
+            for &j in &unassigned {
+                trial.tasks[j].prio = if i == j { level } else { level + 1 };
+            }
+            analyse(&trial).tasks[i].schedulable()
+        })?;
+        let i = unassigned.remove(pick);
+        set.tasks[i].prio = level;
+    }
+    Some(set)
+}
+
+/// Merges adjacent levels of `set` (levels `1..=n`), from the lowest up,
+/// while more than `levels` are used and the set stays schedulable.
+fn merge(mut set: TaskSet, levels: usize) -> TaskSet {
+    let mut top = set.tasks.iter().map(|t| t.prio).max().unwrap_or(0);
+    let mut level = 1;
+    while top as usize > levels && level < top {
+        let mut trial = set.clone();
+        for t in &mut trial.tasks {
+            if t.prio > level {
+                t.prio -= 1;
+            }
+        }
+        if analyse(&trial).schedulable() {
+            set = trial;
+            top -= 1;
+        } else {
+            level += 1;
+        }
+    }
+    set
+}
+
+/// A priority assignment for `set`, on an NVIC with `bits` priority bits,
+/// refused beyond 255 tasks.
+pub fn assign(set: &TaskSet, bits: u8) -> Result<Assignment, TooManyTasks> {
+    if set.tasks.len() > u8::MAX as usize {
+        return Err(TooManyTasks {
+            tasks: set.tasks.len(),
+        });
+    }
+    let dm = deadline_monotonic(set);
+    let blocked = dm.tasks.iter().find(|t| blocking(&dm, t) > 0);
+    let arbitrary = dm.tasks.iter().find(|t| t.deadline > t.inter_arrival);
+    let (method, why, assigned) = match (blocked, arbitrary) {
+        (None, None) => (
+            Method::DeadlineMonotonic,
+            "no blocking, constrained deadlines".to_string(),
+            dm,
+        ),
+        (Some(t), _) => (
+            Method::Audsley,
+            format!("`{}` is blocked", t.id),
+            audsley(set).unwrap_or(dm),
+        ),
+        (None, Some(t)) => (
+            Method::Audsley,
+            format!("`{}` has a deadline beyond its inter arrival", t.id),
+            audsley(set).unwrap_or(dm),
+        ),
+    };
+    let assigned = merge(assigned, 1 << bits);
+    Ok(Assignment {
+        method,
+        why,
+        analysis: analyse(&assigned),
+        given: set.tasks.iter().map(|t| (t.id.clone(), t.prio)).collect(),
+        set: assigned,
+        bits,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sets;
+    use taskset::{Task, Trace};
+
+    fn prios(set: &TaskSet) -> Vec<u8> {
+        set.tasks.iter().map(|t| t.prio).collect()
+    }
+
+    /// Some permutation of priorities makes `set` schedulable.
+    fn exists(set: &TaskSet) -> bool {
+        fn permute(set: &mut TaskSet, k: usize) -> bool {
+            if k == set.tasks.len() {
+                return analyse(set).schedulable();
+            }
+            for i in k..set.tasks.len() {
+                set.tasks.swap(k, i);
+                set.tasks[k].prio = k as u8 + 1;
+                if permute(set, k + 1) {
+                    return true;
+                }
+                set.tasks.swap(k, i);
+            }
+            false
+        }
+        permute(&mut set.clone(), 0)
+    }
+
+    #[test]
+    fn nvic_levels() {
+        // RTIC `logical2hw`
+        assert_eq!(nvic(1, 4), Some(0xf0));
+        assert_eq!(nvic(16, 4), Some(0x00));
+        assert_eq!(nvic(3, 4), Some(0xd0));
+        assert_eq!(nvic(17, 4), None);
+        assert_eq!(nvic(0, 4), None);
+        assert_eq!(nvic(1, 3), Some(0xe0));
+    }
+
+    #[test]
+    fn exam() {
+        let set = sets::exam();
+        // t2 (D 200) below t1 (D 100), unlike the exam
+        assert_eq!(prios(&deadline_monotonic(&set)), vec![2, 1, 3]);
+        let a = assign(&set, NVIC_PRIO_BITS).unwrap();
+        assert_eq!(a.method, Method::Audsley);
+        assert_eq!(prios(&a.set), vec![2, 1, 3]);
+        assert!(a.fits());
+        assert_eq!(
+            a.to_string(),
+            "\
+Audsley priorities (`t1` is blocked)
+task     prio   nvic deadline     resp    given
+t3          3   0xd0       50       34        3  ok
+t1          2   0xe0      100       44        1  ok
+t2          1   0xf0      200      100        2  ok
+3 levels, the NVIC has 16 (4 priority bits)"
+        );
+    }
+
+    #[test]
+    fn tied_deadlines() {
+        // the first of equal deadlines the higher priority
+        let mut set = sets::exam();
+        set.tasks[1].deadline = 100;
+        assert_eq!(prios(&deadline_monotonic(&set)), vec![2, 1, 3]);
+        set.tasks[2].deadline = 100;
+        assert_eq!(prios(&deadline_monotonic(&set)), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn deadline_monotonic_without_blocking() {
+        let mut set = sets::exam();
+        for t in &mut set.tasks {
+            t.trace.inner.clear();
+        }
+        let a = assign(&set, NVIC_PRIO_BITS).unwrap();
+        assert_eq!(a.method, Method::DeadlineMonotonic);
+        assert_eq!(prios(&a.set), vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn optimal() {
+        // tighten the deadlines, Audsley finds an assignment whenever one
+        // exists
+        let exam = sets::exam();
+        for d1 in (40..=100).step_by(10) {
+            for d2 in (40..=200).step_by(20) {
+                let mut set = exam.clone();
+                set.tasks[0].deadline = d1;
+                set.tasks[1].deadline = d2;
+                let found = audsley(&set);
+                assert_eq!(found.is_some(), exists(&set), "D1 {} D2 {}", d1, d2);
+                if let Some(found) = found {
+                    assert!(analyse(&found).schedulable());
+                }
+            }
+        }
+    }
+
+    /// `n` tasks of WCET 1, deadline monotonic, `deadline(i)` of task `i`.
+    fn tasks(n: u32, deadline: impl Fn(u32) -> u32) -> TaskSet {
+        let tasks = (1..=n)
+            .map(|i| Task {
+                id: format!("t{}", i),
+                prio: 1,
+                deadline: deadline(i),
+                inter_arrival: 100 * n,
+                offset: 0,
+                trace: Trace {
+                    id: format!("t{}", i),
+                    start: 0,
+                    end: 1,
+                    inner: vec![],
+                },
+            })
+            .collect();
+        TaskSet { unit: 1, tasks }
+    }
+
+    #[test]
+    fn shared_levels() {
+        // 20 tasks on 16 levels, the 5 lowest share a level
+        let set = tasks(20, |i| 100 * i);
+        let a = assign(&set, NVIC_PRIO_BITS).unwrap();
+        assert_eq!(a.levels(), 16);
+        assert!(a.fits());
+        assert!(a.analysis.schedulable());
+        assert_eq!(&prios(&a.set)[14..], &[2, 1, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn too_many_levels() {
+        // each task finishes at its deadline, merging any two levels makes
+        // the higher miss it
+        let set = tasks(20, |i| i);
+        let a = assign(&set, NVIC_PRIO_BITS).unwrap();
+        assert!(a.analysis.schedulable());
+        assert_eq!(a.levels(), 20);
+        assert!(!a.fits());
+        let report = a.to_string();
+        assert!(report.contains("20 levels, the NVIC has 16 (4 priority bits), 4 levels too many"));
+        // the four lowest levels have no NVIC priority
+        assert!(report.contains("\nt4         17      -"));
+    }
+
+    #[test]
+    fn too_many_tasks() {
+        let set = tasks(256, |i| 100 * i);
+        assert_eq!(
+            assign(&set, 8).unwrap_err().to_string(),
+            "256 tasks, at most 255 priority levels"
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! host/srp_analysis/src/sensitivity.rs
+//!
+//! Sensitivity analysis, the margins of a schedulable task set.
+//!
+//! - Per task, the largest factor its WCET (the whole trace, including
+//!   its critical sections) can be scaled by, with all other tasks
+//!   unchanged, keeping the task set schedulable ([`crate::analyse`]).
+//!   Found by bisection in steps of 1%.
+//! - Per resource, the largest extension of all critical sections on the
+//!   resource (each prolonging its task by the same amount), keeping the
+//!   task set schedulable, in time units of the task set.
+//!
+//! Response times grow with execution and blocking, so schedulability is
+//! monotonic in both and the bisection finds the exact margin.
+
+use crate::analyse;
+use std::fmt;
+use taskset::{TaskSet, Trace};
+
+/// Scaling factors in steps of `1 / SCALE`.
+const SCALE: u32 = 100;
+
+/// Margin of a task.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TaskSensitivity {
+    pub id: String,
+    pub wcet: u32,
+    /// Largest WCET scaling factor, `None` if not schedulable even at 0.
+    pub scaling: Option<f64>,
+}
+
+/// Margin of a resource.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceSensitivity {
+    pub id: String,
+    pub ceiling: u8,
+    /// Longest critical section on the resource.
+    pub longest: u32,
+    /// Largest extension of the critical sections, `None` if not
+    /// schedulable as is.
+    pub extension: Option<u32>,
+}
+
+impl ResourceSensitivity {
+    /// Longest critical section keeping the task set schedulable.
+    pub fn max_length(&self) -> Option<u32> {
+        self.extension.map(|e| self.longest + e)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sensitivity {
+    pub tasks: Vec<TaskSensitivity>,
+    pub resources: Vec<ResourceSensitivity>,
+}
+
+impl fmt::Display for Sensitivity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let dash = || "-".to_string();
+        writeln!(
+            f,
+            "{:<8} {:>8} {:>8} {:>8}",
+            "task", "wcet", "scaling", "max wcet"
+        )?;
+        for t in &self.tasks {
+            writeln!(
+                f,
+                "{:<8} {:>8} {:>8} {:>8}",
+                t.id,
+                t.wcet,
+                t.scaling.map_or_else(dash, |s| format!("{:.2}", s)),
+                t.scaling
+                    .map_or_else(dash, |s| format!("{:.1}", t.wcet as f64 * s))
+            )?;
+        }
+        write!(
+            f,
+            "{:<8} {:>8} {:>8} {:>8}",
+            "resource", "ceiling", "longest", "max"
+        )?;
+        for r in &self.resources {
+            write!(
+                f,
+                "\n{:<8} {:>8} {:>8} {:>8}",
+                r.id,
+                r.ceiling,
+                r.longest,
+                r.max_length().map_or_else(dash, |m| m.to_string())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Margins of all tasks and resources of `set`.
+pub fn sensitivity(set: &TaskSet) -> Sensitivity {
+    let tasks = set
+        .tasks
+        .iter()
+        .enumerate()
+        .map(|(i, t)| TaskSensitivity {
+            id: t.id.clone(),
+            wcet: t.wcet(),
+            scaling: wcet_scaling(set, i),
+        })
+        .collect();
+    let resources = set
+        .ceilings()
+        .into_iter()
+        .map(|(id, ceiling)| {
+            let mut longest = 0;
+            for t in &set.tasks {
+                t.trace.visit(&mut |s| {
+                    if s.id == id {
+                        longest = longest.max(s.len());
+                    }
+                });
+            }
+            ResourceSensitivity {
+                extension: critical_section_extension(set, &id),
+                id,
+                ceiling,
+                longest,
+            }
+        })
+        .collect();
+    Sensitivity { tasks, resources }
+}
+
+/// Largest WCET scaling factor of task `i`.
+pub fn wcet_scaling(set: &TaskSet, i: usize) -> Option<f64> {
+    let deadline = set.tasks[i].deadline;
+    let wcet = set.tasks[i].wcet().max(1);
+    // beyond the deadline for sure
+    let limit = SCALE * (deadline / wcet + 1);
+    largest(limit, |k| {
+        // in `1 / SCALE` units, the task scaled by `k / SCALE`
+        let mut scaled = set.clone();
+        for (j, t) in scaled.tasks.iter_mut().enumerate() {
+            t.deadline *= SCALE;
+            t.inter_arrival *= SCALE;
+            t.offset *= SCALE;
+            scale(&mut t.trace, if i == j { k } else { SCALE });
+        }
+        analyse(&scaled).schedulable()
+    })
+    .map(|k| k as f64 / SCALE as f64)
+}
+
+/// Largest extension of the critical sections on `resource`.
+pub fn critical_section_extension(set: &TaskSet, resource: &str) -> Option<u32> {
+    let limit = set.tasks.iter().map(|t| t.deadline).max().unwrap_or(0) + 1;
+    largest(limit, |delta| {
+        let mut extended = set.clone();
+        for t in &mut extended.tasks {
+            let mut shift = 0;
+            extend(&mut t.trace, resource, delta, &mut shift);
+        }
+        analyse(&extended).schedulable()
+    })
+}
+
+/// Largest `x` in `0..limit` satisfying a monotonic `ok` (`ok(x)` implies
+/// `ok(y)` for `y < x`).
+fn largest(limit: u32, ok: impl Fn(u32) -> bool) -> Option<u32> {
+    if !ok(0) {
+        return None;
+    }
+    let (mut lo, mut hi) = (0, limit);
+    while hi - lo > 1 {
+        let mid = lo + (hi - lo) / 2;
+        if ok(mid) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+    Some(lo)
+}
+
+fn scale(trace: &mut Trace, k: u32) {
+    trace.start *= k;
+    trace.end *= k;
+    for inner in &mut trace.inner {
+        scale(inner, k);
+    }
+}
+
+/// Prolongs the `resource` sections by `delta`, shifting what follows.
+fn extend(trace: &mut Trace, resource: &str, delta: u32, shift: &mut u32) {
+    trace.start += *shift;
+    for inner in &mut trace.inner {
+        extend(inner, resource, delta, shift);
+    }
+    if trace.id == resource {
+        *shift += delta;
+    }
+    trace.end += *shift;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sets;
+
+    #[test]
+    fn exam() {
+        // t1 completes at its deadline (in the worst case), no margins
+        let set = sets::exam();
+        let s = sensitivity(&set);
+        assert!(s.tasks.iter().all(|t| t.scaling == Some(1.0)));
+        assert!(s.resources.iter().all(|r| r.extension == Some(0)));
+        assert_eq!(
+            s.to_string(),
+            "\
+task         wcet  scaling max wcet
+t1             10     1.00     10.0
+t2             30     1.00     30.0
+t3             30     1.00     30.0
+resource  ceiling  longest      max
+R1              2       10       10
+R2              3       10       10"
+        );
+    }
+
+    #[test]
+    fn margins() {
+        let mut set = sets::exam();
+        set.tasks[0].deadline = 150;
+        set.tasks[0].inter_arrival = 150;
+        let s = sensitivity(&set);
+        // t1 (R 100, D 150) can take another 50 units of its own, but
+        // t3 only 50 / 3 as it preempts t1 three times
+        let scaling: Vec<Option<f64>> = s.tasks.iter().map(|t| t.scaling).collect();
+        assert_eq!(scaling, vec![Some(3.0), Some(1.66), Some(1.22)]);
+        // R2 prolongs t2 and t3, three instances of t3 and one of t2
+        // preempt t1, 130 + 4 * 5 = 150
+        assert_eq!(critical_section_extension(&set, "R2"), Some(5));
+        // R1 is locked twice by t2, 130 + 2 * 10 = 150
+        assert_eq!(critical_section_extension(&set, "R1"), Some(10));
+    }
+
+    #[test]
+    fn not_schedulable() {
+        let mut set = sets::exam();
+        set.tasks[0].trace.end = 20;
+        let s = sensitivity(&set);
+        // t1 needs to be halved
+        assert_eq!(s.tasks[0].scaling, Some(0.5));
+        assert_eq!(s.resources[0].extension, None);
+        assert_eq!(s.resources[0].max_length(), None);
+    }
+
+    #[test]
+    fn extended_trace() {
+        let set = sets::exam();
+        let mut t2 = set.tasks[1].trace.clone();
+        extend(&mut t2, "R1", 2, &mut 0);
+        // R1 10..20 (R2 12..16) and R1 22..28
+        assert_eq!((t2.start, t2.end), (0, 34));
+        assert_eq!((t2.inner[0].start, t2.inner[0].end), (10, 22));
+        assert_eq!(
+            (t2.inner[0].inner[0].start, t2.inner[0].inner[0].end),
+            (12, 16)
+        );
+        assert_eq!((t2.inner[1].start, t2.inner[1].end), (24, 32));
+    }
+}
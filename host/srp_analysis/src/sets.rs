@@ -0,0 +1,33 @@
+//! host/srp_analysis/src/sets.rs
+//!
+//! Task sets shared by the tests.
+
+use taskset::TaskSet;
+
+/// The exam task set, `tasks/timing_exam.toml`.
+pub fn exam() -> TaskSet {
+    TaskSet::from_toml(include_str!("../../../tasks/timing_exam.toml")).unwrap()
+}
+
+/// Two tasks of equal priority released together, one of the two waits
+/// for the other and misses its deadline.
+pub fn equal_priority() -> TaskSet {
+    TaskSet::from_toml(
+        r#"
+        [[tasks]]
+        id = "a"
+        prio = 1
+        deadline = 15
+        inter_arrival = 50
+        trace = { id = "a", start = 0, end = 10 }
+
+        [[tasks]]
+        id = "b"
+        prio = 1
+        deadline = 15
+        inter_arrival = 50
+        trace = { id = "b", start = 0, end = 10 }
+        "#,
+    )
+    .unwrap()
+}
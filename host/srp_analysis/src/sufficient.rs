@@ -0,0 +1,398 @@
+//! host/srp_analysis/src/sufficient.rs
+//!
+//! Quick schedulability tests, with the SRP blocking `B(t)` of
+//! [`crate::blocking`]. Each test explains its verdict per task.
+//!
+//! - Liu & Layland, rate monotonic priorities and `D = A`, task `t` is
+//!   schedulable if `sum(U(h)) + U(t) + B(t) / A(t) <= n (2^(1/n) - 1)`
+//!   over the `n - 1` higher (or equal) priority tasks `h` (sufficient only).
+//! - Hyperbolic (Bini, Buttazzo and Buttazzo), same assumptions,
+//!   `prod(U(h) + 1) * (U(t) + B(t) / A(t) + 1) <= 2` (sufficient only,
+//!   dominates Liu & Layland).
+//! - Processor demand (Lehoczky, Sha and Ding) for any fixed priorities and
+//!   constrained deadlines (`D <= A`), task `t` is schedulable if at some
+//!   scheduling point `s` (a release of a task `h`, or `D(t)`)
+//!   the demand `C(t) + B(t) + sum(ceil(s / A(h)) * C(h)) <= s` (exact but
+//!   for the blocking, as [`crate::analyse`]).
+//!
+//! A failed sufficient test is inconclusive, the task set may still be
+//! schedulable.
+//!
+//! As in the response time analysis, tasks of equal priority (dispatched
+//! in release order) are accounted as higher priority tasks `h`.
+
+use crate::blocking;
+use std::fmt;
+use taskset::{Task, TaskSet};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    /// A sufficient test failed.
+    Inconclusive,
+    Fail,
+    /// The assumptions of the test do not hold.
+    NotApplicable,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Verdict::Pass => "pass",
+            Verdict::Inconclusive => "inconclusive",
+            Verdict::Fail => "FAIL",
+            Verdict::NotApplicable => "not applicable",
+        })
+    }
+}
+
+/// Outcome of a test for a task.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TaskVerdict {
+    pub id: String,
+    pub verdict: Verdict,
+    pub why: String,
+}
+
+/// Outcome of a test for a task set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Test {
+    pub name: &'static str,
+    pub verdict: Verdict,
+    pub why: String,
+    pub tasks: Vec<TaskVerdict>,
+}
+
+impl Test {
+    /// The task set verdict, the worst of the tasks.
+    fn new(name: &'static str, tasks: Vec<TaskVerdict>) -> Test {
+        let fails = |v: Verdict| tasks.iter().filter(|t| t.verdict == v).count();
+        let (verdict, why) = match (fails(Verdict::Fail), fails(Verdict::Inconclusive)) {
+            (0, 0) => (Verdict::Pass, "all tasks pass".to_string()),
+            (0, n) => (
+                Verdict::Inconclusive,
+                format!("{} task(s) not shown schedulable by this test", n),
+            ),
+            (n, _) => (Verdict::Fail, format!("{} task(s) miss their deadline", n)),
+        };
+        Test {
+            name,
+            verdict,
+            why,
+            tasks,
+        }
+    }
+
+    fn not_applicable(name: &'static str, why: String) -> Test {
+        Test {
+            name,
+            verdict: Verdict::NotApplicable,
+            why,
+            tasks: vec![],
+        }
+    }
+}
+
+impl fmt::Display for Test {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}, {}", self.name, self.verdict, self.why)?;
+        for t in &self.tasks {
+            write!(f, "\n  {:<8} {:<12} {}", t.id, t.verdict.to_string(), t.why)?;
+        }
+        Ok(())
+    }
+}
+
+/// All tests.
+pub fn sufficient(set: &TaskSet) -> Vec<Test> {
+    vec![liu_layland(set), hyperbolic(set), processor_demand(set)]
+}
+
+/// Liu & Layland utilisation bound.
+pub fn liu_layland(set: &TaskSet) -> Test {
+    const NAME: &str = "Liu & Layland";
+    if let Err(why) = rate_monotonic(set) {
+        return Test::not_applicable(NAME, why);
+    }
+    let tasks = set
+        .tasks
+        .iter()
+        .map(|t| {
+            let hp = interfering(set, t);
+            let n = hp.len() + 1;
+            let u: f64 = hp.iter().map(|h| u(h)).sum::<f64>() + u(t);
+            let b = blocking(set, t) as f64 / t.inter_arrival as f64;
+            let bound = n as f64 * (2f64.powf(1.0 / n as f64) - 1.0);
+            let ok = u + b <= bound;
+            TaskVerdict {
+                id: t.id.clone(),
+                verdict: if ok {
+                    Verdict::Pass
+                } else {
+                    Verdict::Inconclusive
+                },
+                why: format!(
+                    "U {:.3} + B/A {:.3} = {:.3} {} {:.3} (bound of {} task{})",
+                    u,
+                    b,
+                    u + b,
+                    if ok { "<=" } else { ">" },
+                    bound,
+                    n,
+                    if n == 1 { "" } else { "s" }
+                ),
+            }
+        })
+        .collect();
+    Test::new(NAME, tasks)
+}
+
+/// Hyperbolic bound.
+pub fn hyperbolic(set: &TaskSet) -> Test {
+    const NAME: &str = "hyperbolic";
+    if let Err(why) = rate_monotonic(set) {
+        return Test::not_applicable(NAME, why);
+    }
+    let tasks = set
+        .tasks
+        .iter()
+        .map(|t| {
+            let b = blocking(set, t) as f64 / t.inter_arrival as f64;
+            let product = interfering(set, t)
+                .iter()
+                .map(|h| u(h) + 1.0)
+                .product::<f64>()
+                * (u(t) + b + 1.0);
+            let ok = product <= 2.0;
+            TaskVerdict {
+                id: t.id.clone(),
+                verdict: if ok {
+                    Verdict::Pass
+                } else {
+                    Verdict::Inconclusive
+                },
+                why: format!(
+                    "prod(U + 1) (with B/A {:.3}) = {:.3} {} 2",
+                    b,
+                    product,
+                    if ok { "<=" } else { ">" }
+                ),
+            }
+        })
+        .collect();
+    Test::new(NAME, tasks)
+}
+
+/// Processor demand at the scheduling points.
+pub fn processor_demand(set: &TaskSet) -> Test {
+    let tasks = set
+        .tasks
+        .iter()
+        .map(|t| {
+            if t.deadline > t.inter_arrival {
+                return TaskVerdict {
+                    id: t.id.clone(),
+                    verdict: Verdict::NotApplicable,
+                    why: format!(
+                        "deadline {} beyond inter arrival {}",
+                        t.deadline, t.inter_arrival
+                    ),
+                };
+            }
+            let hp = interfering(set, t);
+            let base = t.wcet() + blocking(set, t);
+            let demand = |s: u32| -> u32 {
+                base + hp
+                    .iter()
+                    .map(|h| s.div_ceil(h.inter_arrival) * h.wcet())
+                    .sum::<u32>()
+            };
+            let mut points: Vec<u32> = hp
+                .iter()
+                .flat_map(|h| (1..=t.deadline / h.inter_arrival).map(move |k| k * h.inter_arrival))
+                .chain(std::iter::once(t.deadline))
+                .filter(|&s| s > 0)
+                .collect();
+            points.sort_unstable();
+            points.dedup();
+            match points.iter().find(|&&s| demand(s) <= s) {
+                Some(&s) => TaskVerdict {
+                    id: t.id.clone(),
+                    verdict: Verdict::Pass,
+                    why: format!(
+                        "demand {} <= {} at t = {} (C {} + B {})",
+                        demand(s),
+                        s,
+                        s,
+                        t.wcet(),
+                        blocking(set, t)
+                    ),
+                },
+                None => {
+                    let (s, excess) = points
+                        .iter()
+                        .map(|&s| (s, demand(s) - s))
+                        .min_by_key(|&(_, excess)| excess)
+                        .unwrap();
+                    TaskVerdict {
+                        id: t.id.clone(),
+                        verdict: Verdict::Fail,
+                        why: format!(
+                            "demand exceeds every scheduling point up to the deadline {}, \
+                             least by {} at t = {}",
+                            t.deadline, excess, s
+                        ),
+                    }
+                }
+            }
+        })
+        .collect();
+    Test::new("processor demand", tasks)
+}
+
+fn u(t: &Task) -> f64 {
+    t.wcet() as f64 / t.inter_arrival as f64
+}
+
+/// The tasks of higher and equal priority than `task`.
+fn interfering<'a>(set: &'a TaskSet, task: &Task) -> Vec<&'a Task> {
+    set.tasks
+        .iter()
+        .filter(|t| t.prio >= task.prio && t.id != task.id)
+        .collect()
+}
+
+/// Rate monotonic priorities with deadlines equal to the inter arrival.
+fn rate_monotonic(set: &TaskSet) -> Result<(), String> {
+    for t in &set.tasks {
+        if t.deadline != t.inter_arrival {
+            return Err(format!(
+                "`{}` has deadline {} != inter arrival {}",
+                t.id, t.deadline, t.inter_arrival
+            ));
+        }
+        if let Some(h) = interfering(set, t)
+            .into_iter()
+            .find(|h| h.inter_arrival > t.inter_arrival)
+        {
+            return Err(format!(
+                "priorities not rate monotonic, `{}` (prio {}, A {}) above `{}` (prio {}, A {})",
+                h.id, h.prio, h.inter_arrival, t.id, t.prio, t.inter_arrival
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sets;
+
+    fn verdicts(test: &Test) -> Vec<Verdict> {
+        test.tasks.iter().map(|t| t.verdict.clone()).collect()
+    }
+
+    /// The exam with rate monotonic priorities (t1 and t2 swapped).
+    fn rate_monotonic_exam() -> TaskSet {
+        let mut set = sets::exam();
+        set.tasks[0].prio = 2;
+        set.tasks[1].prio = 1;
+        set.tasks[1].deadline = 200;
+        set
+    }
+
+    #[test]
+    fn exam() {
+        let set = sets::exam();
+        let tests = sufficient(&set);
+        // t2 has a longer period but a higher priority than t1
+        assert_eq!(tests[0].verdict, Verdict::NotApplicable);
+        assert_eq!(tests[1].verdict, Verdict::NotApplicable);
+        assert_eq!(
+            tests[0].to_string(),
+            "Liu & Layland: not applicable, priorities not rate monotonic, \
+             `t2` (prio 2, A 200) above `t1` (prio 1, A 100)"
+        );
+        assert_eq!(tests[2].verdict, Verdict::Pass);
+        assert_eq!(
+            tests[2].to_string(),
+            "\
+processor demand: pass, all tasks pass
+  t1       pass         demand 100 <= 100 at t = 100 (C 10 + B 0)
+  t2       pass         demand 90 <= 100 at t = 100 (C 30 + B 0)
+  t3       pass         demand 34 <= 50 at t = 50 (C 30 + B 4)"
+        );
+    }
+
+    #[test]
+    fn rate_monotonic() {
+        let set = rate_monotonic_exam();
+        let ll = liu_layland(&set);
+        // U 0.85 > 0.780 for three tasks
+        assert_eq!(
+            verdicts(&ll),
+            vec![Verdict::Pass, Verdict::Inconclusive, Verdict::Pass]
+        );
+        assert_eq!(ll.verdict, Verdict::Inconclusive);
+        assert_eq!(
+            ll.tasks[1].why,
+            "U 0.850 + B/A 0.000 = 0.850 > 0.780 (bound of 3 tasks)"
+        );
+        assert_eq!(
+            ll.tasks[2].why,
+            "U 0.600 + B/A 0.080 = 0.680 <= 1.000 (bound of 1 task)"
+        );
+        // (1.6 * 1.1 * 1.15) = 2.024
+        let h = hyperbolic(&set);
+        assert_eq!(h.verdict, Verdict::Inconclusive);
+        assert_eq!(h.tasks[1].why, "prod(U + 1) (with B/A 0.000) = 2.024 > 2");
+        // yet schedulable
+        assert_eq!(processor_demand(&set).verdict, Verdict::Pass);
+        assert!(crate::analyse(&set).schedulable());
+    }
+
+    #[test]
+    fn hyperbolic_dominates() {
+        // U 0.8 > 0.780 but (1.5 * 1.2 * 1.1) = 1.98 <= 2
+        let mut set = rate_monotonic_exam();
+        set.tasks[0].trace.end = 20;
+        set.tasks[1].trace.inner.clear();
+        set.tasks[1].trace.end = 20;
+        set.tasks[2].trace.inner.clear();
+        set.tasks[2].trace.end = 25;
+        assert_eq!(liu_layland(&set).verdict, Verdict::Inconclusive);
+        assert_eq!(hyperbolic(&set).verdict, Verdict::Pass);
+    }
+
+    #[test]
+    fn demand_fails() {
+        let mut set = sets::exam();
+        set.tasks[0].trace.end = 20;
+        let pd = processor_demand(&set);
+        assert_eq!(pd.verdict, Verdict::Fail);
+        assert_eq!(
+            pd.tasks[0].why,
+            "demand exceeds every scheduling point up to the deadline 100, least by 10 at t = 100"
+        );
+        assert_eq!(pd.why, "1 task(s) miss their deadline");
+
+        set.tasks[2].deadline = 60;
+        assert_eq!(
+            processor_demand(&set).tasks[2].verdict,
+            Verdict::NotApplicable
+        );
+    }
+
+    #[test]
+    fn equal_priority() {
+        // released together, one of the two waits for the other
+        let set = sets::equal_priority();
+        let pd = processor_demand(&set);
+        assert_eq!(verdicts(&pd), vec![Verdict::Fail, Verdict::Fail]);
+        assert_eq!(
+            pd.tasks[0].why,
+            "demand exceeds every scheduling point up to the deadline 15, least by 5 at t = 15"
+        );
+    }
+}
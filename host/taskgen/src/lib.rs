@@ -0,0 +1,362 @@
+//! host/taskgen/src/lib.rs
+//!
+//! Random task sets, for the analysis, the simulator (`rtic_sim`) and the
+//! firmware (`TASKSET=... cargo run --example taskset`).
+//!
+//! - Utilisations by UUniFast (Bini and Buttazzo), uniformly distributed
+//!   over the tasks for a given total.
+//! - Inter arrival times log-uniform in a range, multiples of a
+//!   granularity (keeping the hyperperiod in bounds).
+//! - Deadlines uniform in `[max(C, d * T), T]`, constrained for `d < 1`.
+//! - Deadline monotonic priorities, on at most `levels` levels.
+//! - Critical sections: each task uses each resource with probability
+//!   `sharing`, sections at most `critical * C` long, nested at most
+//!   `nesting` deep.
+//!
+//! The generator is a SplitMix64, so a seed gives the same task set on any
+//! host and version.
+
+use std::fmt;
+use taskset::{Task, TaskSet, Trace};
+
+/// Generator parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub tasks: usize,
+    /// Total utilisation.
+    pub utilisation: f64,
+    /// Range of inter arrival times (in units).
+    pub periods: (u32, u32),
+    /// Inter arrival times (and offsets) are multiples of `granularity`.
+    pub granularity: u32,
+    /// Smallest deadline, relative to the inter arrival time.
+    pub deadlines: f64,
+    pub resources: usize,
+    /// Probability of a task using a resource.
+    pub sharing: f64,
+    /// Deepest nesting of critical sections.
+    pub nesting: usize,
+    /// Longest critical section, relative to the WCET of the task.
+    pub critical: f64,
+    /// Priority levels (the firmware has a dispatcher per level).
+    pub levels: u8,
+    /// Random release offsets (else 0).
+    pub offsets: bool,
+    /// Clock cycles per time unit.
+    pub unit: u32,
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            tasks: 5,
+            utilisation: 0.7,
+            periods: (10, 1_000),
+            granularity: 10,
+            deadlines: 1.0,
+            resources: 2,
+            sharing: 0.5,
+            nesting: 2,
+            critical: 0.3,
+            levels: taskset::codegen::DISPATCHERS.len() as u8,
+            offsets: false,
+            unit: 1_000,
+            seed: 0,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidConfig(pub String);
+
+impl fmt::Display for InvalidConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid generator configuration: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidConfig {}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), InvalidConfig> {
+        let invalid = |what: &str| Err(InvalidConfig(what.into()));
+        let unit = |x: f64| (0.0..=1.0).contains(&x);
+        if self.tasks == 0 {
+            invalid("no tasks")
+        } else if self.utilisation.is_nan() || self.utilisation <= 0.0 {
+            invalid("utilisation must be positive")
+        } else if self.granularity == 0 || self.periods.0 < self.granularity {
+            invalid("periods must be at least the (non-zero) granularity")
+        } else if self.periods.0 > self.periods.1 {
+            invalid("empty period range")
+        } else if !unit(self.deadlines) || !unit(self.sharing) || !unit(self.critical) {
+            invalid("deadlines, sharing and critical are ratios in 0..=1")
+        } else if self.levels == 0 {
+            invalid("no priority levels")
+        } else if self.unit == 0 {
+            invalid("`unit` must be non-zero")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// SplitMix64 (Steele, Lea and Flood).
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `lo..=hi`.
+    pub fn range(&mut self, lo: u32, hi: u32) -> u32 {
+        lo + (self.next_u64() % (hi as u64 - lo as u64 + 1)) as u32
+    }
+}
+
+/// UUniFast, `n` utilisations summing to `total`.
+pub fn uunifast(rng: &mut Rng, n: usize, total: f64) -> Vec<f64> {
+    let mut sum = total;
+    let mut u = Vec::with_capacity(n);
+    for i in 1..n {
+        let next = sum * rng.f64().powf(1.0 / (n - i) as f64);
+        u.push(sum - next);
+        sum = next;
+    }
+    u.push(sum);
+    u
+}
+
+/// A random task set.
+pub fn generate(config: &Config) -> Result<TaskSet, InvalidConfig> {
+    config.validate()?;
+    let c = config;
+    let mut rng = Rng::new(c.seed);
+    let resources: Vec<String> = (1..=c.resources).map(|r| format!("R{}", r)).collect();
+
+    let mut tasks: Vec<Task> = uunifast(&mut rng, c.tasks, c.utilisation)
+        .into_iter()
+        .map(|u| {
+            let (lo, hi) = (c.periods.0 as f64, c.periods.1 as f64);
+            let period = (lo.ln() + rng.f64() * (hi.ln() - lo.ln())).exp();
+            let inter_arrival =
+                ((period / c.granularity as f64).round() as u32).max(1) * c.granularity;
+            let wcet = ((u * inter_arrival as f64).round() as u32).max(1);
+            let shortest = ((c.deadlines * inter_arrival as f64).round() as u32).max(wcet);
+            let deadline = rng.range(shortest.min(inter_arrival), inter_arrival);
+            let offset = if c.offsets {
+                rng.range(0, inter_arrival / c.granularity - 1) * c.granularity
+            } else {
+                0
+            };
+
+            let mut used: Vec<String> = resources
+                .iter()
+                .filter(|_| rng.f64() < c.sharing)
+                .cloned()
+                .collect();
+            shuffle(&mut rng, &mut used);
+            let longest = ((c.critical * wcet as f64) as u32).max(1);
+            let inner = sections(&mut rng, 0, wcet, &mut used, c.nesting, longest);
+            Task {
+                id: String::new(),
+                prio: 0,
+                deadline,
+                inter_arrival,
+                offset,
+                trace: Trace {
+                    id: String::new(),
+                    start: 0,
+                    end: wcet,
+                    inner,
+                },
+            }
+        })
+        .collect();
+
+    // deadline monotonic, `t1` the lowest priority
+    tasks.sort_by_key(|t| std::cmp::Reverse(t.deadline));
+    let n = tasks.len();
+    let levels = n.min(c.levels as usize);
+    for (rank, t) in tasks.iter_mut().enumerate() {
+        t.id = format!("t{}", rank + 1);
+        t.trace.id = t.id.clone();
+        t.prio = 1 + (rank * levels / n) as u8;
+    }
+    let set = TaskSet {
+        unit: c.unit,
+        tasks,
+    };
+    debug_assert!(set.validate().is_ok());
+    Ok(set)
+}
+
+/// Critical sections in `start..end`, of the `resources` (consumed).
+fn sections(
+    rng: &mut Rng,
+    start: u32,
+    end: u32,
+    resources: &mut Vec<String>,
+    depth: usize,
+    longest: u32,
+) -> Vec<Trace> {
+    let mut out = vec![];
+    let mut at = start;
+    while depth > 0 && at < end && !resources.is_empty() {
+        let len = rng.range(1, longest.min(end - at));
+        // leave room for the remaining sections
+        let slack = (end - at - len) / resources.len() as u32;
+        let s = at + rng.range(0, slack);
+        let id = resources.pop().unwrap();
+        let inner = if len > 1 && rng.f64() < 0.5 {
+            sections(rng, s, s + len, resources, depth - 1, len - 1)
+        } else {
+            vec![]
+        };
+        out.push(Trace {
+            id,
+            start: s,
+            end: s + len,
+            inner,
+        });
+        at = s + len;
+    }
+    out
+}
+
+fn shuffle<T>(rng: &mut Rng, v: &mut [T]) {
+    for i in (1..v.len()).rev() {
+        v.swap(i, rng.range(0, i as u32) as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(trace: &Trace) -> usize {
+        trace.inner.iter().map(|i| 1 + depth(i)).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn reproducible() {
+        let config = Config {
+            seed: 42,
+            ..Config::default()
+        };
+        let set = generate(&config).unwrap();
+        assert_eq!(generate(&config).unwrap(), set);
+        assert_ne!(generate(&Config { seed: 43, ..config }).unwrap(), set);
+        // SplitMix64 reference output for seed 0
+        assert_eq!(Rng::new(0).next_u64(), 0xe220_a839_7b1d_cdaf);
+    }
+
+    #[test]
+    fn uunifast_sums() {
+        let mut rng = Rng::new(1);
+        for n in 1..10 {
+            let u = uunifast(&mut rng, n, 0.8);
+            assert_eq!(u.len(), n);
+            assert!(u.iter().all(|&u| u >= 0.0));
+            assert!((u.iter().sum::<f64>() - 0.8).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn properties() {
+        for seed in 0..200 {
+            let config = Config {
+                tasks: 8,
+                resources: 3,
+                sharing: 0.7,
+                deadlines: 0.5,
+                offsets: true,
+                seed,
+                ..Config::default()
+            };
+            let set = generate(&config).unwrap();
+            set.validate().unwrap();
+            // rounding of each WCET to units
+            let u = srp_analysis::utilisation(&set);
+            assert!((u - 0.7).abs() < 8.0 * 0.5 / 10.0, "{}", u);
+            for t in &set.tasks {
+                assert!(t.inter_arrival % 10 == 0 && (10..=1_000).contains(&t.inter_arrival));
+                assert!(t.wcet() <= t.deadline && t.deadline <= t.inter_arrival);
+                assert!(2 * t.deadline >= t.inter_arrival || t.deadline == t.wcet());
+                assert!(t.offset < t.inter_arrival);
+                assert!(depth(&t.trace) <= 2);
+                assert!((1..=5).contains(&t.prio));
+            }
+            // deadline monotonic
+            for w in set.tasks.windows(2) {
+                assert!(w[0].deadline >= w[1].deadline && w[0].prio <= w[1].prio);
+            }
+            // for the firmware
+            taskset::codegen::rtic_app(&set, "generated").unwrap();
+        }
+    }
+
+    #[test]
+    fn analysis_and_simulation() {
+        // few levels, so that tasks share priorities
+        for seed in 0..100 {
+            let set = generate(&Config {
+                tasks: 5,
+                utilisation: 0.6,
+                periods: (50, 200),
+                granularity: 50,
+                levels: 2,
+                offsets: seed % 2 == 0,
+                seed,
+                ..Config::default()
+            })
+            .unwrap();
+            let analysis = srp_analysis::analyse(&set);
+            let sim = rtic_sim::simulate(&set, &rtic_sim::Config::default()).unwrap();
+            // the simulated responses within the analysed bounds (the
+            // iteration stops at a deadline miss, without a bound)
+            for t in analysis.tasks.iter().filter(|t| t.schedulable()) {
+                let simulated = sim.max_response(&t.id).unwrap();
+                let bound = t.response as u64 * set.unit as u64;
+                assert!(
+                    simulated <= bound,
+                    "seed {}: `{}` simulated {} > {}",
+                    seed,
+                    t.id,
+                    simulated,
+                    bound
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let config = Config {
+            periods: (100, 10),
+            ..Config::default()
+        };
+        assert!(generate(&config).is_err());
+        assert!(generate(&Config {
+            tasks: 0,
+            ..Config::default()
+        })
+        .is_err());
+    }
+}
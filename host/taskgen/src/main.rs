@@ -0,0 +1,87 @@
+//! host/taskgen/src/main.rs
+//!
+//! > cargo run -p taskgen -- [options]
+//!
+//! Prints a random task set (TOML), options (defaults of `Config`):
+//!
+//! - `--tasks <n>`, `--utilisation <u>`, `--periods <min>,<max>`,
+//!   `--granularity <g>`, `--deadlines <d>` (smallest D/T),
+//!   `--resources <r>`, `--sharing <p>`, `--nesting <depth>`,
+//!   `--critical <c>` (longest section relative to C), `--levels <l>`,
+//!   `--offsets`, `--unit <cycles>`, `--seed <s>`.
+//! - `--count <k> --out <dir>`, writes `k` task sets (seeds `s..s + k`)
+//!   to `<dir>/taskset_<seed>.toml`.
+
+use std::{env, fs, path::Path, process, str::FromStr};
+use taskgen::{generate, Config};
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
+
+fn value<T: FromStr>(arg: &str, v: Option<String>) -> T {
+    v.and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| fail(&format!("{}: expected a value", arg)))
+}
+
+fn main() {
+    let mut c = Config::default();
+    let mut count = None;
+    let mut out = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--offsets" {
+            c.offsets = true;
+            continue;
+        }
+        let next = args.next();
+        match arg.as_str() {
+            "--tasks" => c.tasks = value(&arg, next),
+            "--utilisation" => c.utilisation = value(&arg, next),
+            "--periods" => {
+                let range: String = value(&arg, next);
+                c.periods = match range.split_once(',') {
+                    Some((lo, hi)) => (value(&arg, Some(lo.into())), value(&arg, Some(hi.into()))),
+                    None => fail("--periods <min>,<max>"),
+                }
+            }
+            "--granularity" => c.granularity = value(&arg, next),
+            "--deadlines" => c.deadlines = value(&arg, next),
+            "--resources" => c.resources = value(&arg, next),
+            "--sharing" => c.sharing = value(&arg, next),
+            "--nesting" => c.nesting = value(&arg, next),
+            "--critical" => c.critical = value(&arg, next),
+            "--levels" => c.levels = value(&arg, next),
+            "--unit" => c.unit = value(&arg, next),
+            "--seed" => c.seed = value(&arg, next),
+            "--count" => count = Some(value::<u64>(&arg, next)),
+            "--out" => out = Some(value::<String>(&arg, next)),
+            _ => fail(&format!("unknown option `{}`", arg)),
+        }
+    }
+
+    let toml = |c: &Config| {
+        let set = generate(c).unwrap_or_else(|e| fail(&e.to_string()));
+        format!(
+            "# generated by `taskgen`, seed {}, utilisation {}\n\n{}",
+            c.seed,
+            c.utilisation,
+            set.to_toml()
+        )
+    };
+    match (count, out) {
+        (None, None) => print!("{}", toml(&c)),
+        (Some(count), Some(dir)) => {
+            fs::create_dir_all(&dir).unwrap_or_else(|e| fail(&format!("{}: {}", dir, e)));
+            let first = c.seed;
+            for seed in first..first + count {
+                c.seed = seed;
+                let path = Path::new(&dir).join(format!("taskset_{}.toml", seed));
+                fs::write(&path, toml(&c))
+                    .unwrap_or_else(|e| fail(&format!("{}: {}", path.display(), e)));
+            }
+        }
+        _ => fail("--count and --out go together"),
+    }
+}
@@ -0,0 +1,366 @@
+//! host/taskset/src/codegen.rs
+//!
+//! Generates an RTIC application emulating a task set, for the
+//! STM32F411 and the `timing` crate (see `examples/taskset.rs`).
+//!
+//! Each task is scheduled periodically (first release at its offset),
+//! its trace is emulated by `burn_cycles` workload, and critical
+//! sections by (nested) instrumented locks. Every task gets response
+//! time, deadline, jitter and execution monitors, every resource a lock
+//! monitor, named after the task (resource) in upper case.
+
+use crate::{Error, Task, TaskSet, Trace};
+use std::fmt::Write;
+
+/// Free interrupts used as dispatchers, one per priority level.
+pub const DISPATCHERS: [&str; 5] = ["EXTI0", "EXTI1", "EXTI2", "EXTI3", "EXTI4"];
+
+/// The RTIC application for `set`, described by `source`.
+pub fn rtic_app(set: &TaskSet, source: &str) -> Result<String, Error> {
+    set.validate()?;
+    let mut priorities: Vec<u8> = set.tasks.iter().map(|t| t.prio).collect();
+    priorities.sort_unstable();
+    priorities.dedup();
+    if priorities.len() > DISPATCHERS.len() {
+        return Err(Error::Invalid(format!(
+            "{} priority levels, at most {} dispatchers",
+            priorities.len(),
+            DISPATCHERS.len()
+        )));
+    }
+    let g = Gen { set };
+    let mut s = String::new();
+    g.header(&mut s, source);
+    g.monitors(&mut s);
+    g.app(&mut s, priorities.len());
+    Ok(s)
+}
+
+struct Gen<'a> {
+    set: &'a TaskSet,
+}
+
+impl Gen<'_> {
+    fn cycles(&self, units: u32) -> String {
+        literal(units as u64 * self.set.unit as u64)
+    }
+
+    fn header(&self, s: &mut String, source: &str) {
+        writeln!(
+            s,
+            "// Generated by `taskset::codegen` from `{}`, do not edit.",
+            source
+        )
+        .unwrap();
+        writeln!(s, "// (1 time unit = {} cycles)", self.set.unit).unwrap();
+        s.push_str(
+            "
+use rtic::cyccnt::U32Ext;
+use stm32f4::stm32f411;
+#[allow(unused_imports)]
+use timing::{
+    burn_cycles, Accounting, Cyccnt, DeadlineMonitor, ExecutionMonitor, InstrumentedLock,
+    JitterMonitor, LockMonitor, Policy, RawLatency, ResponseTimeMonitor, BURN, CALIBRATION,
+};
+",
+        );
+    }
+
+    fn monitors(&self, s: &mut String) {
+        for t in &self.set.tasks {
+            let name = t.id.to_uppercase();
+            writeln!(
+                s,
+                "
+// {}: priority {}, deadline {}, inter-arrival {}, offset {}, wcet {}
+#[no_mangle]
+static {n}_RT: ResponseTimeMonitor = ResponseTimeMonitor::new({id:?});
+#[no_mangle]
+static {n}_DL: DeadlineMonitor = DeadlineMonitor::new({id:?}, {}, Policy::Breakpoint);
+#[no_mangle]
+static {n}_JIT: JitterMonitor = JitterMonitor::new({id:?}, {});
+#[no_mangle]
+static {n}_EXEC: ExecutionMonitor = ExecutionMonitor::new({id:?});",
+                t.id,
+                t.prio,
+                t.deadline,
+                t.inter_arrival,
+                t.offset,
+                t.wcet(),
+                self.cycles(t.deadline),
+                literal(self.set.unit as u64),
+                n = name,
+                id = t.id,
+            )
+            .unwrap();
+        }
+        s.push_str(
+            "
+#[no_mangle]
+static ACCOUNTING: Accounting = Accounting::new();
+",
+        );
+        for (r, ceiling) in self.set.ceilings() {
+            writeln!(
+                s,
+                "#[no_mangle]
+static {}_LOCK: LockMonitor = LockMonitor::new({:?}, {}, &ACCOUNTING);",
+                r.to_uppercase(),
+                r,
+                ceiling
+            )
+            .unwrap();
+        }
+    }
+
+    fn app(&self, s: &mut String, levels: usize) {
+        s.push_str(
+            "
+#[rtic::app(device = stm32f411, monotonic = rtic::cyccnt::CYCCNT)]
+const APP: () = {
+",
+        );
+        let ceilings = self.set.ceilings();
+        if !ceilings.is_empty() {
+            s.push_str("    struct Resources {\n");
+            for r in ceilings.keys() {
+                writeln!(s, "        #[init(0)]\n        {}: u64,", r).unwrap();
+            }
+            s.push_str("    }\n\n");
+        }
+
+        writeln!(
+            s,
+            "    #[init(schedule = [{}])]
+    fn init(mut cx: init::Context) {{
+        // Initialize (enable) the monotonic timer (CYCCNT)
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+
+        CALIBRATION.measure_read(&Cyccnt);
+        BURN.calibrate(&Cyccnt);
+",
+            self.set
+                .tasks
+                .iter()
+                .map(|t| t.id.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+        .unwrap();
+        for t in &self.set.tasks {
+            writeln!(
+                s,
+                "        cx.schedule.{}(cx.start + {}.cycles()).unwrap();",
+                t.id,
+                self.cycles(t.offset)
+            )
+            .unwrap();
+        }
+        s.push_str("    }\n");
+
+        for t in &self.set.tasks {
+            self.task(s, t);
+        }
+
+        s.push_str("\n    extern \"C\" {\n");
+        for d in &DISPATCHERS[..levels] {
+            writeln!(s, "        fn {}();", d).unwrap();
+        }
+        s.push_str("    }\n};\n");
+    }
+
+    fn task(&self, s: &mut String, t: &Task) {
+        let n = t.id.to_uppercase();
+        let resources = t.resources();
+        let ceilings = self.set.ceilings();
+        writeln!(
+            s,
+            "
+    #[inline(never)]
+    #[task(schedule = [{id}], {}priority = {prio})]
+    fn {id}(cx: {id}::Context) {{
+        let start = cx.scheduled;
+        // raw, the monitors subtract the overheads
+        let raw = start.elapsed().as_cycles();
+        {n}_JIT.record(raw);
+        ACCOUNTING.enter(&Cyccnt, {prio}, RawLatency(raw));
+        cx.schedule.{id}(cx.scheduled + {}.cycles()).unwrap();
+",
+            if resources.is_empty() {
+                String::new()
+            } else {
+                format!("resources = [{}], ", resources.join(", "))
+            },
+            self.cycles(t.inter_arrival),
+            id = t.id,
+            prio = t.prio,
+            n = n,
+        )
+        .unwrap();
+        for r in &resources {
+            // at the ceiling the resource is handed out as `&mut`
+            if ceilings[*r] == t.prio {
+                writeln!(
+                    s,
+                    "        let mut {} = rtic::Exclusive(cx.resources.{});",
+                    r.to_lowercase(),
+                    r
+                )
+                .unwrap();
+            } else {
+                writeln!(
+                    s,
+                    "        let mut {} = cx.resources.{};",
+                    r.to_lowercase(),
+                    r
+                )
+                .unwrap();
+            }
+        }
+        self.segment(s, &t.trace, 2);
+        writeln!(
+            s,
+            "
+        {n}_EXEC.record(&ACCOUNTING.exit(&Cyccnt));
+        {n}_DL.complete(&{n}_RT, start.elapsed().as_cycles());
+    }}",
+            n = n
+        )
+        .unwrap();
+    }
+
+    /// Workload of `trace` (excluding the lock of the segment itself).
+    fn segment(&self, s: &mut String, trace: &Trace, depth: usize) {
+        let indent = "    ".repeat(depth);
+        let mut at = trace.start;
+        for inner in &trace.inner {
+            self.burn(s, &indent, inner.start - at);
+            writeln!(
+                s,
+                "{}{}.lock_instrumented(&{}_LOCK, |_| {{",
+                indent,
+                inner.id.to_lowercase(),
+                inner.id.to_uppercase()
+            )
+            .unwrap();
+            self.segment(s, inner, depth + 1);
+            writeln!(s, "{}}});", indent).unwrap();
+            at = inner.end;
+        }
+        self.burn(s, &indent, trace.end - at);
+    }
+
+    fn burn(&self, s: &mut String, indent: &str, units: u32) {
+        if units > 0 {
+            writeln!(s, "{}burn_cycles({});", indent, self.cycles(units)).unwrap();
+        }
+    }
+}
+
+/// Integer literal with `_` separated thousands.
+fn literal(n: u64) -> String {
+    let digits = n.to_string();
+    let mut s = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            s.push('_');
+        }
+        s.push(c);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAM: &str = include_str!("../../../tasks/timing_exam.toml");
+
+    #[test]
+    fn literals() {
+        assert_eq!(literal(0), "0");
+        assert_eq!(literal(999), "999");
+        assert_eq!(literal(1_000), "1_000");
+        assert_eq!(literal(10_000_000), "10_000_000");
+    }
+
+    #[test]
+    fn exam() {
+        let set = TaskSet::from_toml(EXAM).unwrap();
+        let app = rtic_app(&set, "tasks/timing_exam.toml").unwrap();
+        assert!(app.starts_with(
+            "// Generated by `taskset::codegen` from `tasks/timing_exam.toml`, do not edit."
+        ));
+        for expected in &[
+            "static T2_DL: DeadlineMonitor = DeadlineMonitor::new(\"t2\", 200_000, Policy::Breakpoint);",
+            "static R1_LOCK: LockMonitor = LockMonitor::new(\"R1\", 2, &ACCOUNTING);",
+            "static R2_LOCK: LockMonitor = LockMonitor::new(\"R2\", 3, &ACCOUNTING);",
+            "    #[init(schedule = [t1, t2, t3])]",
+            "        cx.schedule.t3(cx.start + 50_000.cycles()).unwrap();",
+            "    #[task(schedule = [t1], priority = 1)]",
+            "    #[task(schedule = [t2], resources = [R1, R2], priority = 2)]",
+            "        cx.schedule.t2(cx.scheduled + 200_000.cycles()).unwrap();",
+            "        fn EXTI2();\n    }\n};\n",
+        ] {
+            assert!(app.contains(expected), "missing {}", expected);
+        }
+        assert!(!app.contains("EXTI3"));
+
+        // t2, R1 at its ceiling, R2 shared with t3
+        let t2 = &app[app.find("    fn t2(").unwrap()..app.find("    fn t3(").unwrap()];
+        assert!(t2.contains(
+            "        let mut r1 = rtic::Exclusive(cx.resources.R1);
+        let mut r2 = cx.resources.R2;
+        burn_cycles(10_000);
+        r1.lock_instrumented(&R1_LOCK, |_| {
+            burn_cycles(2_000);
+            r2.lock_instrumented(&R2_LOCK, |_| {
+                burn_cycles(4_000);
+            });
+            burn_cycles(4_000);
+        });
+        burn_cycles(2_000);
+        r1.lock_instrumented(&R1_LOCK, |_| {
+            burn_cycles(6_000);
+        });
+        burn_cycles(2_000);
+
+        T2_EXEC.record(&ACCOUNTING.exit(&Cyccnt));
+        T2_DL.complete(&T2_RT, start.elapsed().as_cycles());
+    }"
+        ));
+        assert!(app.contains("        let mut r2 = rtic::Exclusive(cx.resources.R2);\n"));
+    }
+
+    #[test]
+    fn raw_latency() {
+        // the accounting takes the raw latency, not the compensated
+        // jitter returned by `record` (the dispatch subtracted twice)
+        let set = TaskSet::from_toml(EXAM).unwrap();
+        let app = rtic_app(&set, "tasks/timing_exam.toml").unwrap();
+        assert!(app.contains(
+            "        let raw = start.elapsed().as_cycles();
+        T1_JIT.record(raw);
+        ACCOUNTING.enter(&Cyccnt, 1, RawLatency(raw));
+"
+        ));
+        assert!(!app.contains("= T1_JIT.record("));
+    }
+
+    #[test]
+    fn too_many_priorities() {
+        let mut set = TaskSet::from_toml(EXAM).unwrap();
+        for prio in 4..=6 {
+            let mut t = set.tasks[0].clone();
+            t.id = format!("t{}", prio);
+            t.trace.id = t.id.clone();
+            t.prio = prio;
+            set.tasks.push(t);
+        }
+        assert_eq!(
+            rtic_app(&set, "").unwrap_err().to_string(),
+            "invalid task set: 6 priority levels, at most 5 dispatchers"
+        );
+    }
+}
@@ -0,0 +1,350 @@
+//! host/taskset/src/lib.rs
+//!
+//! Task set description, shared by the firmware code generator and the
+//! host side analysis.
+//!
+//! The model follows `klee_tutorial/srp_analysis`: each task has a trace,
+//! a (nested) list of resource segments, with times in units of
+//! `unit` clock cycles. A task set is described in TOML:
+//!
+//! ```toml
+//! unit = 1_000
+//!
+//! [[tasks]]
+//! id = "t3"
+//! prio = 3
+//! deadline = 50
+//! inter_arrival = 50
+//! offset = 50
+//! trace = { id = "t3", start = 0, end = 30, inner = [
+//!     { id = "R2", start = 10, end = 20 },
+//! ] }
+//! ```
+//!
+//! See `tasks/timing_exam.toml`.
+
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fs, io, path::Path};
+
+pub mod codegen;
+pub mod overheads;
+
+pub use overheads::Overheads;
+
+/// A task set.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TaskSet {
+    /// Clock cycles per time unit.
+    #[serde(default = "unit")]
+    pub unit: u32,
+    pub tasks: Tasks,
+}
+
+fn unit() -> u32 {
+    1
+}
+
+pub type Tasks = Vec<Task>;
+
+/// A periodic (sporadic) task.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Task {
+    pub id: String,
+    pub prio: u8,
+    pub deadline: u32,
+    pub inter_arrival: u32,
+    /// First release, relative to the start of the system.
+    #[serde(default)]
+    pub offset: u32,
+    pub trace: Trace,
+}
+
+/// A segment of execution, the task itself or a critical section
+/// of the resource `id`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace {
+    pub id: String,
+    pub start: u32,
+    pub end: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inner: Vec<Trace>,
+}
+
+/// Resource ceilings, the highest priority of any task using the resource.
+pub type Ceilings = BTreeMap<String, u8>;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Invalid(e) => write!(f, "invalid task set: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(e: toml::de::Error) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl TaskSet {
+    /// Parses and validates a TOML description.
+    pub fn from_toml(s: &str) -> Result<TaskSet, Error> {
+        let set: TaskSet = toml::from_str(s)?;
+        set.validate()?;
+        Ok(set)
+    }
+
+    /// The TOML description.
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("task set to TOML")
+    }
+
+    /// Reads a TOML description from `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<TaskSet, Error> {
+        TaskSet::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Checks that task ids are unique, priorities non-zero, and
+    /// that each trace is well nested (inner segments ordered and
+    /// within their enclosing segment).
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.unit == 0 {
+            return Err(Error::Invalid("`unit` must be non-zero".into()));
+        }
+        for (i, task) in self.tasks.iter().enumerate() {
+            if self.tasks[..i].iter().any(|t| t.id == task.id) {
+                return Err(Error::Invalid(format!("duplicate task `{}`", task.id)));
+            }
+            if task.prio == 0 {
+                return Err(Error::Invalid(format!("`{}`: priority 0 is idle", task.id)));
+            }
+            if task.inter_arrival == 0 {
+                return Err(Error::Invalid(format!(
+                    "`{}`: inter arrival must be non-zero",
+                    task.id
+                )));
+            }
+            if self.tasks.iter().any(|t| t.id == task.trace.id) && task.trace.id != task.id {
+                return Err(Error::Invalid(format!(
+                    "`{}`: trace of another task `{}`",
+                    task.id, task.trace.id
+                )));
+            }
+            task.trace.validate(&task.id, self, &mut vec![])?;
+        }
+        Ok(())
+    }
+
+    /// Ceilings of all resources.
+    pub fn ceilings(&self) -> Ceilings {
+        let mut ceilings = Ceilings::new();
+        for task in &self.tasks {
+            for r in task.resources() {
+                let ceiling = ceilings.entry(r.to_string()).or_insert(task.prio);
+                *ceiling = (*ceiling).max(task.prio);
+            }
+        }
+        ceilings
+    }
+
+    /// Task by id.
+    pub fn task(&self, id: &str) -> Option<&Task> {
+        self.tasks.iter().find(|t| t.id == id)
+    }
+}
+
+impl Task {
+    /// Worst case execution time.
+    pub fn wcet(&self) -> u32 {
+        self.trace.len()
+    }
+
+    /// Resources used by the task, in order of first use.
+    pub fn resources(&self) -> Vec<&str> {
+        let mut resources = vec![];
+        self.trace.visit(&mut |t| {
+            if t.id != self.id && !resources.contains(&t.id.as_str()) {
+                resources.push(t.id.as_str());
+            }
+        });
+        resources
+    }
+}
+
+impl Trace {
+    /// Length of the segment.
+    pub fn len(&self) -> u32 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Visits the segment and its inner segments (pre-order).
+    pub fn visit<'a>(&'a self, f: &mut impl FnMut(&'a Trace)) {
+        f(self);
+        for inner in &self.inner {
+            inner.visit(f);
+        }
+    }
+
+    /// `enclosing`, the ids of the segments this one is nested within.
+    fn validate<'a>(
+        &'a self,
+        task: &str,
+        set: &TaskSet,
+        enclosing: &mut Vec<&'a str>,
+    ) -> Result<(), Error> {
+        let invalid = |what: &str| {
+            Err(Error::Invalid(format!(
+                "`{}`: segment `{}` {}..{} {}",
+                task, self.id, self.start, self.end, what
+            )))
+        };
+        if self.start > self.end {
+            return invalid("ends before it starts");
+        }
+        if enclosing.contains(&self.id.as_str()) {
+            return invalid("nested within itself");
+        }
+        enclosing.push(&self.id);
+        let mut at = self.start;
+        for inner in &self.inner {
+            if set.task(&inner.id).is_some() {
+                return invalid(&format!("contains task `{}`", inner.id));
+            }
+            if inner.start < at || inner.end > self.end {
+                return invalid(&format!(
+                    "has `{}` {}..{} out of order or bounds",
+                    inner.id, inner.start, inner.end
+                ));
+            }
+            inner.validate(task, set, enclosing)?;
+            at = inner.end;
+        }
+        enclosing.pop();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAM: &str = include_str!("../../../tasks/timing_exam.toml");
+
+    fn trace(id: &str, start: u32, end: u32, inner: Vec<Trace>) -> Trace {
+        Trace {
+            id: id.into(),
+            start,
+            end,
+            inner,
+        }
+    }
+
+    #[test]
+    fn exam() {
+        let set = TaskSet::from_toml(EXAM).unwrap();
+        assert_eq!(set.unit, 1_000);
+        let t2 = set.task("t2").unwrap();
+        assert_eq!(
+            t2.trace,
+            trace(
+                "t2",
+                0,
+                30,
+                vec![
+                    trace("R1", 10, 20, vec![trace("R2", 12, 16, vec![])]),
+                    trace("R1", 22, 28, vec![]),
+                ]
+            )
+        );
+        assert_eq!(
+            set.tasks
+                .iter()
+                .map(|t| (t.prio, t.deadline, t.inter_arrival, t.offset, t.wcet()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, 100, 100, 100, 10),
+                (2, 200, 200, 200, 30),
+                (3, 50, 50, 50, 30)
+            ]
+        );
+        assert_eq!(t2.resources(), vec!["R1", "R2"]);
+        assert_eq!(
+            set.ceilings().into_iter().collect::<Vec<_>>(),
+            vec![("R1".to_string(), 2), ("R2".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn to_toml() {
+        let set = TaskSet::from_toml(EXAM).unwrap();
+        assert_eq!(TaskSet::from_toml(&set.to_toml()).unwrap(), set);
+    }
+
+    #[test]
+    fn defaults() {
+        let set = TaskSet::from_toml(
+            r#"
+            [[tasks]]
+            id = "t"
+            prio = 1
+            deadline = 10
+            inter_arrival = 10
+            trace = { id = "t", start = 0, end = 2 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(set.unit, 1);
+        assert_eq!(set.tasks[0].offset, 0);
+        assert!(set.ceilings().is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        let mut set = TaskSet::from_toml(EXAM).unwrap();
+        set.tasks[1].trace.inner[1].start = 18;
+        let e = set.validate().unwrap_err().to_string();
+        assert_eq!(
+            e,
+            "invalid task set: `t2`: segment `t2` 0..30 has `R1` 18..28 out of order or bounds"
+        );
+
+        let mut set = TaskSet::from_toml(EXAM).unwrap();
+        set.tasks[2].trace.inner[0].end = 31;
+        assert!(set.validate().is_err());
+
+        let mut set = TaskSet::from_toml(EXAM).unwrap();
+        set.tasks[2].id = "t1".into();
+        assert!(set.validate().is_err());
+
+        // R1 within R1, would be a double lock
+        let mut set = TaskSet::from_toml(EXAM).unwrap();
+        let r1 = &mut set.tasks[1].trace.inner[1];
+        r1.inner = vec![trace("R1", 23, 24, vec![])];
+        assert_eq!(
+            set.validate().unwrap_err().to_string(),
+            "invalid task set: `t2`: segment `R1` 23..24 nested within itself"
+        );
+    }
+}
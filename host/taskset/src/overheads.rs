@@ -0,0 +1,139 @@
+//! host/taskset/src/overheads.rs
+//!
+//! RTIC runtime overheads, in clock cycles.
+//!
+//! Given in TOML, or imported from the on-target calibration
+//! (`timing::Calibration`), the RTT line
+//!
+//! ```text
+//! calibration (release): read 1 dispatch 240 switch 44
+//! ```
+//!
+//! where `switch` (pend to handler entry) gives the pend latency, and
+//! `dispatch` (scheduled release to task entry, through the timer queue
+//! and the dispatcher) the remaining dispatch cost.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Costs of the RTIC runtime, all in clock cycles (zero by default).
+///
+/// Described in TOML as, e.g.:
+///
+/// ```toml
+/// pend = 12
+/// dispatch = 180
+/// exit = 40
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Overheads {
+    /// Pend latency, from pending an interrupt (dispatcher or timer)
+    /// to its handler being entered.
+    pub pend: u32,
+    /// Dispatcher entry, from handler entry to the task body.
+    pub dispatch: u32,
+    /// Task return through the dispatcher.
+    pub exit: u32,
+    /// `lock`, raising BASEPRI to the ceiling.
+    pub lock: u32,
+    /// End of `lock`, restoring BASEPRI.
+    pub unlock: u32,
+    /// Timer queue insert (`schedule`).
+    pub timer_insert: u32,
+    /// Timer queue pop (SysTick handler), per released task.
+    pub timer_pop: u32,
+}
+
+impl Overheads {
+    /// Parses a TOML description.
+    pub fn from_toml(s: &str) -> Result<Overheads, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Overheads of the last calibration line of an RTT log (if any),
+    /// the costs not calibrated (lock, timer queue) taken from `self`.
+    pub fn calibrated(self, log: &str) -> Option<Overheads> {
+        let (dispatch, switch) = log.lines().rev().find_map(calibration)?;
+        Some(Overheads {
+            pend: switch,
+            // release to entry, the timer handler and the dispatcher pended
+            dispatch: dispatch.saturating_sub(2 * switch + self.timer_pop),
+            ..self
+        })
+    }
+}
+
+impl fmt::Display for Overheads {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "pend {} dispatch {} exit {} lock {} unlock {} timer_insert {} timer_pop {}",
+            self.pend,
+            self.dispatch,
+            self.exit,
+            self.lock,
+            self.unlock,
+            self.timer_insert,
+            self.timer_pop
+        )
+    }
+}
+
+/// `calibration (<profile>): read <r> dispatch <d> switch <s>`
+fn calibration(line: &str) -> Option<(u32, u32)> {
+    let line = line.trim().strip_prefix("calibration (")?;
+    let fields: Vec<&str> = line[line.find("): ")? + 3..].split_whitespace().collect();
+    match fields.as_slice() {
+        ["read", _, "dispatch", dispatch, "switch", switch] => {
+            Some((dispatch.parse().ok()?, switch.parse().ok()?))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toml() {
+        let o = Overheads::from_toml("pend = 12\ndispatch = 180").unwrap();
+        assert_eq!(
+            o,
+            Overheads {
+                pend: 12,
+                dispatch: 180,
+                ..Overheads::default()
+            }
+        );
+        assert!(Overheads::from_toml("switch = 1").is_err());
+        assert_eq!(
+            o.to_string(),
+            "pend 12 dispatch 180 exit 0 lock 0 unlock 0 timer_insert 0 timer_pop 0"
+        );
+    }
+
+    #[test]
+    fn calibration() {
+        let log = "\
+calibration (debug): read 4 dispatch 900 switch 100
+t3: count 20 min 30020 max 34200 mean 30410
+calibration (release): read 1 dispatch 240 switch 44
+";
+        let defaults = Overheads {
+            lock: 20,
+            timer_pop: 60,
+            ..Overheads::default()
+        };
+        assert_eq!(
+            defaults.calibrated(log),
+            Some(Overheads {
+                pend: 44,
+                dispatch: 92,
+                ..defaults
+            })
+        );
+        assert_eq!(defaults.calibrated("t3: count 20"), None);
+    }
+}
@@ -9,20 +9,32 @@
 // use core::sync::atomic::{self, Ordering};
 use panic_halt as _;
 // use panic_rtt_target as _;
-use rtt_target::{rprintln, rtt_init_print};
+use rtt_target::{rtt_init, UpChannel};
 use stm32f4;
+use timing::{info, Cyccnt, Logger};
+
+// deferred formatting, see `timing::log`
+static LOG: Logger<UpChannel, Cyccnt> = Logger::new(Cyccnt);
 
 #[rtic::app(device = stm32f4)]
 const APP: () = {
     #[init]
-    fn init(_cx: init::Context) {
-        rtt_init_print!();
-        rprintln!("init");
+    fn init(mut cx: init::Context) {
+        // timestamps of the log
+        cx.core.DCB.enable_trace();
+        cx.core.DWT.enable_cycle_counter();
+        let channels = rtt_init! {
+            up: {
+                0: { size: 1024 mode: NoBlockSkip name: "Log" }
+            }
+        };
+        LOG.init(channels.up.0);
+        info!(LOG, "init");
     }
 
     #[idle]
     fn idle(_cx: idle::Context) -> ! {
-        rprintln!("idle");
+        info!(LOG, "idle");
         panic!("panic");
         //loop {
         //    continue;
@@ -31,16 +43,27 @@ const APP: () = {
 };
 
 // A) A Simple Trace
-// > cargo run
-// cargo run
-//    Compiling app v0.1.0 (/home/pln/courses/d7020e/rtic_f4xx_nucleo)
-//     Finished dev [unoptimized + debuginfo] target(s) in 0.18s
-//      Running `probe-run --chip STM32F411RETx target/thumbv7em-none-eabi/debug/app`
-//   (HOST) INFO  flashing program (15.06 KiB)
-//   (HOST) INFO  success!
-// ────────────────────────────────────────────────────────────────────────────────
-// init
-// idle
+// The log is deferred formatted (`timing::log`), the format strings stay
+// in the `.log` section of the ELF, only their indices (and arguments)
+// are sent over RTT. `probe-run` shows the raw channel, capture it with
+// OpenOCD instead (`openocd -f openocd.cfg`, then `cargo run`, in gdb):
+//
+// (gdb) monitor rtt setup 0x20000000 0x20000 "SEGGER RTT"
+// (gdb) monitor rtt start
+// (gdb) monitor rtt server start 9090 0
+// (gdb) continue
+//
+// > nc localhost 9090 > log.bin
+//
+// and decode it on the host, by the image:
+//
+// > cd host
+// > cargo run -p log -- --elf ../target/thumbv7em-none-eabi/debug/app ../log.bin
+//       1093 INFO  init
+//       3301 INFO  idle
+//
+// (your timestamps will differ). Messages below `info` are removed at
+// compile time, `cargo run --features log-debug` to keep `debug!`.
 //
 // B) Breaking
 // Now press Ctrl-C
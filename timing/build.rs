@@ -0,0 +1,13 @@
+//! timing/build.rs
+//!
+//! Puts the `log.x` linker script (the `.log` section of `timing::log`)
+//! on the linker search path of the application.
+
+use std::{env, fs, path::PathBuf};
+
+fn main() {
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::copy("log.x", out.join("log.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=log.x");
+}
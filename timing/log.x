@@ -0,0 +1,10 @@
+/* The interned format strings of `timing::log`, linked by `-Tlog.x`. */
+/* An INFO section at address 0, not loaded to the device (no flash is */
+/* used), the address of an entry is its index. */
+SECTIONS
+{
+  .log 0 (INFO) :
+  {
+    *(.log .log.*);
+  }
+}
@@ -0,0 +1,523 @@
+//! timing/src/accounting.rs
+//!
+//! Preemption aware execution time accounting.
+//!
+//! Each task instance is bracketed by `enter`/`exit` on a (single,
+//! global) accounting stack. As RTIC tasks preempt strictly by
+//! priority, the stack holds one frame per active priority level,
+//! and the time spent in nested (higher priority) tasks is charged
+//! as preemption to the task below.
+//!
+//! ```ignore
+//! #[no_mangle]
+//! static ACCOUNTING: Accounting = Accounting::new();
+//! #[no_mangle]
+//! static T1_EXEC: ExecutionMonitor = ExecutionMonitor::new("t1");
+//!
+//! #[task(schedule = [t1], priority = 1)]
+//! fn t1(cx: t1::Context) {
+//!     let raw = RawLatency(cx.scheduled.elapsed().as_cycles());
+//!     ACCOUNTING.enter(&Cyccnt, 1, raw);
+//!     // ... workload
+//!     T1_EXEC.record(&ACCOUNTING.exit(&Cyccnt));
+//! }
+//! ```
+//!
+//! Blocking is the part of the start latency where the task was
+//! ready, but a lower priority task kept executing (under SRP this
+//! only happens when the system ceiling is raised by a `lock`).
+//! The calibrated dispatch overhead is excluded. Locks instrumented
+//! by a [`LockMonitor`] attribute the blocking to the resource.
+//!
+//! Only the last [`CHILDREN`] nested instances of each task instance
+//! are remembered. When more complete within the start latency of a
+//! task, their execution is taken for blocking (an over-estimate), and
+//! the job is flagged `inexact` (counted by the [`ExecutionMonitor`]).
+
+use crate::{
+    calibrate::{self, Overheads},
+    clock::Clock,
+    cs::Shared,
+    lock::LockMonitor,
+    stats::Stats,
+};
+use core::fmt;
+
+/// Maximum nesting depth, the STM32F4 has 4 NVIC priority bits.
+pub const DEPTH: usize = 16;
+
+/// Number of completed nested task instances remembered per frame.
+pub const CHILDREN: usize = 4;
+
+/// The release to entry latency as measured, `cx.scheduled.elapsed()`,
+/// before any compensation (not the jitter returned by
+/// `JitterMonitor::record`, its dispatch overhead already subtracted).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RawLatency(pub u32);
+
+/// Timing of a completed task instance (in cycles).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Job {
+    pub priority: u8,
+    /// Release to start (release jitter).
+    pub latency: u32,
+    /// Net execution time, excluding preemption (WCET candidate).
+    pub execution: u32,
+    /// Time spent in higher priority tasks.
+    pub preemption: u32,
+    /// Part of the latency where lower priority tasks executed.
+    pub blocking: u32,
+    /// Release to completion.
+    pub response: u32,
+    /// More than [`CHILDREN`] nested instances completed within the
+    /// latency, the blocking is over-estimated.
+    pub inexact: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Frame {
+    priority: u8,
+    entry: u32,
+    latency: u32,
+    blocking: u32,
+    inexact: bool,
+    preemption: u32,
+    // (entry, exit) of the last completed nested instances (ring buffer)
+    children: [(u32, u32); CHILDREN],
+    next_child: usize,
+    full: bool,
+    // exit of the last instance overwritten in `children`
+    evicted: Option<u32>,
+    // (monitor, acquire, release) of the last instrumented lock
+    lock: Option<(&'static LockMonitor, u32, u32)>,
+}
+
+impl Frame {
+    const EMPTY: Frame = Frame {
+        priority: 0,
+        entry: 0,
+        latency: 0,
+        blocking: 0,
+        inexact: false,
+        preemption: 0,
+        children: [(0, 0); CHILDREN],
+        next_child: 0,
+        full: false,
+        evicted: None,
+        lock: None,
+    };
+
+    /// Time this frame executed during the last `window` cycles before `now`.
+    fn executed(&self, now: u32, window: u32) -> u32 {
+        // measure as age w.r.t. `now`, wrap safe for intervals < 2^31
+        let window = window.min(now.wrapping_sub(self.entry));
+        let nested: u32 = self
+            .children
+            .iter()
+            .map(|&(entry, exit)| {
+                let (from, to) = (now.wrapping_sub(entry), now.wrapping_sub(exit));
+                from.min(window).saturating_sub(to)
+            })
+            .sum();
+        window.saturating_sub(nested)
+    }
+}
+
+struct Stack {
+    frames: [Frame; DEPTH],
+    depth: usize,
+}
+
+/// The accounting stack, one per application.
+pub struct Accounting {
+    stack: Shared<Stack>,
+}
+
+impl Accounting {
+    pub const fn new() -> Self {
+        Accounting {
+            stack: Shared::new(Stack {
+                frames: [Frame::EMPTY; DEPTH],
+                depth: 0,
+            }),
+        }
+    }
+
+    /// To be called on task entry, the dispatch overhead is subtracted
+    /// from the blocking here.
+    pub fn enter<C: Clock>(&self, clock: &C, priority: u8, latency: RawLatency) {
+        self.enter_with(clock, priority, latency, &calibrate::overheads());
+    }
+
+    fn enter_with<C: Clock>(
+        &self,
+        clock: &C,
+        priority: u8,
+        RawLatency(latency): RawLatency,
+        overheads: &Overheads,
+    ) {
+        let now = clock.now();
+        let lock = self.stack.with(|s| {
+            assert!(s.depth < DEPTH, "accounting stack overflow");
+            let (blocking, inexact, lock) = match s.depth {
+                0 => (0, false, None), // preempting idle
+                d => {
+                    let top = &s.frames[d - 1];
+                    let blocking = top.executed(now, latency);
+                    // a forgotten instance within the latency
+                    let inexact =
+                        matches!(top.evicted, Some(exit) if now.wrapping_sub(exit) < latency);
+                    (overheads.blocking(blocking), inexact, top.lock)
+                }
+            };
+            s.frames[s.depth] = Frame {
+                priority,
+                entry: now,
+                latency,
+                blocking,
+                inexact,
+                ..Frame::EMPTY
+            };
+            s.depth += 1;
+            lock
+        });
+
+        // blocking by the last critical section of the preempted task
+        if let Some((monitor, acquire, release)) = lock {
+            if monitor.ceiling() >= priority {
+                let window = latency.min(now.wrapping_sub(acquire));
+                let blocking = window.saturating_sub(now.wrapping_sub(release));
+                if blocking > 0 {
+                    monitor.record_blocking(priority, blocking);
+                }
+            }
+        }
+    }
+
+    /// To be called on task exit, returns the timing of the instance.
+    pub fn exit<C: Clock>(&self, clock: &C) -> Job {
+        let now = clock.now();
+        self.stack.with(|s| {
+            assert!(s.depth > 0, "accounting exit without enter");
+            s.depth -= 1;
+            let frame = s.frames[s.depth];
+            let elapsed = now.wrapping_sub(frame.entry);
+
+            if let Some(parent) = s.depth.checked_sub(1).map(|d| &mut s.frames[d]) {
+                parent.preemption = parent.preemption.wrapping_add(elapsed);
+                if parent.full {
+                    parent.evicted = Some(parent.children[parent.next_child].1);
+                }
+                parent.children[parent.next_child] = (frame.entry, now);
+                parent.next_child = (parent.next_child + 1) % CHILDREN;
+                parent.full |= parent.next_child == 0;
+            }
+
+            Job {
+                priority: frame.priority,
+                latency: frame.latency,
+                execution: elapsed.saturating_sub(frame.preemption),
+                preemption: frame.preemption,
+                blocking: frame.blocking,
+                response: frame.latency.wrapping_add(elapsed),
+                inexact: frame.inexact,
+            }
+        })
+    }
+
+    /// Registers a critical section of the running task.
+    pub(crate) fn locked(&self, monitor: &'static LockMonitor, acquire: u32, release: u32) {
+        self.stack.with(|s| {
+            if let Some(d) = s.depth.checked_sub(1) {
+                s.frames[d].lock = Some((monitor, acquire, release));
+            }
+        });
+    }
+
+    /// Current nesting depth.
+    pub fn depth(&self) -> usize {
+        self.stack.with(|s| s.depth)
+    }
+}
+
+impl Default for Accounting {
+    fn default() -> Self {
+        Accounting::new()
+    }
+}
+
+/// Execution, preemption and blocking statistics of a task.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct Execution {
+    pub execution: Stats,
+    pub preemption: Stats,
+    pub blocking: Stats,
+    /// Jobs with an over-estimated blocking (see [`Job::inexact`]).
+    pub inexact: u32,
+}
+
+/// Per task statistics over completed [`Job`]s.
+pub struct ExecutionMonitor {
+    name: &'static str,
+    execution: Shared<Execution>,
+}
+
+impl ExecutionMonitor {
+    pub const fn new(name: &'static str) -> Self {
+        ExecutionMonitor {
+            name,
+            execution: Shared::new(Execution {
+                execution: Stats::new(),
+                preemption: Stats::new(),
+                blocking: Stats::new(),
+                inexact: 0,
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn record(&self, job: &Job) {
+        self.execution.with(|e| {
+            e.execution.record(job.execution);
+            e.preemption.record(job.preemption);
+            e.blocking.record(job.blocking);
+            e.inexact = e.inexact.saturating_add(job.inexact as u32);
+        });
+    }
+
+    pub fn execution(&self) -> Execution {
+        self.execution.get()
+    }
+
+    /// The largest net execution time observed (WCET candidate).
+    pub fn wcet(&self) -> u32 {
+        self.execution().execution.max
+    }
+}
+
+impl fmt::Display for ExecutionMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let e = self.execution();
+        write!(
+            f,
+            "{}: execution {}, preemption {}, blocking {}",
+            self.name, e.execution, e.preemption, e.blocking
+        )?;
+        if e.inexact > 0 {
+            write!(f, " ({} inexact)", e.inexact)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::mock::MockClock;
+
+    // drives the accounting with synthetic (absolute) time stamps
+    struct Sim {
+        clock: MockClock,
+        acc: Accounting,
+    }
+
+    impl Sim {
+        fn new() -> Self {
+            Sim {
+                clock: MockClock::new(0),
+                acc: Accounting::new(),
+            }
+        }
+
+        fn at(&self, t: u32) -> &Self {
+            self.clock.advance(t - self.clock.now());
+            self
+        }
+
+        fn enter(&self, t: u32, priority: u8, release: u32) {
+            self.at(t)
+                .acc
+                .enter(&self.clock, priority, RawLatency(t - release));
+        }
+
+        fn exit(&self, t: u32) -> Job {
+            self.at(t).acc.exit(&self.clock)
+        }
+    }
+
+    #[test]
+    fn single() {
+        let sim = Sim::new();
+        sim.enter(100_240, 1, 100_000);
+        let job = sim.exit(110_240);
+        assert_eq!(
+            job,
+            Job {
+                priority: 1,
+                latency: 240,
+                execution: 10_000,
+                preemption: 0,
+                blocking: 0,
+                response: 10_240,
+                inexact: false,
+            }
+        );
+        assert_eq!(sim.acc.depth(), 0);
+    }
+
+    #[test]
+    fn preemption() {
+        // t1 preempted by t3, the exam scenario of 10k execution + 30k preemption
+        let sim = Sim::new();
+        sim.enter(240, 1, 0);
+        sim.enter(5_240, 3, 5_000);
+        let t3 = sim.exit(35_240);
+        let t1 = sim.exit(40_240);
+
+        assert_eq!(t3.execution, 30_000);
+        assert_eq!(t3.response, 30_240);
+        // t1 was running when t3 got released (dispatch overhead only,
+        // as the overheads are not calibrated)
+        assert_eq!(t3.blocking, 240);
+
+        assert_eq!(t1.execution, 10_000);
+        assert_eq!(t1.preemption, 30_000);
+        assert_eq!(t1.response, 40_240);
+    }
+
+    #[test]
+    fn nested_preemption() {
+        let sim = Sim::new();
+        sim.enter(0, 1, 0);
+        sim.enter(1_000, 2, 1_000);
+        sim.enter(2_000, 3, 2_000);
+        let t3 = sim.exit(3_000);
+        let t2 = sim.exit(5_000);
+        let t1 = sim.exit(6_000);
+
+        assert_eq!((t3.execution, t3.preemption), (1_000, 0));
+        assert_eq!((t2.execution, t2.preemption), (3_000, 1_000));
+        assert_eq!((t1.execution, t1.preemption), (2_000, 4_000));
+    }
+
+    #[test]
+    fn blocking() {
+        // t2 holds R2 (ceiling 3), t3 released at 1_000 starts at 3_000 (unlock)
+        let sim = Sim::new();
+        sim.enter(0, 2, 0);
+        sim.enter(3_000, 3, 1_000);
+        let t3 = sim.exit(4_000);
+        let t2 = sim.exit(10_000);
+
+        assert_eq!(t3.blocking, 2_000);
+        assert_eq!(t3.response, 3_000);
+        assert_eq!(t2.preemption, 1_000);
+        assert_eq!(t2.blocking, 0);
+    }
+
+    #[test]
+    fn blocking_excludes_interference() {
+        // t1 holds a resource with ceiling 3 during [0, 500],
+        // t3 released at 100 and t2 released at 200 are both blocked,
+        // t3 runs [500, 600] (interference for t2), then t2 starts
+        let sim = Sim::new();
+        sim.enter(0, 1, 0);
+        sim.enter(500, 3, 100);
+        let t3 = sim.exit(600);
+        sim.enter(600, 2, 200);
+        let t2 = sim.exit(700);
+        sim.exit(800);
+
+        assert_eq!(t3.blocking, 400);
+        assert_eq!(t2.latency, 400);
+        assert_eq!(t2.blocking, 300);
+    }
+
+    #[test]
+    fn wrapping() {
+        let sim = Sim::new();
+        sim.clock.advance(u32::MAX - 1_000);
+        let t0 = sim.clock.now();
+        sim.acc.enter(&sim.clock, 1, RawLatency(0));
+        sim.clock.advance(1_000);
+        sim.acc.enter(&sim.clock, 2, RawLatency(0));
+        sim.clock.advance(1_000);
+        sim.acc.exit(&sim.clock);
+        sim.clock.advance(1_000);
+        let t1 = sim.acc.exit(&sim.clock);
+        assert_eq!(sim.clock.now().wrapping_sub(t0), 3_000);
+        assert_eq!((t1.execution, t1.preemption), (2_000, 1_000));
+    }
+
+    #[test]
+    fn compensated_once() {
+        // the raw latency in, the dispatch overhead out once
+        let o = Overheads {
+            read: 2,
+            dispatch: 240,
+            switch: 44,
+        };
+        let sim = Sim::new();
+        sim.at(0).acc.enter_with(&sim.clock, 2, RawLatency(0), &o);
+        // t3 released at 1_000, blocked until 3_000
+        sim.at(3_000)
+            .acc
+            .enter_with(&sim.clock, 3, RawLatency(2_000), &o);
+        let t3 = sim.exit(4_000);
+        sim.exit(10_000);
+        assert_eq!(t3.blocking, 2_000 - 240);
+        assert_eq!(t3.latency, 2_000);
+    }
+
+    #[test]
+    fn inexact() {
+        // t1 preempted by 5 short t3 instances while t2 waits
+        let sim = Sim::new();
+        sim.enter(0, 1, 0);
+        for i in 0..5 {
+            sim.enter(100 + i * 100, 3, 100 + i * 100);
+            sim.exit(150 + i * 100);
+        }
+        // the first instance is forgotten, its 50 cycles taken for blocking
+        sim.enter(700, 2, 50);
+        let t2 = sim.exit(800);
+        assert_eq!(t2.blocking, 650 - 4 * 50);
+        assert!(t2.inexact);
+        let m = ExecutionMonitor::new("t2");
+        m.record(&t2);
+        assert!(m.to_string().ends_with(" (1 inexact)"));
+        // a later job, the forgotten instance is out of its latency
+        sim.enter(900, 2, 600);
+        assert!(!sim.exit(1_000).inexact);
+        sim.exit(1_100);
+    }
+
+    #[test]
+    fn monitor() {
+        let m = ExecutionMonitor::new("t1");
+        m.record(&Job {
+            execution: 10_000,
+            preemption: 30_000,
+            ..Job::default()
+        });
+        m.record(&Job {
+            execution: 10_100,
+            preemption: 90_000,
+            blocking: 10,
+            ..Job::default()
+        });
+        assert_eq!(m.wcet(), 10_100);
+        assert_eq!(m.execution().preemption.max, 90_000);
+        assert_eq!(m.execution().blocking.max, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "accounting exit without enter")]
+    fn unbalanced() {
+        Sim::new().exit(0);
+    }
+}
@@ -0,0 +1,305 @@
+//! timing/src/burn.rs
+//!
+//! Cycle exact synthetic workload, for emulating a task set.
+//!
+//! `cortex_m::asm::delay(units)` is a precompiled assembly loop, so its
+//! cost is affine in `units` regardless of the optimization level (and
+//! without the `inline-asm` feature). The slope (cycles per unit) depends
+//! on the core and flash wait states, the intercept on the call path.
+//! [`Burn::calibrate`] fits both against a clock on target, and
+//! [`Burn::cycles`] inverts the fit, compensating for the call overhead.
+//!
+//! A request is rounded to whole loop iterations, and requests below
+//! [`Burn::min_cycles`] (the call overhead) return immediately. Otherwise
+//! the cycles burnt are within [`TOLERANCE`] of the request, as verified
+//! by [`Burn::self_check`].
+//!
+//! The loop counts iterations, so under preemption it still consumes
+//! `n` cycles of its own execution (only the response is delayed).
+//!
+//! ```ignore
+//! // init (interrupts disabled)
+//! BURN.calibrate(&Cyccnt);
+//! assert!(BURN.self_check(&Cyccnt).ok());
+//!
+//! // a task
+//! burn_cycles(10_000);
+//! ```
+
+use crate::{calibrate::read_cost, clock::Clock, cs::Shared};
+use core::fmt;
+
+/// Accepted deviation (in cycles) from the requested workload.
+pub const TOLERANCE: u32 = 8;
+
+/// Fixed point fraction bits of the slope.
+const FRAC: u32 = 8;
+
+/// Samples per calibration and self check measurement (minimum is used).
+const SAMPLES: usize = 8;
+
+/// Delay units for the two point fit.
+const FIT: (u32, u32) = (64, 4_096);
+
+/// Workload for the end to end correction of the intercept.
+const CORRECT: u32 = 2_000;
+
+/// Workloads verified by the self check.
+const CHECKS: [u32; 6] = [100, 500, 1_000, 4_000, 10_000, 100_000];
+
+/// Affine cost of the delay loop.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Model {
+    /// Cycles per delay unit (fixed point, `FRAC` bits).
+    slope: u32,
+    /// Cycles of call overhead.
+    offset: u32,
+}
+
+impl Model {
+    /// One cycle per unit, i.e., plain `asm::delay` until calibrated.
+    const NOMINAL: Model = Model {
+        slope: 1 << FRAC,
+        offset: 0,
+    };
+
+    /// Delay units consuming `n` cycles (rounded), 0 if below the overhead.
+    fn units(&self, n: u32) -> u32 {
+        let n = match n.checked_sub(self.offset) {
+            Some(n) => n as u64,
+            None => return 0,
+        };
+        let slope = self.slope as u64;
+        (((n << FRAC) + slope / 2) / slope) as u32
+    }
+}
+
+/// Outcome of [`Burn::self_check`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Check {
+    /// The requested workload with the largest deviation.
+    pub cycles: u32,
+    /// Measured minus requested cycles.
+    pub error: i32,
+}
+
+impl Check {
+    pub fn ok(&self) -> bool {
+        self.error.unsigned_abs() <= TOLERANCE
+    }
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "burn check: worst error {} at {} cycles ({})",
+            self.error,
+            self.cycles,
+            if self.ok() { "ok" } else { "FAIL" }
+        )
+    }
+}
+
+/// A calibrated delay loop.
+pub struct Burn {
+    model: Shared<Model>,
+    spin: fn(u32),
+}
+
+impl Burn {
+    pub const fn new() -> Self {
+        Burn::with(spin)
+    }
+
+    /// A workload built on `spin` (a delay loop of `units`).
+    pub const fn with(spin: fn(u32)) -> Self {
+        Burn {
+            model: Shared::new(Model::NOMINAL),
+            spin,
+        }
+    }
+
+    /// Consumes `n` cycles (within [`TOLERANCE`] once calibrated).
+    #[inline(never)]
+    pub fn cycles(&self, n: u32) {
+        let units = self.model.get().units(n);
+        if units > 0 {
+            (self.spin)(units);
+        }
+    }
+
+    /// Fits the delay loop against `clock`.
+    ///
+    /// Call from `init` (interrupts disabled).
+    pub fn calibrate<C: Clock>(&self, clock: &C) {
+        let read = read_cost(clock);
+        let (a, b) = FIT;
+        let t_a = self.measure(clock, read, || (self.spin)(a));
+        let t_b = self.measure(clock, read, || (self.spin)(b));
+        let slope = ((t_b.saturating_sub(t_a) as u64) << FRAC) / (b - a) as u64;
+        let slope = (slope as u32).max(1);
+        let offset = t_a.saturating_sub(((a as u64 * slope as u64) >> FRAC) as u32);
+        self.model.with(|m| *m = Model { slope, offset });
+
+        // the spin intercept misses the cost of `cycles` itself
+        let error = self.measure(clock, read, || self.cycles(CORRECT)) as i64 - CORRECT as i64;
+        self.model
+            .with(|m| m.offset = (m.offset as i64 + error).max(0) as u32);
+    }
+
+    /// Measures the accuracy for a range of workloads against `clock`.
+    ///
+    /// Call from `init` (interrupts disabled), after [`Burn::calibrate`].
+    pub fn self_check<C: Clock>(&self, clock: &C) -> Check {
+        let read = read_cost(clock);
+        let min = self.min_cycles();
+        CHECKS
+            .iter()
+            .filter(|&&n| n > min)
+            .map(|&n| Check {
+                cycles: n,
+                error: self.measure(clock, read, || self.cycles(n)) as i32 - n as i32,
+            })
+            .max_by_key(|c| c.error.unsigned_abs())
+            .unwrap_or(Check {
+                cycles: 0,
+                error: 0,
+            })
+    }
+
+    /// The call overhead, the least workload that can be emulated.
+    pub fn min_cycles(&self) -> u32 {
+        self.model.get().offset
+    }
+
+    /// Cycles of `f` excluding the clock `read` (minimum over `SAMPLES`).
+    fn measure<C: Clock>(&self, clock: &C, read: u32, f: impl Fn()) -> u32 {
+        (0..SAMPLES)
+            .map(|_| {
+                let start = clock.now();
+                f();
+                clock.now().wrapping_sub(start).saturating_sub(read)
+            })
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+impl Default for Burn {
+    fn default() -> Self {
+        Burn::new()
+    }
+}
+
+impl fmt::Display for Burn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let m = self.model.get();
+        write!(
+            f,
+            "burn: {}.{:03} cycles/unit, overhead {}",
+            m.slope >> FRAC,
+            ((m.slope & ((1 << FRAC) - 1)) * 1000) >> FRAC,
+            m.offset
+        )
+    }
+}
+
+fn spin(units: u32) {
+    cortex_m::asm::delay(units)
+}
+
+/// The workload used by [`burn_cycles`].
+#[no_mangle]
+pub static BURN: Burn = Burn::new();
+
+/// Consumes `n` cycles of execution, see [`Burn::cycles`].
+pub fn burn_cycles(n: u32) {
+    BURN.cycles(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Clock;
+    use core::cell::Cell;
+
+    // An emulated core, the delay loop advances its cycle counter.
+    thread_local! {
+        static NOW: Cell<u32> = const { Cell::new(0) };
+    }
+
+    fn advance(cycles: u32) {
+        NOW.with(|now| now.set(now.get().wrapping_add(cycles)));
+    }
+
+    struct Core;
+
+    impl Clock for Core {
+        fn now(&self) -> u32 {
+            let now = NOW.with(|now| now.get());
+            advance(2);
+            now
+        }
+    }
+
+    // Cortex-M4 like, 3 cycles per iteration, 11 cycles call overhead.
+    fn m4(units: u32) {
+        advance(11 + 3 * units);
+    }
+
+    // A loop running `units / 4 + 1` iterations.
+    fn scaled(units: u32) {
+        advance(7 + 3 * (units / 4 + 1));
+    }
+
+    fn burnt(burn: &Burn, n: u32) -> u32 {
+        let start = Core.now();
+        burn.cycles(n);
+        Core.now().wrapping_sub(start) - 2
+    }
+
+    #[test]
+    fn uncalibrated() {
+        let burn = Burn::with(m4);
+        assert_eq!(burnt(&burn, 1_000), 3_011);
+        assert_eq!(burn.min_cycles(), 0);
+    }
+
+    #[test]
+    fn calibrated() {
+        let burn = Burn::with(m4);
+        burn.calibrate(&Core);
+        assert_eq!(burn.to_string(), "burn: 3.000 cycles/unit, overhead 11");
+        for &n in &[30, 1_000, 10_000, 1_000_000] {
+            let error = burnt(&burn, n) as i32 - n as i32;
+            assert!(error.unsigned_abs() <= TOLERANCE, "{} {}", n, error);
+        }
+        // below the call overhead nothing is burnt
+        assert_eq!(burnt(&burn, 5), 0);
+    }
+
+    #[test]
+    fn self_check() {
+        let burn = Burn::with(scaled);
+        burn.calibrate(&Core);
+        let check = burn.self_check(&Core);
+        assert!(check.ok(), "{}", check);
+        assert!(check.to_string().ends_with("(ok)"));
+    }
+
+    #[test]
+    fn self_check_fails() {
+        // fit against one core, run on a slower one
+        let burn = Burn::with(m4);
+        burn.calibrate(&Core);
+        let burn = Burn {
+            model: Shared::new(burn.model.get()),
+            spin: scaled,
+        };
+        let check = burn.self_check(&Core);
+        assert!(!check.ok());
+        assert_eq!(check.cycles, 100_000);
+        assert!(check.to_string().ends_with("(FAIL)"));
+    }
+}
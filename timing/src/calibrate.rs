@@ -0,0 +1,215 @@
+//! timing/src/calibrate.rs
+//!
+//! Measurement overhead calibration.
+//!
+//! Every measurement includes some overhead, the cost of reading
+//! the clock, the dispatch of a released (software) task, and the
+//! context switch on preemption. These costs depend on the build
+//! (debug/release/`inline-asm`), so they are measured on target:
+//!
+//! - `read`, measured from `init` by [`Calibration::measure_read`].
+//! - `dispatch`, the release jitter of an undisturbed software task,
+//!   recorded by [`Calibration::record_dispatch`].
+//! - `switch`, pend to entry of a (higher priority) hardware task,
+//!   recorded by [`Calibration::mark_pend`]/[`Calibration::record_switch`].
+//!
+//! Once calibrated, the monitors subtract the overheads automatically:
+//! the response time excludes `read`, the release jitter and
+//! the blocking exclude `dispatch`.
+
+use crate::{clock::Clock, cs::Shared};
+use core::fmt;
+
+/// Number of samples for the clock read calibration.
+const SAMPLES: usize = 16;
+
+/// Measurement overheads in cycles, zero until calibrated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct Overheads {
+    pub read: u32,
+    pub dispatch: u32,
+    pub switch: u32,
+}
+
+impl Overheads {
+    pub const ZERO: Overheads = Overheads {
+        read: 0,
+        dispatch: 0,
+        switch: 0,
+    };
+
+    /// Response time excluding the cost of reading the clock.
+    pub fn response(&self, raw: u32) -> u32 {
+        raw.saturating_sub(self.read)
+    }
+
+    /// Release jitter beyond the dispatch overhead (measured as a raw
+    /// jitter, including the clock read).
+    pub fn jitter(&self, raw: u32) -> u32 {
+        raw.saturating_sub(self.dispatch)
+    }
+
+    /// Blocking excluding the dispatch overhead.
+    pub fn blocking(&self, raw: u32) -> u32 {
+        raw.saturating_sub(self.dispatch)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct State {
+    overheads: Overheads,
+    pend: u32,
+}
+
+pub struct Calibration {
+    state: Shared<State>,
+}
+
+impl Calibration {
+    pub const fn new() -> Self {
+        Calibration {
+            state: Shared::new(State {
+                overheads: Overheads::ZERO,
+                pend: 0,
+            }),
+        }
+    }
+
+    /// Measures the cost of reading `clock` (minimum over back to back reads).
+    ///
+    /// Call from `init` (interrupts disabled).
+    pub fn measure_read<C: Clock>(&self, clock: &C) -> u32 {
+        let read = read_cost(clock);
+        self.state.with(|s| s.overheads.read = read);
+        read
+    }
+
+    /// Records the dispatch overhead, the raw release jitter
+    /// (`cx.scheduled.elapsed().as_cycles()`) of a task released
+    /// while the system is idle.
+    pub fn record_dispatch(&self, latency: u32) {
+        self.state.with(|s| s.overheads.dispatch = latency);
+    }
+
+    /// To be called right before pending the calibration task.
+    pub fn mark_pend<C: Clock>(&self, clock: &C) {
+        let now = clock.now();
+        self.state.with(|s| s.pend = now);
+    }
+
+    /// To be called first thing in the pended calibration task.
+    pub fn record_switch<C: Clock>(&self, clock: &C) -> u32 {
+        let now = clock.now();
+        self.state.with(|s| {
+            s.overheads.switch = now.wrapping_sub(s.pend).saturating_sub(s.overheads.read);
+            s.overheads.switch
+        })
+    }
+
+    pub fn overheads(&self) -> Overheads {
+        self.state.get().overheads
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration::new()
+    }
+}
+
+impl fmt::Display for Calibration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let o = self.overheads();
+        write!(
+            f,
+            "calibration ({}): read {} dispatch {} switch {}",
+            profile(),
+            o.read,
+            o.dispatch,
+            o.switch
+        )
+    }
+}
+
+/// Cost of reading `clock`, the minimum over back to back reads.
+pub(crate) fn read_cost<C: Clock>(clock: &C) -> u32 {
+    (0..SAMPLES)
+        .map(|_| {
+            let start = clock.now();
+            clock.now().wrapping_sub(start)
+        })
+        .min()
+        .unwrap_or(0)
+}
+
+/// The calibration used by all monitors.
+#[no_mangle]
+pub static CALIBRATION: Calibration = Calibration::new();
+
+/// The calibrated overheads.
+pub fn overheads() -> Overheads {
+    CALIBRATION.overheads()
+}
+
+/// The build profile, to compare calibrations across builds.
+pub fn profile() -> &'static str {
+    match (cfg!(debug_assertions), cfg!(feature = "inline-asm")) {
+        (true, false) => "debug",
+        (true, true) => "debug, inline-asm",
+        (false, false) => "release",
+        (false, true) => "release, inline-asm",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::mock::MockClock;
+
+    #[test]
+    fn read() {
+        let c = Calibration::new();
+        assert_eq!(c.measure_read(&MockClock::ticking(0, 3)), 3);
+        assert_eq!(c.overheads().read, 3);
+    }
+
+    #[test]
+    fn dispatch_and_switch() {
+        let clock = MockClock::ticking(50_000, 2);
+        let c = Calibration::new();
+        c.measure_read(&clock);
+        c.record_dispatch(240);
+        c.mark_pend(&clock);
+        clock.advance(44);
+        assert_eq!(c.record_switch(&clock), 44);
+        assert_eq!(
+            c.overheads(),
+            Overheads {
+                read: 2,
+                dispatch: 240,
+                switch: 44
+            }
+        );
+        assert!(c.to_string().ends_with("): read 2 dispatch 240 switch 44"));
+    }
+
+    #[test]
+    fn compensation() {
+        let o = Overheads {
+            read: 2,
+            dispatch: 240,
+            switch: 44,
+        };
+        assert_eq!(o.response(30_362), 30_360);
+        assert_eq!(o.jitter(251), 11);
+        assert_eq!(o.jitter(100), 0);
+        assert_eq!(o.blocking(3_240), 3_000);
+    }
+
+    #[test]
+    fn uncalibrated() {
+        assert_eq!(Overheads::ZERO.response(30_362), 30_362);
+        assert_eq!(Overheads::ZERO.jitter(240), 240);
+    }
+}
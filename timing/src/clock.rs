@@ -0,0 +1,68 @@
+//! timing/src/clock.rs
+//!
+//! Time sources, all measurements are in clock cycles.
+
+use cortex_m::peripheral::DWT;
+
+/// A wrapping 32 bit cycle counter.
+pub trait Clock {
+    /// Current value of the counter.
+    fn now(&self) -> u32;
+}
+
+/// The DWT cycle counter, i.e., the time base of `rtic::cyccnt::CYCCNT`.
+///
+/// The counter must be enabled in `init`:
+///
+/// ```ignore
+/// cx.core.DCB.enable_trace();
+/// cx.core.DWT.enable_cycle_counter();
+/// ```
+pub struct Cyccnt;
+
+impl Clock for Cyccnt {
+    #[inline(always)]
+    fn now(&self) -> u32 {
+        DWT::get_cycle_count()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::Clock;
+    use core::cell::Cell;
+
+    /// A manually advanced clock for host tests.
+    ///
+    /// Optionally each read advances the clock by `step` cycles,
+    /// i.e., emulates the cost of reading the clock.
+    pub struct MockClock {
+        now: Cell<u32>,
+        step: u32,
+    }
+
+    impl MockClock {
+        pub fn new(now: u32) -> Self {
+            MockClock::ticking(now, 0)
+        }
+
+        pub fn ticking(now: u32, step: u32) -> Self {
+            MockClock {
+                now: Cell::new(now),
+                step,
+            }
+        }
+
+        pub fn advance(&self, cycles: u32) {
+            self.now.set(self.now.get().wrapping_add(cycles));
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> u32 {
+            let now = self.now.get();
+            self.advance(self.step);
+            now
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! timing/src/cs.rs
+//!
+//! Shared state, updated in critical sections.
+//!
+//! On target a critical section is interrupt free, so the state can be
+//! updated from tasks at any priority. On the host (unit tests) each
+//! cell is protected by its own spin lock instead.
+
+use core::cell::UnsafeCell;
+#[cfg(not(target_arch = "arm"))]
+use core::sync::atomic::{AtomicBool, Ordering};
+
+pub(crate) struct Shared<T> {
+    #[cfg(not(target_arch = "arm"))]
+    lock: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// Safety: all access to `value` goes through `with`, i.e., in a critical section.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Shared {
+            #[cfg(not(target_arch = "arm"))]
+            lock: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Runs `f` with exclusive access to the value.
+    ///
+    /// Must not be nested for the same cell.
+    #[cfg(target_arch = "arm")]
+    #[inline]
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        cortex_m::interrupt::free(|_| f(unsafe { &mut *self.value.get() }))
+    }
+
+    /// Runs `f` with exclusive access to the value.
+    ///
+    /// Must not be nested for the same cell.
+    #[cfg(not(target_arch = "arm"))]
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        struct Guard<'a>(&'a AtomicBool);
+
+        impl Drop for Guard<'_> {
+            fn drop(&mut self) {
+                self.0.store(false, Ordering::Release);
+            }
+        }
+
+        while self
+            .lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        let _guard = Guard(&self.lock);
+        f(unsafe { &mut *self.value.get() })
+    }
+}
+
+impl<T: Copy> Shared<T> {
+    pub(crate) fn get(&self) -> T {
+        self.with(|value| *value)
+    }
+}
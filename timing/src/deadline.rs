@@ -0,0 +1,268 @@
+//! timing/src/deadline.rs
+//!
+//! Deadline miss detection for periodic tasks.
+//!
+//! Each task declares its relative deadline and a [`Policy`], the
+//! check is done at task completion:
+//!
+//! ```ignore
+//! #[no_mangle]
+//! static T1_DL: DeadlineMonitor = DeadlineMonitor::new("t1", 100_000, Policy::Breakpoint);
+//!
+//! #[task(schedule = [t1], priority = 1)]
+//! fn t1(cx: t1::Context) {
+//!     // ... workload
+//!     T1_DL.complete(&T1_RT, cx.scheduled.elapsed().as_cycles());
+//! }
+//! ```
+
+use crate::{
+    calibrate::{self, Overheads},
+    cs::Shared,
+    monitor::ResponseTimeMonitor,
+};
+use core::fmt;
+
+/// What to do on a deadline miss.
+///
+/// Misses are always counted, regardless of policy.
+#[derive(Clone, Copy)]
+pub enum Policy {
+    /// Halt in the debugger (`asm::bkpt`), a no-op on the host.
+    Breakpoint,
+    /// Panic, the panic handler decides what happens next.
+    Panic,
+    /// Count the miss and continue.
+    Count,
+    /// Report the miss over RTT and continue.
+    ///
+    /// Requires the `rtt` feature and RTT to be initialized.
+    #[cfg(feature = "rtt")]
+    Report,
+    /// Invoke a user callback and continue.
+    Callback(fn(&Miss)),
+}
+
+/// A deadline miss.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Miss {
+    pub task: &'static str,
+    pub response: u32,
+    pub deadline: u32,
+}
+
+impl Miss {
+    /// By how many cycles the deadline was missed.
+    pub fn lateness(&self) -> u32 {
+        self.response - self.deadline
+    }
+}
+
+impl fmt::Display for Miss {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: deadline miss, response {} deadline {} (late {})",
+            self.task,
+            self.response,
+            self.deadline,
+            self.lateness()
+        )
+    }
+}
+
+/// Number of misses and the worst lateness observed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct Misses {
+    pub count: u32,
+    pub max_lateness: u32,
+}
+
+/// Relative deadline (in cycles) and miss policy of a task.
+pub struct DeadlineMonitor {
+    name: &'static str,
+    deadline: u32,
+    policy: Policy,
+    misses: Shared<Misses>,
+}
+
+impl DeadlineMonitor {
+    pub const fn new(name: &'static str, deadline: u32, policy: Policy) -> Self {
+        DeadlineMonitor {
+            name,
+            deadline,
+            policy,
+            misses: Shared::new(Misses {
+                count: 0,
+                max_lateness: 0,
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn deadline(&self) -> u32 {
+        self.deadline
+    }
+
+    /// Checks a response time (in cycles) against the deadline,
+    /// on a miss the policy is applied.
+    pub fn check(&self, response: u32) -> Result<(), Miss> {
+        if response <= self.deadline {
+            return Ok(());
+        }
+
+        let miss = Miss {
+            task: self.name,
+            response,
+            deadline: self.deadline,
+        };
+        self.misses.with(|misses| {
+            misses.count = misses.count.saturating_add(1);
+            misses.max_lateness = misses.max_lateness.max(miss.lateness());
+        });
+
+        match self.policy {
+            Policy::Breakpoint => breakpoint(),
+            Policy::Panic => panic!("{}", miss),
+            Policy::Count => {}
+            #[cfg(feature = "rtt")]
+            Policy::Report => rtt_target::rprintln!("{}", miss),
+            Policy::Callback(f) => f(&miss),
+        }
+        Err(miss)
+    }
+
+    /// Records the response time of a completed job in `monitor`
+    /// and checks it against the deadline, both compensated for the
+    /// calibrated clock read overhead.
+    ///
+    /// Returns the (compensated) response time.
+    pub fn complete(&self, monitor: &ResponseTimeMonitor, response: u32) -> u32 {
+        self.complete_with(monitor, response, &calibrate::overheads())
+    }
+
+    fn complete_with(&self, monitor: &ResponseTimeMonitor, raw: u32, overheads: &Overheads) -> u32 {
+        let response = monitor.record_with(raw, overheads);
+        let _ = self.check(response);
+        response
+    }
+
+    pub fn misses(&self) -> Misses {
+        self.misses.get()
+    }
+}
+
+impl fmt::Display for DeadlineMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let misses = self.misses();
+        write!(
+            f,
+            "{}: deadline {} misses {} max lateness {}",
+            self.name, self.deadline, misses.count, misses.max_lateness
+        )
+    }
+}
+
+#[cfg(target_arch = "arm")]
+#[inline(always)]
+fn breakpoint() {
+    cortex_m::asm::bkpt();
+}
+
+#[cfg(not(target_arch = "arm"))]
+fn breakpoint() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicU32, Ordering};
+
+    #[test]
+    fn within_deadline() {
+        let dl = DeadlineMonitor::new("t3", 50_000, Policy::Panic);
+        assert_eq!(dl.check(30_362), Ok(()));
+        assert_eq!(dl.check(50_000), Ok(()));
+        assert_eq!(dl.misses().count, 0);
+    }
+
+    #[test]
+    fn count_and_continue() {
+        let dl = DeadlineMonitor::new("t1", 100_000, Policy::Count);
+        let miss = dl.check(130_104).unwrap_err();
+        assert_eq!(miss.lateness(), 30_104);
+        dl.check(100_001).unwrap_err();
+        dl.check(40_655).unwrap();
+        assert_eq!(
+            dl.misses(),
+            Misses {
+                count: 2,
+                max_lateness: 30_104
+            }
+        );
+        assert_eq!(
+            dl.to_string(),
+            "t1: deadline 100000 misses 2 max lateness 30104"
+        );
+    }
+
+    #[test]
+    fn breakpoint_counts_on_host() {
+        let dl = DeadlineMonitor::new("t1", 100_000, Policy::Breakpoint);
+        dl.check(130_104).unwrap_err();
+        assert_eq!(dl.misses().count, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "t1: deadline miss, response 130104 deadline 100000 (late 30104)")]
+    fn panic() {
+        let dl = DeadlineMonitor::new("t1", 100_000, Policy::Panic);
+        let _ = dl.check(130_104);
+    }
+
+    #[test]
+    fn callback() {
+        static LATE: AtomicU32 = AtomicU32::new(0);
+        fn on_miss(miss: &Miss) {
+            LATE.fetch_add(miss.lateness(), Ordering::Relaxed);
+        }
+
+        let dl = DeadlineMonitor::new("t2", 200_000, Policy::Callback(on_miss));
+        dl.check(91_134).unwrap();
+        dl.check(200_010).unwrap_err();
+        dl.check(200_020).unwrap_err();
+        assert_eq!(LATE.load(Ordering::Relaxed), 30);
+    }
+
+    #[test]
+    fn complete_records_every_job() {
+        // a miss that is also a new maximum is both recorded and flagged
+        let rt = ResponseTimeMonitor::new("t1");
+        let dl = DeadlineMonitor::new("t1", 100_000, Policy::Count);
+        for response in &[40_655, 130_104, 40_600] {
+            dl.complete(&rt, *response);
+        }
+        assert_eq!(rt.max(), 130_104);
+        assert_eq!(rt.stats().count, 3);
+        assert_eq!(dl.misses().count, 1);
+    }
+
+    #[test]
+    fn complete_compensated() {
+        // just beyond the deadline raw, within once the read is excluded
+        let o = Overheads {
+            read: 10,
+            ..Overheads::ZERO
+        };
+        let rt = ResponseTimeMonitor::new("t1");
+        let dl = DeadlineMonitor::new("t1", 100_000, Policy::Count);
+        assert_eq!(dl.complete_with(&rt, 100_005, &o), 99_995);
+        assert_eq!(dl.misses().count, 0);
+        assert_eq!(dl.complete_with(&rt, 100_011, &o), 100_001);
+        assert_eq!(dl.misses().max_lateness, 1);
+        assert_eq!(rt.max(), 100_001);
+    }
+}
@@ -0,0 +1,308 @@
+//! timing/src/instant.rs
+//!
+//! Wrap free 64 bit time stamps, extending the 32 bit DWT CYCCNT.
+//!
+//! The extension keeps track of counter overflows, thus it must
+//! observe the counter at least once per 2^32 cycles (~4.3s at
+//! 1GHz, ~268s at the default 16MHz).
+//!
+//! Used as the RTIC monotonic, `cx.scheduled`, `cx.start` and
+//! `elapsed()` work as with `rtic::cyccnt::CYCCNT`, but in 64 bits:
+//!
+//! ```ignore
+//! use timing::instant::U32Ext;
+//!
+//! #[rtic::app(device = stm32f411, monotonic = timing::instant::CYCCNT64)]
+//! const APP: () = {
+//!     #[init(schedule = [t1])]
+//!     fn init(mut cx: init::Context) {
+//!         cx.core.DCB.enable_trace();
+//!         cx.core.DWT.enable_cycle_counter();
+//!         cx.schedule.t1(cx.start + 100_000.cycles()).unwrap();
+//!     }
+//!     ...
+//! };
+//! ```
+//!
+//! The RTIC timer queue (SysTick) reads the clock at least every
+//! 2^24 cycles as long as tasks are scheduled. Otherwise, call
+//! [`Instant::now`] periodically, at least every [`TICK_PERIOD`].
+
+use crate::{clock::Clock, cs::Shared};
+use core::{
+    convert::TryFrom,
+    fmt,
+    ops::{Add, AddAssign, Sub, SubAssign},
+};
+
+/// Maximum interval between two observations of the counter.
+pub const TICK_PERIOD: u32 = 1 << 31;
+
+/// Overflow tracking on top of a 32 bit clock.
+pub struct Extended {
+    // (last observed counter value, overflows)
+    state: Shared<(u32, u32)>,
+}
+
+impl Extended {
+    pub const fn new() -> Self {
+        Extended {
+            state: Shared::new((0, 0)),
+        }
+    }
+
+    /// Reads `clock`, extended to 64 bits.
+    pub fn now<C: Clock>(&self, clock: &C) -> u64 {
+        self.state.with(|(last, high)| {
+            let now = clock.now();
+            if now < *last {
+                *high = high.wrapping_add(1);
+            }
+            *last = now;
+            (*high as u64) << 32 | now as u64
+        })
+    }
+
+    /// Restarts the extension, e.g., after the counter is cleared.
+    pub fn reset(&self) {
+        self.state.with(|state| *state = (0, 0));
+    }
+}
+
+impl Default for Extended {
+    fn default() -> Self {
+        Extended::new()
+    }
+}
+
+/// The extended DWT CYCCNT.
+static CYCCNT: Extended = Extended::new();
+
+/// A point in time, cycles since the counter was (last) reset.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    cycles: u64,
+}
+
+impl Instant {
+    /// Reads the extended DWT CYCCNT.
+    pub fn now() -> Self {
+        Instant::from_cycles(CYCCNT.now(&crate::clock::Cyccnt))
+    }
+
+    pub const fn from_cycles(cycles: u64) -> Self {
+        Instant { cycles }
+    }
+
+    pub const fn as_cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Time elapsed since this instant.
+    pub fn elapsed(&self) -> Duration {
+        Instant::now() - *self
+    }
+
+    /// Time elapsed from `earlier` to this instant.
+    ///
+    /// # Panics
+    ///
+    /// If `earlier` is later than this instant.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier)
+            .expect("`earlier` is later than `self`")
+    }
+
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.cycles
+            .checked_sub(earlier.cycles)
+            .map(Duration::from_cycles)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, d: Duration) -> Instant {
+        Instant::from_cycles(self.cycles + d.cycles)
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, d: Duration) {
+        *self = *self + d;
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    fn sub(self, d: Duration) -> Instant {
+        Instant::from_cycles(self.cycles - d.cycles)
+    }
+}
+
+impl SubAssign<Duration> for Instant {
+    fn sub_assign(&mut self, d: Duration) {
+        *self = *self - d;
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+/// A span of time, in cycles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Duration {
+    cycles: u64,
+}
+
+impl Duration {
+    pub const fn from_cycles(cycles: u64) -> Self {
+        Duration { cycles }
+    }
+
+    pub const fn as_cycles(&self) -> u64 {
+        self.cycles
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, d: Duration) -> Duration {
+        Duration::from_cycles(self.cycles + d.cycles)
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, d: Duration) -> Duration {
+        Duration::from_cycles(self.cycles - d.cycles)
+    }
+}
+
+/// Fails if the duration does not fit 32 bits (as used by the RTIC timer queue).
+impl TryFrom<Duration> for u32 {
+    type Error = core::num::TryFromIntError;
+
+    fn try_from(d: Duration) -> Result<u32, Self::Error> {
+        u32::try_from(d.cycles)
+    }
+}
+
+impl fmt::Display for Duration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} cycles", self.cycles)
+    }
+}
+
+/// `100_000.cycles()`, use `Duration::from_cycles` beyond 32 bits.
+pub trait U32Ext {
+    fn cycles(self) -> Duration;
+}
+
+impl U32Ext for u32 {
+    fn cycles(self) -> Duration {
+        Duration::from_cycles(self as u64)
+    }
+}
+
+/// The extended DWT CYCCNT as RTIC monotonic timer.
+#[cfg(feature = "rtic")]
+pub struct CYCCNT64;
+
+#[cfg(feature = "rtic")]
+impl rtic::Monotonic for CYCCNT64 {
+    type Instant = Instant;
+
+    fn ratio() -> rtic::Fraction {
+        rtic::Fraction {
+            numerator: 1,
+            denominator: 1,
+        }
+    }
+
+    fn now() -> Instant {
+        Instant::now()
+    }
+
+    unsafe fn reset() {
+        (*cortex_m::peripheral::DWT::ptr()).cyccnt.write(0);
+        CYCCNT.reset();
+    }
+
+    fn zero() -> Instant {
+        Instant::from_cycles(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::mock::MockClock;
+
+    #[test]
+    fn extends_over_wraps() {
+        let clock = MockClock::new(0);
+        let ext = Extended::new();
+        let mut expected = 0u64;
+        for _ in 0..10 {
+            clock.advance(TICK_PERIOD);
+            expected += TICK_PERIOD as u64;
+            assert_eq!(ext.now(&clock), expected);
+        }
+        assert_eq!(expected, 5 << 32);
+    }
+
+    #[test]
+    fn monotonic() {
+        let clock = MockClock::new(u32::MAX - 10);
+        let ext = Extended::new();
+        let a = ext.now(&clock);
+        clock.advance(20);
+        let b = ext.now(&clock);
+        assert_eq!(b - a, 20);
+        assert_eq!(b >> 32, 1);
+    }
+
+    #[test]
+    fn reset() {
+        let clock = MockClock::new(u32::MAX);
+        let ext = Extended::new();
+        ext.now(&clock);
+        clock.advance(1);
+        assert_eq!(ext.now(&clock), 1 << 32);
+        ext.reset();
+        assert_eq!(ext.now(&clock), 0);
+    }
+
+    #[test]
+    fn instant_arithmetic() {
+        let start = Instant::from_cycles(0);
+        let t1 = start + 100_000.cycles();
+        assert_eq!(t1.as_cycles(), 100_000);
+        let long = Duration::from_cycles(10_000_000_000);
+        let late = t1 + long;
+        assert_eq!(late.duration_since(t1), long);
+        assert_eq!(t1.checked_duration_since(late), None);
+        assert_eq!(late - long, t1);
+        assert!(late > t1);
+    }
+
+    #[test]
+    fn duration_to_u32() {
+        assert_eq!(u32::try_from(100_000.cycles()), Ok(100_000));
+        assert!(u32::try_from(Duration::from_cycles(1 << 32)).is_err());
+        assert_eq!(
+            (1_000u32.cycles() + 24.cycles() - 4.cycles()).to_string(),
+            "1020 cycles"
+        );
+    }
+}
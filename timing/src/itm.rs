@@ -0,0 +1,192 @@
+//! timing/src/itm.rs
+//!
+//! Tracing and logging over the ITM (Instrumentation Trace Macrocell),
+//! output on the SWO pin.
+//!
+//! Unlike RTT, the ITM needs no RAM buffers nor a probe polling the
+//! target, the stimulus ports are written directly, and the packets are
+//! shifted out by the TPIU. Ports are assigned by the application, by
+//! default:
+//!
+//! - [`LOG`] (port 0), text, `write!(Port::LOG, ..)`
+//! - [`TRACE`] (port 1), the binary records of a [`Tracer`], with
+//!   [`Port`] as its sink
+//!
+//! The TPIU (and the SWO baud rate) is configured by the debugger, e.g.,
+//! OpenOCD (see `openocd.gdb`), writing the raw SWO stream to `itm.txt`.
+//! [`enable`] configures the ITM itself, including the local timestamps
+//! (in cycles, relative to the previous timestamp packet) and the
+//! forwarding of DWT packets, e.g., the exception trace enabled by
+//! [`enable_exception_trace`] (a packet per handler entry, exit and
+//! return, without any instrumentation of the handlers).
+//!
+//! ```ignore
+//! cx.core.DCB.enable_trace();
+//! itm::enable(&mut cx.core.ITM, &itm::Config::default());
+//! TRACE.init(itm::Port::TRACE);
+//! writeln!(itm::Port::LOG, "init").ok();
+//! ```
+//!
+//! Writes busy-wait for room in the ITM FIFO, at the SWO bit rate. Text
+//! written from several priorities may interleave, trace records are
+//! written in the critical section of the tracer.
+//!
+//! The host side decoder is `host/itm`.
+//!
+//! [`Tracer`]: crate::trace::Tracer
+
+use crate::trace::Sink;
+use core::fmt;
+use cortex_m::peripheral::{DWT, ITM};
+
+/// Stimulus port of the text log.
+pub const LOG: u8 = 0;
+
+/// Stimulus port of the binary trace.
+pub const TRACE: u8 = 1;
+
+/// Key unlocking the ITM registers for writes.
+const UNLOCK: u32 = 0xc5ac_ce55;
+
+// ITM_TCR
+const ITMENA: u32 = 1 << 0;
+const TSENA: u32 = 1 << 1;
+const SYNCENA: u32 = 1 << 2;
+const TXENA: u32 = 1 << 3;
+
+// DWT_CTRL
+const EXCTRCENA: u32 = 1 << 16;
+
+/// ITM configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Enabled stimulus ports (bit n for port n, 0..32).
+    pub ports: u32,
+    /// Local timestamps.
+    pub timestamps: bool,
+    /// Timestamp prescaler (0..=3, divide by 1, 4, 16 or 64).
+    pub prescaler: u8,
+    /// Synchronization packets (at the rate set by DWT SYNCTAP).
+    pub sync: bool,
+    /// Forwarding of the DWT packets (exception trace, PC samples).
+    pub dwt: bool,
+    /// Trace bus id (non-zero, 1..=0x7f).
+    pub bus_id: u8,
+}
+
+impl Default for Config {
+    /// The [`LOG`] and [`TRACE`] ports, with timestamps and sync packets.
+    fn default() -> Self {
+        Config {
+            ports: 1 << LOG | 1 << TRACE,
+            timestamps: true,
+            prescaler: 0,
+            sync: true,
+            dwt: false,
+            bus_id: 1,
+        }
+    }
+}
+
+impl Config {
+    /// The value of the Trace Control Register.
+    pub fn tcr(&self) -> u32 {
+        let mut tcr =
+            ITMENA | (self.prescaler as u32 & 0b11) << 8 | (self.bus_id as u32 & 0x7f) << 16;
+        if self.timestamps {
+            tcr |= TSENA;
+        }
+        if self.sync {
+            tcr |= SYNCENA;
+        }
+        if self.dwt {
+            tcr |= TXENA;
+        }
+        tcr
+    }
+}
+
+/// Configures and enables the ITM, trace must be enabled in the DCB
+/// (`DCB::enable_trace`) and the TPIU configured by the debugger.
+pub fn enable(itm: &mut ITM, config: &Config) {
+    unsafe {
+        itm.lar.write(UNLOCK);
+        // disabled while configured
+        itm.tcr.modify(|tcr| tcr & !ITMENA);
+        // unprivileged access to all ports
+        itm.tpr.write(0);
+        itm.ter[0].write(config.ports);
+        itm.tcr.write(config.tcr());
+    }
+}
+
+/// Enables the DWT exception trace, forwarded by the ITM with `dwt` set
+/// in its [`Config`].
+pub fn enable_exception_trace(dwt: &mut DWT) {
+    unsafe { dwt.ctrl.modify(|ctrl| ctrl | EXCTRCENA) }
+}
+
+/// A stimulus port.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Port(pub u8);
+
+impl Port {
+    pub const LOG: Port = Port(LOG);
+    pub const TRACE: Port = Port(TRACE);
+
+    /// Port and ITM enabled, writes to a disabled port would never
+    /// complete.
+    pub fn enabled(&self) -> bool {
+        let itm = unsafe { &*ITM::ptr() };
+        itm.tcr.read() & ITMENA != 0 && itm.ter[0].read() & 1 << (self.0 & 31) != 0
+    }
+
+    /// Writes `bytes`, waiting for room in the FIFO.
+    pub fn write_all(&mut self, bytes: &[u8]) {
+        let stim = unsafe { &mut (*ITM::ptr()).stim[self.0 as usize & 31] };
+        cortex_m::itm::write_all(stim, bytes);
+    }
+}
+
+impl Sink for Port {
+    /// All of `bytes` if enabled, otherwise nothing (the record is
+    /// accounted as dropped).
+    fn write(&mut self, bytes: &[u8]) -> usize {
+        if self.enabled() {
+            self.write_all(bytes);
+            bytes.len()
+        } else {
+            0
+        }
+    }
+}
+
+impl fmt::Write for Port {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.enabled() {
+            self.write_all(s.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tcr() {
+        // ITMENA, TSENA, SYNCENA, bus id 1
+        assert_eq!(Config::default().tcr(), 0x0001_0007);
+        let config = Config {
+            ports: 1,
+            timestamps: false,
+            prescaler: 2,
+            sync: false,
+            dwt: true,
+            bus_id: 0x12,
+        };
+        assert_eq!(config.tcr(), 0x0012_0209);
+        assert_eq!(Config::default().ports, 0b11);
+    }
+}
@@ -0,0 +1,185 @@
+//! timing/src/jitter.rs
+//!
+//! Release jitter, i.e., the start latency of a task instance
+//! relative to its scheduled (release) time.
+//!
+//! Recorded on task entry, separate from the response time:
+//!
+//! ```ignore
+//! #[no_mangle]
+//! static T1_JIT: JitterMonitor = JitterMonitor::new("t1", 1_000);
+//!
+//! #[task(schedule = [t1], priority = 1)]
+//! fn t1(cx: t1::Context) {
+//!     T1_JIT.record(cx.scheduled.elapsed().as_cycles());
+//!     // ... workload
+//! }
+//! ```
+//!
+//! A jitter in the first histogram bucket is typically dispatch
+//! overhead only, larger values are due to preemption (or blocking).
+
+use crate::{calibrate, cs::Shared, stats::Stats};
+use core::fmt;
+
+/// Number of histogram buckets, the last bucket collects all
+/// samples beyond the range of the others.
+pub const BUCKETS: usize = 8;
+
+/// Histogram with buckets of equal width (in cycles).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct Histogram {
+    pub width: u32,
+    pub counts: [u32; BUCKETS],
+}
+
+impl Histogram {
+    /// # Panics
+    ///
+    /// If `width` is 0 (at compile time for a `static`).
+    pub const fn new(width: u32) -> Self {
+        assert!(width > 0, "histogram bucket width must be non-zero");
+        Histogram {
+            width,
+            counts: [0; BUCKETS],
+        }
+    }
+
+    /// Bucket index for `sample`.
+    pub fn bucket(&self, sample: u32) -> usize {
+        // `width` is non-zero, checked by `new`
+        ((sample / self.width) as usize).min(BUCKETS - 1)
+    }
+
+    pub fn record(&mut self, sample: u32) {
+        let i = self.bucket(sample);
+        self.counts[i] = self.counts[i].saturating_add(1);
+    }
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "hist/{} [", self.width)?;
+        for (i, count) in self.counts.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", count)?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// Release jitter statistics and histogram.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct Jitter {
+    pub stats: Stats,
+    pub histogram: Histogram,
+}
+
+/// Min/max/mean/count and histogram of the release jitter of a task.
+pub struct JitterMonitor {
+    name: &'static str,
+    jitter: Shared<Jitter>,
+}
+
+impl JitterMonitor {
+    /// `width` is the histogram bucket width in cycles, non-zero.
+    pub const fn new(name: &'static str, width: u32) -> Self {
+        JitterMonitor {
+            name,
+            jitter: Shared::new(Jitter {
+                stats: Stats::new(),
+                histogram: Histogram::new(width),
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Records the release jitter (in cycles), e.g., `cx.scheduled.elapsed().as_cycles()`
+    /// on task entry.
+    ///
+    /// Returns the recorded jitter, compensated for the calibrated
+    /// dispatch overhead.
+    pub fn record(&self, jitter: u32) -> u32 {
+        let jitter = calibrate::overheads().jitter(jitter);
+        self.jitter.with(|j| {
+            j.stats.record(jitter);
+            j.histogram.record(jitter);
+        });
+        jitter
+    }
+
+    pub fn jitter(&self) -> Jitter {
+        self.jitter.get()
+    }
+
+    pub fn reset(&self) {
+        self.jitter.with(|j| {
+            *j = Jitter {
+                stats: Stats::new(),
+                histogram: Histogram::new(j.histogram.width),
+            }
+        });
+    }
+}
+
+impl fmt::Display for JitterMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let jitter = self.jitter();
+        write!(f, "{}: {} {}", self.name, jitter.stats, jitter.histogram)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets() {
+        let h = Histogram::new(1_000);
+        assert_eq!(h.bucket(0), 0);
+        assert_eq!(h.bucket(999), 0);
+        assert_eq!(h.bucket(1_000), 1);
+        assert_eq!(h.bucket(7_999), 7);
+        assert_eq!(h.bucket(30_595), BUCKETS - 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "histogram bucket width must be non-zero")]
+    fn zero_width() {
+        Histogram::new(0);
+    }
+
+    #[test]
+    fn dispatch_vs_preemption() {
+        // t1 is released at 100_000 but starts at 130_595 (preempted by t3),
+        // later instances start after the dispatch overhead only
+        let m = JitterMonitor::new("t1", 1_000);
+        for jitter in &[30_595, 240, 251, 246] {
+            m.record(*jitter);
+        }
+        let j = m.jitter();
+        assert_eq!(j.stats.min, 240);
+        assert_eq!(j.stats.max, 30_595);
+        assert_eq!(j.histogram.counts, [3, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(
+            m.to_string(),
+            "t1: count 4 min 240 max 30595 mean 7833 hist/1000 [3 0 0 0 0 0 0 1]"
+        );
+    }
+
+    #[test]
+    fn reset_keeps_width() {
+        let m = JitterMonitor::new("t3", 100);
+        m.record(240);
+        m.reset();
+        assert_eq!(m.jitter().stats, Stats::new());
+        assert_eq!(m.jitter().histogram, Histogram::new(100));
+    }
+}
@@ -0,0 +1,43 @@
+//! timing/src/lib.rs
+//!
+//! Timing instrumentation for RTIC applications, based on the
+//! DWT cycle counter (CYCCNT).
+//!
+//! All bookkeeping is plain Rust, the only hardware access is
+//! confined to the [`Cyccnt`] clock. Thus the library can be unit
+//! tested on the host (against a mock clock) before flashing:
+//!
+//! > cargo test -p timing --target x86_64-unknown-linux-gnu
+
+#![cfg_attr(not(test), no_std)]
+
+pub mod accounting;
+pub mod burn;
+pub mod calibrate;
+pub mod clock;
+mod cs;
+pub mod deadline;
+pub mod instant;
+pub mod itm;
+pub mod jitter;
+pub mod lock;
+pub mod log;
+pub mod monitor;
+pub mod stats;
+pub mod trace;
+
+pub use accounting::{Accounting, ExecutionMonitor, RawLatency};
+pub use burn::{burn_cycles, BURN};
+pub use calibrate::CALIBRATION;
+pub use clock::{Clock, Cyccnt};
+pub use deadline::{DeadlineMonitor, Policy};
+pub use jitter::JitterMonitor;
+#[cfg(feature = "rtic")]
+pub use lock::InstrumentedLock;
+pub use lock::LockMonitor;
+pub use log::Logger;
+pub use monitor::ResponseTimeMonitor;
+pub use stats::Stats;
+pub use trace::Tracer;
+#[cfg(feature = "rtic")]
+pub use trace::TracedLock;
@@ -0,0 +1,217 @@
+//! timing/src/lock.rs
+//!
+//! Instrumented resource locks.
+//!
+//! A [`LockMonitor`] per resource records the hold time of its
+//! critical sections and, together with the [`Accounting`] stack,
+//! the blocking each higher priority task actually suffered due to
+//! the resource ceiling.
+//!
+//! Instrumentation is opt-in per lock (requires the `rtic` feature):
+//!
+//! ```ignore
+//! #[no_mangle]
+//! static R2_LOCK: LockMonitor = LockMonitor::new("R2", 3, &ACCOUNTING);
+//!
+//! cx.resources.R2.lock_instrumented(&R2_LOCK, |r2| {
+//!     // critical section
+//! });
+//! ```
+//!
+//! Blocking is only attributed to tasks bracketed by
+//! `ACCOUNTING.enter`/`exit`.
+
+use crate::{accounting::Accounting, accounting::DEPTH, clock::Clock, cs::Shared, stats::Stats};
+use core::fmt;
+
+/// Hold time statistics and worst blocking per (logical) priority.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct LockStats {
+    pub hold: Stats,
+    /// `blocking[p - 1]` is the worst blocking of a task at priority `p`.
+    pub blocking: [u32; DEPTH],
+}
+
+pub struct LockMonitor {
+    name: &'static str,
+    ceiling: u8,
+    accounting: &'static Accounting,
+    stats: Shared<LockStats>,
+}
+
+impl LockMonitor {
+    /// `ceiling` is the (logical) priority ceiling of the resource, i.e.,
+    /// the highest priority of the tasks accessing it.
+    pub const fn new(name: &'static str, ceiling: u8, accounting: &'static Accounting) -> Self {
+        LockMonitor {
+            name,
+            ceiling,
+            accounting,
+            stats: Shared::new(LockStats {
+                hold: Stats::new(),
+                blocking: [0; DEPTH],
+            }),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn ceiling(&self) -> u8 {
+        self.ceiling
+    }
+
+    /// Runs the critical section `f` (call it from inside the `lock`
+    /// closure) and records its hold time.
+    pub fn hold<C: Clock, R>(&'static self, clock: &C, f: impl FnOnce() -> R) -> R {
+        let acquire = clock.now();
+        let r = f();
+        let release = clock.now();
+        self.stats
+            .with(|s| s.hold.record(release.wrapping_sub(acquire)));
+        self.accounting.locked(self, acquire, release);
+        r
+    }
+
+    pub(crate) fn record_blocking(&self, priority: u8, blocking: u32) {
+        if let Some(i) = (priority as usize).checked_sub(1).filter(|i| *i < DEPTH) {
+            self.stats
+                .with(|s| s.blocking[i] = s.blocking[i].max(blocking));
+        }
+    }
+
+    pub fn stats(&self) -> LockStats {
+        self.stats.get()
+    }
+
+    /// The longest critical section observed.
+    pub fn max_hold(&self) -> u32 {
+        self.stats().hold.max
+    }
+
+    /// The worst blocking observed for a task at `priority`.
+    pub fn blocking(&self, priority: u8) -> u32 {
+        match (priority as usize).checked_sub(1) {
+            Some(i) if i < DEPTH => self.stats().blocking[i],
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for LockMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stats = self.stats();
+        write!(
+            f,
+            "{}: ceiling {} hold {}",
+            self.name, self.ceiling, stats.hold
+        )?;
+        for (i, blocking) in stats.blocking.iter().enumerate() {
+            if *blocking > 0 {
+                write!(f, ", blocking p{} {}", i + 1, blocking)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `lock` with instrumentation, for any RTIC resource proxy.
+#[cfg(feature = "rtic")]
+pub trait InstrumentedLock: rtic::Mutex {
+    fn lock_instrumented<R>(
+        &mut self,
+        monitor: &'static LockMonitor,
+        f: impl FnOnce(&mut Self::T) -> R,
+    ) -> R {
+        self.lock(|t| monitor.hold(&crate::clock::Cyccnt, || f(t)))
+    }
+}
+
+#[cfg(feature = "rtic")]
+impl<M: rtic::Mutex> InstrumentedLock for M {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{accounting::RawLatency, clock::mock::MockClock};
+
+    fn leak<T>(t: T) -> &'static T {
+        Box::leak(Box::new(t))
+    }
+
+    #[test]
+    fn hold_time() {
+        let clock = MockClock::new(0);
+        let r2 = leak(LockMonitor::new("R2", 3, leak(Accounting::new())));
+        for cycles in &[4_000, 4_010, 3_990] {
+            r2.hold(&clock, || clock.advance(*cycles));
+        }
+        assert_eq!(r2.max_hold(), 4_010);
+        assert_eq!(r2.stats().hold.min, 3_990);
+        assert_eq!(
+            r2.to_string(),
+            "R2: ceiling 3 hold count 3 min 3990 max 4010 mean 4000"
+        );
+    }
+
+    #[test]
+    fn blocking() {
+        // t2 (priority 2) locks R2 [2_000, 6_000], t3 (priority 3) released at 3_000
+        let clock = MockClock::new(0);
+        let acc = leak(Accounting::new());
+        let r2 = leak(LockMonitor::new("R2", 3, acc));
+
+        acc.enter(&clock, 2, RawLatency(0));
+        clock.advance(2_000);
+        r2.hold(&clock, || clock.advance(4_000));
+        clock.advance(10);
+        acc.enter(&clock, 3, RawLatency(3_010));
+        clock.advance(30_000);
+        let t3 = acc.exit(&clock);
+        acc.exit(&clock);
+
+        assert_eq!(t3.blocking, 3_010);
+        assert_eq!(r2.blocking(3), 3_000);
+        assert_eq!(r2.blocking(2), 0);
+        assert!(r2.to_string().ends_with(", blocking p3 3000"));
+    }
+
+    #[test]
+    fn no_blocking_above_ceiling() {
+        // R1 (ceiling 2) does not block a task at priority 3
+        let clock = MockClock::new(0);
+        let acc = leak(Accounting::new());
+        let r1 = leak(LockMonitor::new("R1", 2, acc));
+
+        acc.enter(&clock, 1, RawLatency(0));
+        r1.hold(&clock, || clock.advance(1_000));
+        acc.enter(&clock, 3, RawLatency(500));
+        acc.exit(&clock);
+        acc.exit(&clock);
+
+        assert_eq!(r1.stats().blocking, [0; DEPTH]);
+    }
+
+    #[test]
+    fn interference_after_unlock() {
+        // t1 holds R (ceiling 3) [0, 500], t3 (released 100) runs [500, 600],
+        // t2 (released 200) starts at 600, both blocked by R
+        let clock = MockClock::new(0);
+        let acc = leak(Accounting::new());
+        let r = leak(LockMonitor::new("R", 3, acc));
+
+        acc.enter(&clock, 1, RawLatency(0));
+        r.hold(&clock, || clock.advance(500));
+        acc.enter(&clock, 3, RawLatency(400));
+        clock.advance(100);
+        acc.exit(&clock);
+        acc.enter(&clock, 2, RawLatency(400));
+        acc.exit(&clock);
+        acc.exit(&clock);
+
+        assert_eq!(r.blocking(3), 400);
+        assert_eq!(r.blocking(2), 300);
+    }
+}
@@ -0,0 +1,497 @@
+//! timing/src/log.rs
+//!
+//! Deferred formatting logging.
+//!
+//! `rprintln!` formats on the target, the formatting machinery costs
+//! flash and many cycles at the priority of the task. Here the format
+//! string is interned at compile time, into the `.log` section of the
+//! image, and only its index (the address in the section) and the raw
+//! arguments are written to the [`Sink`], a frame per message:
+//!
+//! ```text
+//! [len] [index, 2 bytes] [CYCCNT, 4 bytes] [args, len - 7 bytes]
+//! ```
+//!
+//! all little endian. Each argument is a type tag ([`U8`] .. [`BYTES`])
+//! followed by its value, strings and byte slices by a length byte and
+//! the bytes. Arguments that do not fit the frame ([`MAX`] bytes) are
+//! dropped, strings are cut to fit.
+//!
+//! `.log` is an `INFO` section at address 0 (see `log.x`, linked by
+//! `-Tlog.x` in `.cargo/config`), it takes no flash. Each entry is
+//!
+//! ```text
+//! [level] [format string] 0 [file:line] 0
+//! ```
+//!
+//! The host decoder (`host/log`) reads the entries from the ELF image
+//! and formats the messages.
+//!
+//! ```ignore
+//! static LOG: Logger<UpChannel, Cyccnt> = Logger::new(Cyccnt);
+//!
+//! let channels = rtt_init! {
+//!     up: {
+//!         0: { size: 1024 mode: NoBlockSkip name: "Log" }
+//!     }
+//! };
+//! LOG.init(channels.up.0);
+//! info!(LOG, "init, {} tasks", 3);
+//! warn!(LOG, "{}: response {} > deadline {}", "t2", rt, deadline);
+//! ```
+//!
+//! Levels are filtered at compile time, messages below [`LEVEL`] are
+//! removed along with their format strings and arguments. The level is
+//! that of the most verbose `log-*` feature of `timing` (`log-trace`,
+//! `log-debug`, `log-info`, `log-warn` and `log-error`), `Info` without
+//! any.
+//!
+//! Dropped frames are counted, and reported by an [`OVERFLOW`] frame (the
+//! count as a 4 byte argument) once the sink has room again.
+
+use crate::{clock::Clock, cs::Shared, trace::Sink};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum Level {
+    Trace = 0,
+    Debug = 1,
+    Info = 2,
+    Warn = 3,
+    Error = 4,
+}
+
+/// The lowest level logged, by the `log-*` features.
+pub const LEVEL: Level = if cfg!(feature = "log-trace") {
+    Level::Trace
+} else if cfg!(feature = "log-debug") {
+    Level::Debug
+} else if cfg!(feature = "log-info") {
+    Level::Info
+} else if cfg!(feature = "log-warn") {
+    Level::Warn
+} else if cfg!(feature = "log-error") {
+    Level::Error
+} else {
+    Level::Info
+};
+
+// argument tags
+pub const U8: u8 = 0x01;
+pub const U16: u8 = 0x02;
+pub const U32: u8 = 0x03;
+pub const U64: u8 = 0x04;
+pub const I8: u8 = 0x05;
+pub const I16: u8 = 0x06;
+pub const I32: u8 = 0x07;
+pub const I64: u8 = 0x08;
+pub const BOOL: u8 = 0x09;
+pub const F32: u8 = 0x0a;
+pub const STR: u8 = 0x0b;
+pub const BYTES: u8 = 0x0c;
+
+/// Index of the overflow frame, not an entry.
+pub const OVERFLOW: u16 = 0xffff;
+
+/// Size of the frame header (length, index and timestamp).
+pub const HEADER: usize = 7;
+
+/// Maximum size of a frame.
+pub const MAX: usize = 64;
+
+/// A message under construction.
+pub struct Frame {
+    buf: [u8; MAX],
+    len: usize,
+}
+
+impl Frame {
+    pub fn new(index: u16, time: u32) -> Self {
+        let mut buf = [0; MAX];
+        buf[1..3].copy_from_slice(&index.to_le_bytes());
+        buf[3..7].copy_from_slice(&time.to_le_bytes());
+        Frame { buf, len: HEADER }
+    }
+
+    /// Appends an argument, dropped unless it fits as a whole.
+    pub fn arg(&mut self, tag: u8, value: &[u8]) {
+        if self.len + 1 + value.len() <= MAX {
+            self.buf[self.len] = tag;
+            self.buf[self.len + 1..self.len + 1 + value.len()].copy_from_slice(value);
+            self.len += 1 + value.len();
+        }
+    }
+
+    /// Appends a length prefixed argument, cut to fit.
+    pub fn slice(&mut self, tag: u8, value: &[u8]) {
+        if self.len + 2 <= MAX {
+            let n = value.len().min(MAX - self.len - 2).min(255);
+            self.buf[self.len] = tag;
+            self.buf[self.len + 1] = n as u8;
+            self.buf[self.len + 2..self.len + 2 + n].copy_from_slice(&value[..n]);
+            self.len += 2 + n;
+        }
+    }
+
+    pub fn bytes(&mut self) -> &[u8] {
+        self.buf[0] = self.len as u8;
+        &self.buf[..self.len]
+    }
+}
+
+/// An argument of a message.
+pub trait Format {
+    fn format(&self, frame: &mut Frame);
+}
+
+macro_rules! fixed {
+    ($($t:ty => $tag:ident),*) => {
+        $(
+            impl Format for $t {
+                #[inline]
+                fn format(&self, frame: &mut Frame) {
+                    frame.arg($tag, &self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+fixed!(u8 => U8, u16 => U16, u32 => U32, u64 => U64, i8 => I8, i16 => I16,
+    i32 => I32, i64 => I64, f32 => F32);
+
+/// As on the target, 32 bits.
+impl Format for usize {
+    fn format(&self, frame: &mut Frame) {
+        (*self as u32).format(frame);
+    }
+}
+
+/// As on the target, 32 bits.
+impl Format for isize {
+    fn format(&self, frame: &mut Frame) {
+        (*self as i32).format(frame);
+    }
+}
+
+impl Format for bool {
+    fn format(&self, frame: &mut Frame) {
+        frame.arg(BOOL, &[*self as u8]);
+    }
+}
+
+impl Format for str {
+    fn format(&self, frame: &mut Frame) {
+        frame.slice(STR, self.as_bytes());
+    }
+}
+
+impl Format for [u8] {
+    fn format(&self, frame: &mut Frame) {
+        frame.slice(BYTES, self);
+    }
+}
+
+impl<T: Format + ?Sized> Format for &T {
+    fn format(&self, frame: &mut Frame) {
+        (**self).format(frame);
+    }
+}
+
+struct State<S> {
+    sink: Option<S>,
+    dropped: u32,
+}
+
+/// The logger, one per application. Messages before `init` are ignored.
+pub struct Logger<S, C> {
+    state: Shared<State<S>>,
+    clock: C,
+}
+
+impl<S, C> Logger<S, C> {
+    pub const fn new(clock: C) -> Self {
+        Logger {
+            state: Shared::new(State {
+                sink: None,
+                dropped: 0,
+            }),
+            clock,
+        }
+    }
+}
+
+impl<S: Sink, C: Clock> Logger<S, C> {
+    pub fn init(&self, sink: S) {
+        self.state.with(|s| s.sink = Some(sink));
+    }
+
+    /// Frames dropped so far.
+    pub fn dropped(&self) -> u32 {
+        self.state.with(|s| s.dropped)
+    }
+
+    /// Logs the interned `entry`, used by the macros.
+    #[inline]
+    pub fn log(&self, entry: &'static [u8], args: impl FnOnce(&mut Frame)) {
+        // the address in `.log` (at 0)
+        self.frame(entry.as_ptr() as usize as u16, args);
+    }
+
+    fn frame(&self, index: u16, args: impl FnOnce(&mut Frame)) {
+        let mut frame = Frame::new(index, self.clock.now());
+        args(&mut frame);
+        self.state.with(|s| {
+            if let Some(sink) = &mut s.sink {
+                if s.dropped > 0 {
+                    let mut overflow = Frame::new(OVERFLOW, self.clock.now());
+                    overflow.arg(U32, &s.dropped.to_le_bytes());
+                    let bytes = overflow.bytes();
+                    if sink.write(bytes) == bytes.len() {
+                        s.dropped = 0;
+                    }
+                }
+                let bytes = frame.bytes();
+                if s.dropped > 0 || sink.write(bytes) < bytes.len() {
+                    s.dropped += 1;
+                }
+            }
+        });
+    }
+}
+
+/// Logs a message at `level`, the format string interned in `.log`.
+///
+/// The format string is that of `core::fmt` (`{}`, `{:?}`, `{:x}`,
+/// `{:#010b}`, ..), but formatted by the host. Arguments implement
+/// [`Format`] (integers, `f32`, `bool`, `str` and `[u8]`).
+///
+/// Not filtered by [`LEVEL`], use the level macros ([`trace!`] ..
+/// [`error!`]), those below [`LEVEL`] expand to nothing.
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $logger:expr, $format:literal $(, $arg:expr)* $(,)?) => {
+        {
+            const ENTRY: &str = concat!($format, "\0", file!(), ":", line!());
+            #[allow(unsafe_code)]
+            #[link_section = ".log"]
+            static INTERNED: [u8; ENTRY.len() + 2] = {
+                let mut interned = [0; ENTRY.len() + 2];
+                interned[0] = $level as u8;
+                let mut i = 0;
+                while i < ENTRY.len() {
+                    interned[i + 1] = ENTRY.as_bytes()[i];
+                    i += 1;
+                }
+                interned
+            };
+            $logger.log(&INTERNED, |_frame| {
+                $($crate::log::Format::format(&$arg, _frame);)*
+            });
+        }
+    };
+}
+
+// The level macros, removed by `#[cfg]` (of `timing`) below `LEVEL`.
+
+#[cfg(feature = "log-trace")]
+#[macro_export]
+macro_rules! trace {
+    ($($t:tt)*) => { $crate::log!($crate::log::Level::Trace, $($t)*) };
+}
+
+#[cfg(not(feature = "log-trace"))]
+#[macro_export]
+macro_rules! trace {
+    ($($t:tt)*) => {};
+}
+
+#[cfg(any(feature = "log-trace", feature = "log-debug"))]
+#[macro_export]
+macro_rules! debug {
+    ($($t:tt)*) => { $crate::log!($crate::log::Level::Debug, $($t)*) };
+}
+
+#[cfg(not(any(feature = "log-trace", feature = "log-debug")))]
+#[macro_export]
+macro_rules! debug {
+    ($($t:tt)*) => {};
+}
+
+#[cfg(any(
+    feature = "log-trace",
+    feature = "log-debug",
+    feature = "log-info",
+    not(any(feature = "log-warn", feature = "log-error"))
+))]
+#[macro_export]
+macro_rules! info {
+    ($($t:tt)*) => { $crate::log!($crate::log::Level::Info, $($t)*) };
+}
+
+#[cfg(not(any(
+    feature = "log-trace",
+    feature = "log-debug",
+    feature = "log-info",
+    not(any(feature = "log-warn", feature = "log-error"))
+)))]
+#[macro_export]
+macro_rules! info {
+    ($($t:tt)*) => {};
+}
+
+#[cfg(any(
+    feature = "log-trace",
+    feature = "log-debug",
+    feature = "log-info",
+    feature = "log-warn",
+    not(feature = "log-error")
+))]
+#[macro_export]
+macro_rules! warn {
+    ($($t:tt)*) => { $crate::log!($crate::log::Level::Warn, $($t)*) };
+}
+
+#[cfg(not(any(
+    feature = "log-trace",
+    feature = "log-debug",
+    feature = "log-info",
+    feature = "log-warn",
+    not(feature = "log-error")
+)))]
+#[macro_export]
+macro_rules! warn {
+    ($($t:tt)*) => {};
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($t:tt)*) => { $crate::log!($crate::log::Level::Error, $($t)*) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::mock::MockClock, trace::mock::Buffer};
+    use std::vec::Vec;
+
+    fn bytes(logger: &Logger<Buffer, MockClock>) -> Vec<u8> {
+        logger.state.with(|s| s.sink.as_ref().unwrap().0.clone())
+    }
+
+    #[test]
+    fn frames() {
+        let mut f = Frame::new(0x0123, 0x1234_5678);
+        1u8.format(&mut f);
+        (-2i16).format(&mut f);
+        true.format(&mut f);
+        "t1".format(&mut f);
+        assert_eq!(
+            f.bytes(),
+            &[
+                18, 0x23, 0x01, 0x78, 0x56, 0x34, 0x12, U8, 1, I16, 0xfe, 0xff, BOOL, 1, STR, 2,
+                b't', b'1'
+            ][..]
+        );
+    }
+
+    #[test]
+    fn full() {
+        let mut f = Frame::new(0, 0);
+        // cut to fit
+        "x".repeat(100).as_str().format(&mut f);
+        assert_eq!(f.bytes().len(), MAX);
+        assert_eq!(f.bytes()[HEADER + 1] as usize, MAX - HEADER - 2);
+        // dropped
+        1u32.format(&mut f);
+        assert_eq!(f.bytes().len(), MAX);
+    }
+
+    #[test]
+    #[cfg(not(any(
+        feature = "log-trace",
+        feature = "log-debug",
+        feature = "log-info",
+        feature = "log-warn",
+        feature = "log-error"
+    )))]
+    fn levels() {
+        // the default, without `log-*` features
+        assert_eq!(LEVEL, Level::Info);
+        // below, nothing left of the message, not even its entry
+        let logger = Logger::new(MockClock::new(10));
+        logger.init(Buffer(Vec::new(), 1024));
+        crate::trace!(logger, "{}", undefined);
+        crate::debug!(logger, "{}", undefined);
+        assert!(bytes(&logger).is_empty());
+    }
+
+    #[test]
+    fn macros() {
+        let logger = Logger::new(MockClock::new(10));
+        logger.init(Buffer(Vec::new(), 1024));
+        crate::error!(logger, "{} {}", 1u8, "t2");
+        let b = bytes(&logger);
+        assert_eq!(b[0] as usize, b.len());
+        assert_eq!(&b[3..], &[10, 0, 0, 0, U8, 1, STR, 2, b't', b'2']);
+    }
+
+    #[test]
+    fn capture() {
+        // the encoding of the capture decoded by the host tests (as
+        // generated by `host/fixtures`), indices of the entries of
+        // `examples/timing_log.rs` in its `.log` section
+        let (init, count, response, state) = (0, 97, 157, 211);
+        let logger = Logger::new(MockClock::new(1093));
+        logger.init(Buffer(Vec::new(), 1024));
+        logger.frame(init, |f| 100_000u32.format(f));
+        let mut now = 1093;
+        let mut at = |t: u32| {
+            logger.clock.advance(t - now);
+            now = t;
+        };
+        for (n, rt) in [(1u32, 10_210u32), (2, 20_210), (3, 30_210)].iter() {
+            at(n * 100_000 + 1342);
+            logger.frame(count, |f| {
+                n.format(f);
+                rt.format(f);
+            });
+        }
+        at(301_401);
+        logger.frame(response, |f| {
+            30_210u32.format(f);
+            25_000u32.format(f);
+        });
+        // two frames lost, reported before the next
+        logger.state.with(|s| s.dropped = 2);
+        at(801_342);
+        logger.frame(count, |f| {
+            8u32.format(f);
+            10_210u32.format(f);
+        });
+        at(801_380);
+        logger.frame(state, |f| {
+            "state".format(f);
+            [0xc0u8, 0xff, 0xee][..].format(f);
+        });
+        let expected: &[u8] = include_bytes!("../../host/log/captures/log.bin");
+        assert_eq!(bytes(&logger), expected);
+    }
+
+    #[test]
+    fn overflow() {
+        let logger = Logger::new(MockClock::new(0));
+        logger.init(Buffer(Vec::new(), 2 * HEADER));
+        for _ in 0..4 {
+            logger.frame(0, |_| {});
+        }
+        assert_eq!(logger.dropped(), 2);
+        logger.state.with(|s| s.sink.as_mut().unwrap().1 = 1024);
+        logger.frame(1, |_| {});
+        assert_eq!(logger.dropped(), 0);
+        assert_eq!(
+            &bytes(&logger)[2 * HEADER..],
+            &[12, 0xff, 0xff, 0, 0, 0, 0, U32, 2, 0, 0, 0, 7, 1, 0, 0, 0, 0, 0]
+        );
+    }
+}
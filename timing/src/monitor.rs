@@ -0,0 +1,142 @@
+//! timing/src/monitor.rs
+//!
+//! Per task response time monitoring.
+//!
+//! One monitor is declared per task, the symbol name keys the task,
+//! and `#[no_mangle]` keeps it readable for `gdb` (and vscode WATCHES).
+//!
+//! ```ignore
+//! #[no_mangle]
+//! static T1_RT: ResponseTimeMonitor = ResponseTimeMonitor::new("t1");
+//!
+//! #[task(schedule = [t1], priority = 1)]
+//! fn t1(cx: t1::Context) {
+//!     // ... workload
+//!     T1_RT.record(cx.scheduled.elapsed().as_cycles());
+//! }
+//! ```
+
+use crate::{
+    calibrate::{self, Overheads},
+    clock::Clock,
+    cs::Shared,
+    stats::Stats,
+};
+use core::fmt;
+
+/// Min/max/mean/count of the response times of a task.
+///
+/// Recording is done in a (short) critical section, so a monitor
+/// can be safely updated and read from any RTIC priority.
+pub struct ResponseTimeMonitor {
+    name: &'static str,
+    stats: Shared<Stats>,
+}
+
+impl ResponseTimeMonitor {
+    pub const fn new(name: &'static str) -> Self {
+        ResponseTimeMonitor {
+            name,
+            stats: Shared::new(Stats::new()),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Records a response time (in cycles), e.g., `cx.scheduled.elapsed().as_cycles()`.
+    ///
+    /// Returns the recorded response time, compensated for the
+    /// calibrated clock read overhead.
+    pub fn record(&self, response: u32) -> u32 {
+        self.record_with(response, &calibrate::overheads())
+    }
+
+    pub(crate) fn record_with(&self, response: u32, overheads: &Overheads) -> u32 {
+        let response = overheads.response(response);
+        self.stats.with(|stats| stats.record(response));
+        response
+    }
+
+    /// Records the response time of a job released at `release`,
+    /// measured as the (wrapping) time elapsed on `clock`.
+    pub fn record_since<C: Clock>(&self, clock: &C, release: u32) -> u32 {
+        self.record(clock.now().wrapping_sub(release))
+    }
+
+    /// A snapshot of the statistics recorded so far.
+    pub fn stats(&self) -> Stats {
+        self.stats.get()
+    }
+
+    /// The maximum observed response time.
+    pub fn max(&self) -> u32 {
+        self.stats().max
+    }
+
+    pub fn reset(&self) {
+        self.stats.with(|stats| *stats = Stats::new());
+    }
+}
+
+impl fmt::Display for ResponseTimeMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.stats())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::mock::MockClock;
+
+    #[test]
+    fn empty() {
+        let m = ResponseTimeMonitor::new("t");
+        assert_eq!(m.stats().count, 0);
+        assert_eq!(m.stats().mean(), None);
+        assert_eq!(m.to_string(), "t: count 0");
+    }
+
+    #[test]
+    fn min_max_mean_count() {
+        let m = ResponseTimeMonitor::new("t1");
+        for rt in &[30_362, 30_426, 40_655] {
+            m.record(*rt);
+        }
+        let stats = m.stats();
+        assert_eq!(stats.count, 3);
+        assert_eq!(stats.min, 30_362);
+        assert_eq!(stats.max, 40_655);
+        assert_eq!(stats.mean(), Some(33_814));
+        assert_eq!(m.to_string(), "t1: count 3 min 30362 max 40655 mean 33814");
+    }
+
+    #[test]
+    fn mock_clock() {
+        let clock = MockClock::new(100_000);
+        let m = ResponseTimeMonitor::new("t1");
+        let release = clock.now();
+        clock.advance(10_240);
+        assert_eq!(m.record_since(&clock, release), 10_240);
+        assert_eq!(m.max(), 10_240);
+    }
+
+    #[test]
+    fn wrapping_clock() {
+        let clock = MockClock::new(u32::MAX - 99);
+        let m = ResponseTimeMonitor::new("t1");
+        let release = clock.now();
+        clock.advance(300);
+        assert_eq!(m.record_since(&clock, release), 300);
+    }
+
+    #[test]
+    fn reset() {
+        let m = ResponseTimeMonitor::new("t1");
+        m.record(1);
+        m.reset();
+        assert_eq!(m.stats(), Stats::new());
+    }
+}
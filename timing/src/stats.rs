@@ -0,0 +1,92 @@
+//! timing/src/stats.rs
+//!
+//! Running statistics over cycle counts.
+
+use core::fmt;
+
+/// Count, min, max and sum of recorded samples (in cycles).
+///
+/// `#[repr(C)]` to keep the layout simple for the debugger and for
+/// binary memory dumps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(C)]
+pub struct Stats {
+    pub count: u32,
+    pub min: u32,
+    pub max: u32,
+    pub sum: u64,
+}
+
+impl Stats {
+    pub const fn new() -> Self {
+        Stats {
+            count: 0,
+            min: u32::MAX,
+            max: 0,
+            sum: 0,
+        }
+    }
+
+    /// Records `sample`. Once `count` saturates the mean is that of the
+    /// first `u32::MAX` samples (`count` and `sum` stop together), min and
+    /// max still cover all.
+    pub fn record(&mut self, sample: u32) {
+        if self.count < u32::MAX {
+            self.count += 1;
+            self.sum += sample as u64;
+        }
+        if sample < self.min {
+            self.min = sample;
+        }
+        if sample > self.max {
+            self.max = sample;
+        }
+    }
+
+    /// Mean of the recorded samples, `None` if nothing recorded yet.
+    pub fn mean(&self) -> Option<u32> {
+        if self.count == 0 {
+            None
+        } else {
+            Some((self.sum / self.count as u64) as u32)
+        }
+    }
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Stats::new()
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.mean() {
+            None => write!(f, "count 0"),
+            Some(mean) => write!(
+                f,
+                "count {} min {} max {} mean {}",
+                self.count, self.min, self.max, mean
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saturated() {
+        let mut s = Stats::new();
+        s.record(10);
+        s.count = u32::MAX - 1;
+        s.sum = 10 * (u32::MAX - 1) as u64;
+        s.record(10);
+        assert_eq!((s.count, s.mean()), (u32::MAX, Some(10)));
+        // neither count nor sum move, the mean holds
+        s.record(1_000_000);
+        assert_eq!((s.count, s.mean()), (u32::MAX, Some(10)));
+        assert_eq!((s.min, s.max), (10, 1_000_000));
+    }
+}
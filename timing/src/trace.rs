@@ -0,0 +1,317 @@
+//! timing/src/trace.rs
+//!
+//! Binary task event tracing.
+//!
+//! Each event is a 6 byte record, written in a critical section (so
+//! records are in time order) to a [`Sink`], typically an RTT up-channel
+//! of its own in `NoBlockSkip` mode (a record either fits the buffer or
+//! is dropped as a whole):
+//!
+//! ```text
+//! [tag] [id] [CYCCNT, 4 bytes little endian]
+//! ```
+//!
+//! `id` is the task (for `ENTER`, `EXIT`, `PEND` and `SCHEDULE`) or the
+//! resource (for `LOCK` and `UNLOCK`), numbered by the application. Names
+//! are announced once (after `init` of the tracer), without timestamp:
+//!
+//! ```text
+//! [TASK_NAME] [id] [len] [name, len bytes]
+//! [RESOURCE_NAME] [id] [ceiling] [len] [name, len bytes]
+//! ```
+//!
+//! Dropped records are counted, and reported by an `OVERFLOW` record
+//! (`id` the count, saturated at 255) once the buffer has room again.
+//!
+//! ```ignore
+//! static TRACE: Tracer<UpChannel> = Tracer::new();
+//!
+//! let channels = rtt_init! {
+//!     up: {
+//!         0: { size: 1024 mode: NoBlockSkip name: "Terminal" }
+//!         1: { size: 4096 mode: NoBlockSkip name: "Trace" }
+//!     }
+//! };
+//! TRACE.init(channels.up.1);
+//! TRACE.task_name(0, "t1");
+//! TRACE.resource_name(0, "R1", 2);
+//!
+//! TRACE.enter(&Cyccnt, 0);
+//! cx.resources.R1.lock_traced(&TRACE, 0, |r1| { /* ... */ });
+//! TRACE.exit(&Cyccnt, 0);
+//! ```
+//!
+//! The host side decoder is `host/rtic_trace` (`--binary`).
+
+use crate::{clock::Clock, cs::Shared};
+
+pub const ENTER: u8 = 0x01;
+pub const EXIT: u8 = 0x02;
+pub const LOCK: u8 = 0x03;
+pub const UNLOCK: u8 = 0x04;
+pub const PEND: u8 = 0x05;
+pub const SCHEDULE: u8 = 0x06;
+pub const OVERFLOW: u8 = 0x07;
+pub const TASK_NAME: u8 = 0x10;
+pub const RESOURCE_NAME: u8 = 0x11;
+
+/// Size of a timestamped record.
+pub const RECORD: usize = 6;
+
+/// Destination of the trace records.
+pub trait Sink {
+    /// Writes `bytes` (all or nothing), returns the number written.
+    fn write(&mut self, bytes: &[u8]) -> usize;
+}
+
+#[cfg(feature = "rtt")]
+impl Sink for rtt_target::UpChannel {
+    fn write(&mut self, bytes: &[u8]) -> usize {
+        rtt_target::UpChannel::write(self, bytes)
+    }
+}
+
+struct State<S> {
+    sink: Option<S>,
+    dropped: u32,
+}
+
+/// The tracer, one per application. Events before `init` are ignored.
+pub struct Tracer<S> {
+    state: Shared<State<S>>,
+}
+
+impl<S> Tracer<S> {
+    pub const fn new() -> Self {
+        Tracer {
+            state: Shared::new(State {
+                sink: None,
+                dropped: 0,
+            }),
+        }
+    }
+}
+
+impl<S: Sink> Tracer<S> {
+    pub fn init(&self, sink: S) {
+        self.state.with(|s| s.sink = Some(sink));
+    }
+
+    /// Announces the name of task `id`.
+    pub fn task_name(&self, id: u8, name: &str) {
+        self.name(&[TASK_NAME, id], name);
+    }
+
+    /// Announces the name and (logical) priority ceiling of resource `id`.
+    pub fn resource_name(&self, id: u8, name: &str, ceiling: u8) {
+        self.name(&[RESOURCE_NAME, id, ceiling], name);
+    }
+
+    /// Task body entered.
+    pub fn enter<C: Clock>(&self, clock: &C, task: u8) {
+        self.record(clock, ENTER, task);
+    }
+
+    /// Task body left.
+    pub fn exit<C: Clock>(&self, clock: &C, task: u8) {
+        self.record(clock, EXIT, task);
+    }
+
+    /// Critical section entered.
+    pub fn lock<C: Clock>(&self, clock: &C, resource: u8) {
+        self.record(clock, LOCK, resource);
+    }
+
+    /// Critical section left.
+    pub fn unlock<C: Clock>(&self, clock: &C, resource: u8) {
+        self.record(clock, UNLOCK, resource);
+    }
+
+    /// Task pended (spawned, or released by the timer queue).
+    pub fn pend<C: Clock>(&self, clock: &C, task: u8) {
+        self.record(clock, PEND, task);
+    }
+
+    /// Task scheduled (inserted in the timer queue).
+    pub fn schedule<C: Clock>(&self, clock: &C, task: u8) {
+        self.record(clock, SCHEDULE, task);
+    }
+
+    /// Records dropped so far.
+    pub fn dropped(&self) -> u32 {
+        self.state.with(|s| s.dropped)
+    }
+
+    fn record<C: Clock>(&self, clock: &C, tag: u8, id: u8) {
+        self.state.with(|s| {
+            if let Some(sink) = &mut s.sink {
+                let now = clock.now();
+                if s.dropped > 0 {
+                    let count = s.dropped.min(255) as u8;
+                    if sink.write(&record(OVERFLOW, count, now)) == RECORD {
+                        s.dropped -= count as u32;
+                    }
+                }
+                if s.dropped > 0 || sink.write(&record(tag, id, now)) < RECORD {
+                    s.dropped += 1;
+                }
+            }
+        });
+    }
+
+    fn name(&self, header: &[u8], name: &str) {
+        let name = &name.as_bytes()[..name.len().min(32)];
+        let mut buf = [0; 3 + 1 + 32];
+        buf[..header.len()].copy_from_slice(header);
+        buf[header.len()] = name.len() as u8;
+        let len = header.len() + 1 + name.len();
+        buf[header.len() + 1..len].copy_from_slice(name);
+        self.state.with(|s| {
+            if let Some(sink) = &mut s.sink {
+                if sink.write(&buf[..len]) < len {
+                    s.dropped += 1;
+                }
+            }
+        });
+    }
+}
+
+impl<S: Sink> Default for Tracer<S> {
+    fn default() -> Self {
+        Tracer::new()
+    }
+}
+
+fn record(tag: u8, id: u8, time: u32) -> [u8; RECORD] {
+    let t = time.to_le_bytes();
+    [tag, id, t[0], t[1], t[2], t[3]]
+}
+
+/// `lock` with tracing, for any RTIC resource proxy.
+#[cfg(feature = "rtic")]
+pub trait TracedLock: rtic::Mutex {
+    fn lock_traced<S: Sink, R>(
+        &mut self,
+        tracer: &Tracer<S>,
+        resource: u8,
+        f: impl FnOnce(&mut Self::T) -> R,
+    ) -> R {
+        self.lock(|t| {
+            tracer.lock(&crate::clock::Cyccnt, resource);
+            let r = f(t);
+            tracer.unlock(&crate::clock::Cyccnt, resource);
+            r
+        })
+    }
+}
+
+#[cfg(feature = "rtic")]
+impl<M: rtic::Mutex> TracedLock for M {}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::Sink;
+    use std::vec::Vec;
+
+    /// A buffer of limited capacity (the RTT ring buffer, never read).
+    pub struct Buffer(pub Vec<u8>, pub usize);
+
+    impl Sink for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> usize {
+            if self.0.len() + bytes.len() > self.1 {
+                0
+            } else {
+                self.0.extend_from_slice(bytes);
+                bytes.len()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mock::Buffer, *};
+    use crate::clock::mock::MockClock;
+    use std::vec::Vec;
+
+    fn bytes(tracer: &Tracer<Buffer>) -> Vec<u8> {
+        tracer.state.with(|s| s.sink.as_ref().unwrap().0.clone())
+    }
+
+    #[test]
+    fn records() {
+        let tracer = Tracer::new();
+        let clock = MockClock::new(0x1234_5678);
+        // ignored before init
+        tracer.enter(&clock, 0);
+        tracer.init(Buffer(Vec::new(), 1024));
+        tracer.task_name(2, "t3");
+        tracer.resource_name(1, "R2", 3);
+        tracer.enter(&clock, 2);
+        clock.advance(0x100);
+        tracer.lock(&clock, 1);
+        assert_eq!(
+            bytes(&tracer),
+            [
+                &[TASK_NAME, 2, 2, b't', b'3'][..],
+                &[RESOURCE_NAME, 1, 3, 2, b'R', b'2'],
+                &[ENTER, 2, 0x78, 0x56, 0x34, 0x12],
+                &[LOCK, 1, 0x78, 0x57, 0x34, 0x12],
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let tracer = Tracer::new();
+        let clock = MockClock::new(0);
+        tracer.init(Buffer(Vec::new(), 3 * RECORD));
+        for task in 0..5 {
+            tracer.pend(&clock, task);
+        }
+        assert_eq!(tracer.dropped(), 2);
+        // room for the overflow record and the event
+        tracer
+            .state
+            .with(|s| s.sink.as_mut().unwrap().1 = 5 * RECORD);
+        tracer.exit(&clock, 0);
+        assert_eq!(tracer.dropped(), 0);
+        let b = bytes(&tracer);
+        assert_eq!(
+            &b[3 * RECORD..],
+            &[OVERFLOW, 2, 0, 0, 0, 0, EXIT, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn capture() {
+        // the encoding of the capture decoded by the host tests (as
+        // generated by `host/fixtures`)
+        let tracer = Tracer::new();
+        tracer.init(Buffer(Vec::new(), 1024));
+        for (id, name) in ["t1", "t2", "t3"].iter().enumerate() {
+            tracer.task_name(id as u8, name);
+        }
+        tracer.resource_name(0, "R1", 2);
+        tracer.resource_name(1, "R2", 3);
+        let at = |t: u32| MockClock::new(t);
+        // t2 preempted by t3 while holding R1 (ceiling 2)
+        tracer.pend(&at(200_012), 1);
+        tracer.enter(&at(200_240), 1);
+        tracer.schedule(&at(200_250), 1);
+        tracer.lock(&at(210_250), 0);
+        tracer.lock(&at(212_270), 1);
+        tracer.unlock(&at(216_290), 1);
+        tracer.pend(&at(218_000), 2);
+        tracer.enter(&at(218_240), 2);
+        tracer.schedule(&at(218_250), 2);
+        tracer.lock(&at(228_250), 1);
+        tracer.unlock(&at(238_290), 1);
+        tracer.exit(&at(248_330), 2);
+        tracer.unlock(&at(250_000), 0);
+        tracer.exit(&at(260_000), 1);
+        let expected: &[u8] = include_bytes!("../../host/rtic_trace/captures/exam.bin");
+        assert_eq!(bytes(&tracer), expected);
+    }
+}